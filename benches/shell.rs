@@ -0,0 +1,11 @@
+extern crate rsh;
+
+use rsh::shell::Shell;
+
+fn main() {
+    let mut shell = Shell::new().expect("failed to create the shell");
+    let measurements = shell.run_benchmarks().expect("failed to run benchmarks");
+    for measurement in measurements {
+        println!("{}", measurement);
+    }
+}
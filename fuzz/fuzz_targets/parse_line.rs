@@ -0,0 +1,21 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate rsh;
+
+use rsh::shell::ast::parse_line;
+use rsh::shell::lexer::parse_line_lossless;
+
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+    let parsed = parse_line_lossless(&line);
+    // every word is made of at least one character of the input
+    assert!(parsed.words.len() <= line.chars().count());
+    // malformed lines are errors, the parser must not panic on them
+    if let Ok(list) = parse_line(&line) {
+        assert!(list.items.len() <= line.chars().count());
+        for (_, command) in &list.items {
+            command.first_simple();
+        }
+    }
+});
@@ -0,0 +1,6 @@
+extern crate libc;
+
+#[macro_use]
+pub mod messages;
+pub mod native;
+pub mod shell;
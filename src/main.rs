@@ -1,31 +1,43 @@
-extern crate libc;
+extern crate rsh;
 
-use shell::Shell;
+use std::process::exit;
 
-use native::write_exit;
+use rsh::shell::{Flow, Shell};
 
-pub mod native;
-pub mod shell;
+use rsh::native::{write_exit, write_to_file};
 
 fn main() {
     match Shell::new() {
-        Err(reason) => write_exit(4, &format!("{}", reason)),
+        Err(reason) => write_exit(4, &reason.diagnostic()),
         Ok(mut shell) => {
-            shell.on_start().ok();
-            if shell.argv.len() > 1 {
-                if let Err(reason) = shell.handle_arguments() {
-                    let error = format!("{}\n", reason);
-                    write_exit(5, &error);
-                }
+            let flow = if shell.reads_rc() {
+                shell.read_rc_files().unwrap_or_else(|reason| {
+                    // a broken rc file shouldn't prevent using the shell
+                    write_to_file(2, &reason.diagnostic()).ok();
+                    Flow::Continue
+                })
             } else {
-                if let Err(reason) = shell.interact() {
-                    let error = format!("{}\n", reason);
-                    write_exit(6, &error);
+                Flow::Continue
+            };
+            // rc files can call exit too
+            if flow == Flow::Continue {
+                if shell.options.command.is_some() || shell.options.script.is_some() {
+                    if let Err(reason) = shell.handle_arguments() {
+                        shell.remove_temp_dir();
+                        write_exit(5, &reason.diagnostic());
+                    }
+                } else {
+                    if let Err(reason) = shell.interact() {
+                        shell.remove_temp_dir();
+                        write_exit(6, &reason.diagnostic());
+                    }
                 }
             }
-            if shell.is_login {
-                shell.interpret_rc(".logout").ok();
+            if shell.is_login && shell.reads_rc() {
+                shell.on_logout().ok();
             }
+            shell.remove_temp_dir();
+            exit(shell.status);
         }
     }
 }
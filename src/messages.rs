@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::env::var;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use libc::O_RDONLY;
+
+use native::{close_file, open_file, read_file};
+
+/// Directory with catalogs if `RSH_LOCALEDIR` is not set
+const DEFAULT_LOCALE_DIR: &str = "/usr/share/rsh/locale";
+
+/// Translations of the current locale, loaded on the first message
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Translates the message and substitutes its `{}` placeholders with the arguments:
+/// `msg!("Unmatched {}", quote)`
+#[macro_export]
+macro_rules! msg {
+    ($id:expr) => {
+        $crate::messages::message($id, &[])
+    };
+    ($id:expr, $($argument:expr),+) => {
+        $crate::messages::message($id, &[$(&$argument),+])
+    };
+}
+
+/// Looks the English message up in the catalog of LC_MESSAGES locale and fills in the arguments.
+/// The English text is used as is if there is no catalog or translation.
+pub fn message(id: &str, arguments: &[&dyn Display]) -> String {
+    let catalog = CATALOG.get_or_init(load_catalog);
+    let text = catalog.get(id).map(String::as_str).unwrap_or(id);
+    fill(text, arguments)
+}
+
+/// Replaces `{}` placeholders with the arguments in order, extra placeholders are kept
+fn fill(text: &str, arguments: &[&dyn Display]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut arguments = arguments.iter();
+    let mut parts = text.split("{}");
+    if let Some(first) = parts.next() {
+        result.push_str(first);
+    }
+    for part in parts {
+        match arguments.next() {
+            Some(argument) => result.push_str(&argument.to_string()),
+            None => result.push_str("{}"),
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+/// Reads the catalog of the locale trying `ll_CC` and then `ll` names
+fn load_catalog() -> HashMap<String, String> {
+    let locale = match locale_name() {
+        Some(locale) => locale,
+        None => return HashMap::new(),
+    };
+    let dir = match var("RSH_LOCALEDIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(DEFAULT_LOCALE_DIR),
+    };
+    for name in candidates(&locale) {
+        if let Some(text) = read_catalog(&dir.join(format!("{}.msg", name))) {
+            return parse_catalog(&text);
+        }
+    }
+    HashMap::new()
+}
+
+/// Gets the locale of messages like setlocale(3): LC_ALL, LC_MESSAGES, then LANG.
+/// Returns None for the default C locale.
+fn locale_name() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| var(name).ok())
+        .find(|value| !value.is_empty())?;
+    if locale == "C" || locale == "POSIX" || locale.starts_with("C.") {
+        None
+    } else {
+        Some(locale)
+    }
+}
+
+/// Names of catalogs for the locale from the most specific one: ru_RU.UTF-8 gives ru_RU and ru
+fn candidates(locale: &str) -> Vec<&str> {
+    let name = locale.split(['.', '@']).next().unwrap_or(locale);
+    let mut result = vec![name];
+    if let Some(index) = name.find('_') {
+        result.push(&name[..index]);
+    }
+    result
+}
+
+/// Reads the whole catalog file, None if it is missing or unreadable
+fn read_catalog(path: &Path) -> Option<String> {
+    let fd = open_file(path, O_RDONLY, None).ok()?;
+    let text = read_file(fd).ok();
+    close_file(fd).ok();
+    text
+}
+
+/// Parses lines of `English message<TAB>translation`, empty lines and `#` comments are skipped
+fn parse_catalog(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(id, translation)| (unescape(id), unescape(translation)))
+        .collect()
+}
+
+/// Turns `\n` and `\t` sequences of the catalog into the characters
+fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n").replace("\\t", "\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_placeholders() {
+        assert_eq!(fill("Unmatched {}", &[&'"']), "Unmatched \"");
+        assert_eq!(fill("{} and {}", &[&1]), "1 and {}");
+        assert_eq!(fill("No arguments", &[]), "No arguments");
+    }
+
+    #[test]
+    fn locale_candidates() {
+        assert_eq!(candidates("ru_RU.UTF-8"), vec!["ru_RU", "ru"]);
+        assert_eq!(candidates("de"), vec!["de"]);
+        assert_eq!(candidates("sr_RS@latin"), vec!["sr_RS", "sr"]);
+    }
+
+    #[test]
+    fn parse_catalog_lines() {
+        let catalog = parse_catalog("# comment\n\nSyntax error\tСинтаксическая ошибка\nbroken\n");
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog["Syntax error"], "Синтаксическая ошибка");
+    }
+}
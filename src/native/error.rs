@@ -1,5 +1,5 @@
 use std::fmt::{Formatter, Display};
-use libc::{c_int, strerror, c_char};
+use libc::{c_int, strerror, c_char, E2BIG};
 
 use super::{write_exit, copy_string};
 
@@ -12,6 +12,77 @@ pub enum Error {
     InvalidCString,
     InvalidUnicode,
     NotFound,
+    DirStackEmpty,
+    NoOtherDirectory,
+    SyntaxError,
+    UnmatchedQuote(char),
+    MissingArgument(&'static str),
+    NotLoginShell,
+    TooManyWords(usize),
+    LineTooLong(usize),
+    MissingRedirectName,
+    NullCommand,
+    NoSuchJob,
+    /// `reattach` needs a job started with `bg --detach`
+    NotDetached,
+    SuspendLoginShell,
+    /// `suspend` needs an interactive shell which owns the terminal
+    SuspendWithoutTerminal,
+    NoSuchTheme,
+    /// `bindkey` was given an unknown editor function
+    NoSuchFunction(String),
+    /// A program can't be run because the number of its arguments, their size in bytes and
+    /// the size of the environment exceed the limit of the system, the last number
+    ArgumentListTooLong(usize, usize, usize, usize),
+    /// An argument or a variable of the environment is longer than the system allows, its length
+    ArgumentTooLong(usize),
+    /// The terminal lacks a feature required by the prompt theme
+    MissingFeature(&'static str),
+    /// No input has come in time
+    TimedOut,
+    /// An rc file has run longer than the number of seconds
+    RcTimedOut(u32),
+    /// A watched signal has arrived while waiting for input
+    Interrupted,
+    /// Ctrl-C was pressed while reading the rest of an unfinished command
+    Aborted,
+    /// An option needs the Cargo feature which this build lacks
+    NotBuiltIn(&'static str),
+    /// The tag of the encrypted file doesn't match: a wrong passphrase or a damaged file
+    DecryptionFailed,
+    DivisionByZero,
+    /// A filename pattern has matched nothing
+    NoMatch,
+    /// A history reference like `!ls` has found no command, its text after `!`
+    EventNotFound(String),
+    /// A substitution modifier like `:s/old/new/` has found nothing to replace
+    ModifierFailed,
+    /// A word designator like `!!:3` selects words which the command doesn't have
+    BadWordDesignator,
+    NoMoreWords,
+    /// `~name` names a user who isn't in passwd
+    UnknownUser(String),
+    ReadOnlyVariable(&'static str),
+    /// The URL isn't an `http://` one, there is no TLS for `https://`
+    UnsupportedUrl,
+    /// The server has sent something which isn't an HTTP response
+    BadResponse,
+    HttpStatus(u16),
+    /// The response is longer than the limit in bytes
+    ResponseTooLarge(usize),
+    TooManyRedirects,
+    /// getaddrinfo(3) has failed, the text is from gai_strerror(3)
+    AddressLookup(String),
+    /// No program with the name was found in PATH
+    CommandNotFound(String),
+    /// The error happened to the word, e.g. a file name, it is printed before the error
+    Context(String, Box<Error>),
+    /// The error happened on the line of the script
+    Located {
+        file: String,
+        line: usize,
+        reason: Box<Error>,
+    },
     Errno(Errno),
 }
 
@@ -19,16 +90,118 @@ impl Error {
     pub fn from_errno() -> Self {
         Error::Errno(Errno::last())
     }
+
+    /// Checks whether the error is the errno value, e.g. EINTR
+    pub fn is_errno(&self, code: c_int) -> bool {
+        match self {
+            Error::Errno(errno) => errno.code == code,
+            Error::Context(_, reason) | Error::Located { reason, .. } => reason.is_errno(code),
+            _ => false,
+        }
+    }
+
+    /// Checks whether a program can't be run because its arguments or environment are too big
+    pub fn is_too_long(&self) -> bool {
+        match self {
+            Error::ArgumentListTooLong(..) | Error::ArgumentTooLong(_) => true,
+            reason => reason.is_errno(E2BIG),
+        }
+    }
+
+    /// Tells which word the error is about, like `rsh: /etc/foo: Permission denied`
+    pub fn about<W: Display>(self, word: W) -> Self {
+        Error::Context(word.to_string(), Box::new(self))
+    }
+
+    /// Tells where in the script the error happened, the innermost location is kept
+    /// if the error comes from a script interpreted by another one
+    pub fn at(self, file: String, line: usize) -> Self {
+        match self {
+            Error::Located { .. } => self,
+            reason => Error::Located {
+                file,
+                line,
+                reason: Box::new(reason),
+            },
+        }
+    }
+
+    /// Gets the script and the line number if the error happened in a script
+    pub fn location(&self) -> Option<(&str, usize)> {
+        match self {
+            Error::Located { file, line, .. } => Some((file, *line)),
+            _ => None,
+        }
+    }
+
+    /// Formats the error as a line printed to stderr
+    pub fn diagnostic(&self) -> String {
+        format!("rsh: {}\n", self)
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
-        match self {
-            Error::InvalidCString => write!(formatter, "Fail to produce valid C string"),
-            Error::InvalidUnicode => write!(formatter, "Fail to produce valid Unicode string"),
-            Error::NotFound => write!(formatter, "Value was not found"),
-            Error::Errno(reason) => write!(formatter, "{}", reason),
-        }
+        let text = match self {
+            Error::InvalidCString => msg!("Fail to produce valid C string"),
+            Error::InvalidUnicode => msg!("Fail to produce valid Unicode string"),
+            Error::NotFound => msg!("Value was not found"),
+            Error::DirStackEmpty => msg!("Directory stack empty"),
+            Error::NoOtherDirectory => msg!("No other directory"),
+            Error::SyntaxError => msg!("Syntax error"),
+            Error::UnmatchedQuote(quote) => msg!("Unmatched {}", quote),
+            Error::MissingArgument(flag) => msg!("{}: Missing argument", flag),
+            Error::NotLoginShell => msg!("Not login shell"),
+            Error::TooManyWords(limit) => msg!("Too many words, the limit is {}", limit),
+            Error::LineTooLong(limit) => msg!("Line too long, the limit is {} bytes", limit),
+            Error::MissingRedirectName => msg!("Missing name for redirect"),
+            Error::NullCommand => msg!("Invalid null command"),
+            Error::NoSuchJob => msg!("No such job"),
+            Error::NotDetached => msg!("The job isn't detached"),
+            Error::SuspendLoginShell => msg!("Can't suspend a login shell"),
+            Error::SuspendWithoutTerminal => msg!("Can't suspend a shell without job control"),
+            Error::NoSuchTheme => msg!("No such theme"),
+            Error::NoSuchFunction(ref name) => msg!("{}: No such editor function", name),
+            Error::ArgumentListTooLong(count, bytes, environment, limit) => msg!(
+                "Argument list too long: {} arguments of {} bytes and {} bytes of the environment \
+                 exceed the limit of {} bytes, see `set argbatch`",
+                count,
+                bytes,
+                environment,
+                limit
+            ),
+            Error::ArgumentTooLong(length) => {
+                msg!("An argument of {} bytes is longer than the system allows", length)
+            }
+            Error::MissingFeature(feature) => msg!("The terminal doesn't support {}", feature),
+            Error::TimedOut => msg!("Timed out"),
+            Error::RcTimedOut(seconds) => {
+                msg!("Still running after {} seconds, the rest of the file is skipped", seconds)
+            }
+            Error::Interrupted => msg!("Interrupted"),
+            Error::Aborted => msg!("Aborted"),
+            Error::NotBuiltIn(feature) => msg!("rsh is built without the {} feature", feature),
+            Error::DecryptionFailed => msg!("Wrong passphrase or damaged file"),
+            Error::DivisionByZero => msg!("Division by zero"),
+            Error::NoMatch => msg!("No match"),
+            Error::EventNotFound(event) => msg!("{}: Event not found", event),
+            Error::ModifierFailed => msg!("Modifier failed"),
+            Error::BadWordDesignator => msg!("Bad word designator"),
+            Error::NoMoreWords => msg!("No more words"),
+            Error::UnknownUser(name) => msg!("Unknown user: {}", name),
+            Error::ReadOnlyVariable(name) => msg!("{}: Read-only variable", name),
+            Error::UnsupportedUrl => msg!("Only http:// URLs are supported"),
+            Error::BadResponse => msg!("Bad response"),
+            Error::HttpStatus(status) => msg!("HTTP status {}", status),
+            Error::ResponseTooLarge(limit) => msg!("Response is longer than {} bytes", limit),
+            Error::TooManyRedirects => msg!("Too many redirects"),
+            Error::AddressLookup(text) => return write!(formatter, "{}", text),
+            Error::CommandNotFound(name) => msg!("{}: Command not found.", name),
+            Error::Context(word, reason) => return write!(formatter, "{}: {}", word, reason),
+            Error::Located { file, line, reason } => msg!("{}: line {}: {}", file, line, reason),
+            Error::Errno(reason) => return write!(formatter, "{}", reason),
+        };
+        write!(formatter, "{}", text)
     }
 }
 
@@ -56,16 +229,20 @@ impl Errno {
         if errno_ptr.is_null() {
             write_exit(1, "errno location is unknown");
         } else {
-            let code: c_int = unsafe { *errno_ptr };
-            let text: *const c_char = unsafe { strerror(code) };
-            if text.is_null() {
-                write_exit(2, "errno code is unknown");
+            Errno::from_code(unsafe { *errno_ptr })
+        }
+    }
+
+    /// Wraps the error number returned by functions which don't set errno
+    pub fn from_code(code: c_int) -> Self {
+        let text: *const c_char = unsafe { strerror(code) };
+        if text.is_null() {
+            write_exit(2, "errno code is unknown");
+        } else {
+            if let Ok(text) = unsafe { copy_string(text) } {
+                Errno { code, text }
             } else {
-                if let Ok(text) = unsafe { copy_string(text) } {
-                    Errno { code, text }
-                } else {
-                    write_exit(3, "errno string is incorrect C string");
-                }
+                write_exit(3, "errno string is incorrect C string");
             }
         }
     }
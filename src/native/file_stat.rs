@@ -1,19 +1,20 @@
-use std::path::PathBuf;
+use std::path::Path;
 
-use libc::{stat, c_int, c_char};
+use libc::{stat, lstat, c_int, c_char, S_IFIFO, S_IFMT, S_IFREG, S_IRGRP, S_IROTH, S_IRUSR,
+           S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR};
 
 use super::error::{Result, Error};
 use super::native_path;
-use super::users::{UserId, GroupId};
+use super::users::{get_gid, get_groups, get_uid, UserId, GroupId};
 
 /// Calls stat(2) on the file to determine an owner-user
-pub fn get_file_uid(path: &PathBuf) -> Result<UserId> {
+pub fn get_file_uid(path: &Path) -> Result<UserId> {
     let stat = unsafe { stat_file(path)? };
     Ok(stat.st_uid)
 }
 
 /// Calls stat(2) on the file to determine an owner-group
-pub fn get_file_gid(path: &PathBuf) -> Result<GroupId> {
+pub fn get_file_gid(path: &Path) -> Result<GroupId> {
     let stat = unsafe { stat_file(path)? };
     Ok(stat.st_gid)
 }
@@ -21,13 +22,82 @@ pub fn get_file_gid(path: &PathBuf) -> Result<GroupId> {
 pub type FileMode = u32;
 
 /// Calls stat(2) on the file to determine rights on the file
-pub fn get_file_mode(path: &PathBuf) -> Result<FileMode> {
+pub fn get_file_mode(path: &Path) -> Result<FileMode> {
     let stat: stat = unsafe { stat_file(path)? };
     Ok(stat.st_mode)
 }
 
+/// Calls lstat(2) on the file to determine its type and rights, a symlink isn't followed
+pub fn get_link_mode(path: &Path) -> Result<FileMode> {
+    let path = native_path(path)?;
+    let mut buf: stat = unsafe { std::mem::zeroed() };
+    let status: c_int = unsafe { lstat(path.as_ptr(), &mut buf) };
+    errno!(status, buf.st_mode)
+}
+
+/// Checks whether the path is a named pipe, following symlinks
+pub fn is_fifo(path: &Path) -> bool {
+    get_file_mode(path).is_ok_and(|mode| mode & S_IFMT == S_IFIFO)
+}
+
+/// Kind of access to a file checked by `may_access`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Checks whether the path is a regular file which the current user may execute
+pub fn is_executable_file(path: &Path) -> bool {
+    let regular = get_file_mode(path).is_ok_and(|mode| mode & S_IFMT == S_IFREG);
+    regular && may_access(path, Access::Execute)
+}
+
+/// Checks whether the current user may access the file, following symlinks.
+/// Like the kernel does it, the owner bits are used for the owner, the group bits for members
+/// of the group and the others bits for the rest. Root may read and write anything
+/// and needs any of the execute bits.
+pub fn may_access(path: &Path, access: Access) -> bool {
+    let stat: stat = match unsafe { stat_file(path) } {
+        Ok(stat) => stat,
+        Err(_) => return false,
+    };
+    let (owner, group, others) = match access {
+        Access::Read => (S_IRUSR, S_IRGRP, S_IROTH),
+        Access::Write => (S_IWUSR, S_IWGRP, S_IWOTH),
+        Access::Execute => (S_IXUSR, S_IXGRP, S_IXOTH),
+    };
+    let uid = get_uid();
+    let bit = if uid == 0 {
+        if access != Access::Execute {
+            return true;
+        }
+        owner | group | others
+    } else if stat.st_uid == uid {
+        owner
+    } else if stat.st_gid == get_gid() || get_groups().contains(&stat.st_gid) {
+        group
+    } else {
+        others
+    };
+    stat.st_mode & bit != 0
+}
+
+/// Calls stat(2) on the file to determine its size in bytes
+pub fn get_file_size(path: &Path) -> Result<u64> {
+    let stat: stat = unsafe { stat_file(path)? };
+    Ok(stat.st_size as u64)
+}
+
+/// Calls stat(2) on the file to determine when it was modified, seconds and nanoseconds
+pub fn get_modification_time(path: &Path) -> Result<(i64, i64)> {
+    let stat: stat = unsafe { stat_file(path)? };
+    Ok((stat.st_mtime as i64, stat.st_mtime_nsec as i64))
+}
+
 /// Wraps result of stat(2) call
-unsafe fn stat_file(path: &PathBuf) -> Result<stat> {
+unsafe fn stat_file(path: &Path) -> Result<stat> {
     let path = native_path(path)?;
     let mut buf: stat = std::mem::zeroed();
     let status: c_int = stat(path.into_raw() as *const c_char, &mut buf);
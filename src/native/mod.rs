@@ -22,18 +22,39 @@ macro_rules! unwrap_or_return {
     };
 }
 
-use std::ffi::CString;
-use std::os::unix::io::RawFd;
-use std::path::PathBuf;
-use std::process::exit;
-use std::ptr::null;
+use std::ffi::{CStr, CString};
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, exit};
+use std::cmp::Ordering;
+use std::ptr::{null, null_mut};
+use std::sync::Once;
+use std::time::{Duration, Instant};
 use std::iter::once;
+use std::mem::size_of;
 
 pub mod file_stat;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod procfs;
+pub mod signal;
+pub mod term;
+#[cfg(test)]
+pub mod test_dir;
 pub mod users;
+pub mod watch;
 
-use libc::{c_char, c_int, c_void, getcwd, gethostname, open, read, ssize_t, strlen, write, execve,
-           fork, waitpid, dup2, PATH_MAX, strcpy};
+use libc::{c_char, c_int, c_void, chdir, getcwd, gethostname, open, read, ssize_t, strlen, write,
+           execve, fork, waitpid, dup2, pid_t, PATH_MAX, strcpy, WEXITSTATUS, WIFSIGNALED,
+           WTERMSIG, close, off_t, pread, rename, unlink, fsync, pipe, O_WRONLY, O_CREAT,
+           O_TRUNC, setpgid, EINTR, ECHILD, WNOHANG, WUNTRACED, WCONTINUED, WIFSTOPPED,
+           WSTOPSIG, WIFCONTINUED, getpgrp, getpid, nfds_t, poll, pollfd, POLLIN, setlocale,
+           strcoll, LC_COLLATE, mkdtemp, mkstemp, mkfifo, fcntl, F_GETFL, F_SETFL, F_GETFD,
+           F_SETFD, FD_CLOEXEC, O_NONBLOCK, EINVAL, WCOREDUMP, SIGINT, SIGKILL, SIGPIPE, dup,
+           ENOEXEC, accept, sysconf, _SC_ARG_MAX, opendir, readdir, closedir, F_DUPFD_CLOEXEC};
+#[cfg(target_os = "linux")]
+use libc::posix_spawn;
 
 /// Gets the name of the host using gethostname() from libc.
 /// Returns None in case of error in gethostname() or in String::from_utf8().
@@ -54,7 +75,7 @@ pub fn get_hostname() -> Result<String> {
 /// Opens the file which is located on the provided path with the provided flags.
 /// More information about the flags is in open(2).
 /// These constants are available in libc crate.
-pub fn open_file(path: &PathBuf, flags: i32, mode: Option<u32>) -> Result<RawFd> {
+pub fn open_file(path: &Path, flags: i32, mode: Option<u32>) -> Result<RawFd> {
     let path = native_path(path)?;
     let status: c_int = match mode {
         Some(mode) => unsafe { open(path.into_raw() as *const c_char, flags, mode) },
@@ -63,7 +84,74 @@ pub fn open_file(path: &PathBuf, flags: i32, mode: Option<u32>) -> Result<RawFd>
     errno!(status, status)
 }
 
-//// Writes text to the file and returns non-negative number in the case of success.
+/// Creates a directory with a unique name using mkdtemp(3),
+/// the template must end with `XXXXXX` which is replaced to make the name
+pub fn make_temp_dir(template: &Path) -> Result<PathBuf> {
+    let mut buf = native_path(template)?.into_bytes_with_nul();
+    let result = unsafe { mkdtemp(buf.as_mut_ptr() as *mut c_char) };
+    if result.is_null() {
+        return Err(Error::from_errno());
+    }
+    buf.pop();
+    read_buf(buf).map(PathBuf::from)
+}
+
+/// Creates and opens a file with a unique name using mkstemp(3), see `make_temp_dir`
+pub fn make_temp_file(template: &Path) -> Result<(RawFd, PathBuf)> {
+    let mut buf = native_path(template)?.into_bytes_with_nul();
+    let fd = unsafe { mkstemp(buf.as_mut_ptr() as *mut c_char) };
+    if fd < 0 {
+        return Err(Error::from_errno());
+    }
+    buf.pop();
+    read_buf(buf).map(|path| (fd, PathBuf::from(path)))
+}
+
+/// Creates a named pipe with mkfifo(3), the umask is applied to the mode
+pub fn make_fifo(path: &Path, mode: u32) -> Result<()> {
+    let path = native_path(path)?;
+    let status = unsafe { mkfifo(path.as_ptr(), mode) };
+    errno!(status, ())
+}
+
+/// Connects to the Unix domain stream socket at the path, returns the connected descriptor
+pub fn connect_socket(path: &Path) -> Result<RawFd> {
+    match UnixStream::connect(path) {
+        Ok(stream) => Ok(stream.into_raw_fd()),
+        Err(reason) => Err(io_error(reason)),
+    }
+}
+
+/// Creates a Unix domain stream socket at the path listening for connections
+pub fn listen_socket(path: &Path) -> Result<RawFd> {
+    match UnixListener::bind(path) {
+        Ok(listener) => Ok(listener.into_raw_fd()),
+        Err(reason) => Err(io_error(reason)),
+    }
+}
+
+/// Accepts a connection to the listening socket using accept(2)
+pub fn accept_connection(fd: RawFd) -> Result<RawFd> {
+    let connection = unsafe { accept(fd, null_mut(), null_mut()) };
+    errno!(connection, connection)
+}
+
+/// Converts an error of the standard library to the errno one
+fn io_error(reason: std::io::Error) -> Error {
+    Error::Errno(Errno::from_code(reason.raw_os_error().unwrap_or(EINVAL)))
+}
+
+/// Clears O_NONBLOCK of the descriptor, so reads and writes wait again
+pub fn set_blocking(fd: RawFd) -> Result<()> {
+    let flags = unsafe { fcntl(fd, F_GETFL) };
+    if flags < 0 {
+        return Err(Error::from_errno());
+    }
+    let status = unsafe { fcntl(fd, F_SETFL, flags & !O_NONBLOCK) };
+    errno!(status, ())
+}
+
+/// Writes text to the file and returns non-negative number in the case of success.
 pub fn write_to_file(fd: RawFd, text: &str) -> Result<isize> {
     let len = text.len();
     let text = native_string(text)?;
@@ -71,6 +159,22 @@ pub fn write_to_file(fd: RawFd, text: &str) -> Result<isize> {
     errno!(status, status)
 }
 
+/// Writes all the bytes to the file, unlike `write_to_file` they may contain zeros
+pub fn write_all(fd: RawFd, mut data: &[u8]) -> Result<()> {
+    while !data.is_empty() {
+        let status = unsafe { write(fd, data.as_ptr() as *const c_void, data.len()) };
+        if status < 0 {
+            let error = Error::from_errno();
+            if error.is_errno(EINTR) {
+                continue;
+            }
+            return Err(error);
+        }
+        data = &data[(status as usize)..];
+    }
+    Ok(())
+}
+
 /// Gets current working dir from the system
 pub fn get_current_dir() -> Result<PathBuf> {
     let mut buf = vec![0; PATH_MAX as usize];
@@ -87,8 +191,102 @@ pub fn get_current_dir() -> Result<PathBuf> {
     }
 }
 
+/// Reads up to len bytes starting at the offset using pread(2)
+pub fn read_at(fd: RawFd, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    let status = unsafe { pread(fd, buf.as_mut_ptr() as *mut c_void, len, offset as off_t) };
+    if status < 0 {
+        Err(Error::from_errno())
+    } else {
+        buf.truncate(status as usize);
+        Ok(buf)
+    }
+}
+
+/// Reads whatever is available into the buffer using read(2), 0 means the end of file
+pub fn read_chunk(fd: RawFd, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        let status = unsafe { read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if status >= 0 {
+            return Ok(status as usize);
+        }
+        let error = Error::from_errno();
+        if !error.is_errno(EINTR) {
+            return Err(error);
+        }
+    }
+}
+
+/// Makes another descriptor of the same open file using dup(2)
+pub fn duplicate_fd(fd: RawFd) -> Result<RawFd> {
+    let status = unsafe { dup(fd) };
+    errno!(status, status)
+}
+
+/// Copies the descriptor to a free one not below 10 using fcntl(2), the copy is closed
+/// on exec and keeps the file while the descriptor itself is redirected
+pub fn save_fd(fd: RawFd) -> Result<RawFd> {
+    let status = unsafe { fcntl(fd, F_DUPFD_CLOEXEC, 10) };
+    errno!(status, status)
+}
+
+/// Closes the file descriptor using close(2)
+pub fn close_file(fd: RawFd) -> Result<()> {
+    let status = unsafe { close(fd) };
+    errno!(status, ())
+}
+
+/// Renames the file using rename(2), an existing file at the new path is replaced atomically
+pub fn rename_file(from: &Path, to: &Path) -> Result<()> {
+    let from = native_path(from)?;
+    let to = native_path(to)?;
+    let status = unsafe { rename(from.as_ptr(), to.as_ptr()) };
+    errno!(status, ())
+}
+
+/// Removes the file using unlink(2)
+pub fn remove_file(path: &Path) -> Result<()> {
+    let path = native_path(path)?;
+    let status = unsafe { unlink(path.as_ptr()) };
+    errno!(status, ())
+}
+
+/// Writes the data to a temporary file next to the path, flushes it with fsync(2)
+/// and renames it over the path. After a crash the file has either the old or the new contents.
+pub fn write_file_atomically(path: &Path, data: &[u8], mode: u32) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", process::id()));
+    let temporary = PathBuf::from(temporary);
+    let fd = open_file(&temporary, O_WRONLY | O_CREAT | O_TRUNC, Some(mode))?;
+    let written = write_all(fd, data).and_then(|_| sync_file(fd));
+    let closed = close_file(fd);
+    let result = written.and(closed).and_then(|_| rename_file(&temporary, path));
+    if result.is_err() {
+        remove_file(&temporary).ok();
+    }
+    result
+}
+
+/// Flushes the contents of the file to the disk using fsync(2)
+fn sync_file(fd: RawFd) -> Result<()> {
+    let status = unsafe { fsync(fd) };
+    errno!(status, ())
+}
+
+/// Changes the current working directory of the process using chdir(2)
+pub fn change_dir(path: &Path) -> Result<()> {
+    let path = native_path(path)?;
+    let status: c_int = unsafe { chdir(path.as_ptr()) };
+    errno!(status, ())
+}
+
 /// Reads file contents to a String
 pub fn read_file(fdi: RawFd) -> Result<String> {
+    read_buf(read_bytes(fdi)?)
+}
+
+/// Reads file contents as bytes which don't have to be text
+pub fn read_bytes(fdi: RawFd) -> Result<Vec<u8>> {
     let mut result = Vec::new();
     let mut buf = vec![0; 4096]; // like in csh
     let mut status;
@@ -104,30 +302,220 @@ pub fn read_file(fdi: RawFd) -> Result<String> {
     if status < 0 {
         Err(Error::Errno(Errno::last()))
     } else {
-        read_buf(result)
+        Ok(result)
     }
 }
 
-/// Reads a line (chars till '\n' or EOF) from the provided file
-pub fn read_line(fdi: RawFd) -> Result<String> {
-    let mut result = Vec::new();
-    let mut buf = [0; 1];
-    let mut status;
+/// Size of the buffer used by Reader, like in csh
+const READER_BUFFER_SIZE: usize = 4096;
+
+/// Reads lines from a file descriptor in chunks instead of a read(2) call per character.
+/// Bytes read beyond the current line stay in the buffer, so processes which share
+/// the descriptor won't see them.
+pub struct Reader {
+    fd: RawFd,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// The line being read, kept if the read is interrupted by a signal
+    line: Vec<u8>,
+    too_long: bool,
+    started: bool,
+    /// How long to wait for input before failing with TimedOut, None means forever
+    pub timeout: Option<Duration>,
+    /// Fail with Interrupted when a watched signal arrives while waiting for input
+    pub wake_on_signals: bool,
+}
+
+impl Reader {
+    /// Creates a reader of the file descriptor, the descriptor is not closed by it
+    pub fn new(fd: RawFd) -> Self {
+        Reader {
+            fd,
+            buf: vec![0; READER_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            line: Vec::new(),
+            too_long: false,
+            started: false,
+            timeout: None,
+            wake_on_signals: false,
+        }
+    }
+
+    /// Reads a line (chars till '\n' or EOF) without the line break.
+    /// Returns None if the end of file is reached before anything is read.
+    /// Lines longer than the limit are consumed entirely but reported as an error.
+    /// If a signal interrupts the read, the next call goes on with the same line.
+    pub fn read_line(&mut self, limit: usize) -> Result<Option<String>> {
+        loop {
+            if self.start == self.end && !self.fill()? {
+                break;
+            }
+            self.started = true;
+            let chunk = &self.buf[self.start..self.end];
+            let (line, found) = match chunk.iter().position(|c| *c == b'\n') {
+                Some(index) => (&chunk[..index], true),
+                None => (chunk, false),
+            };
+            let free = limit.saturating_sub(self.line.len());
+            self.too_long |= line.len() > free;
+            self.line.extend_from_slice(&line[..line.len().min(free)]);
+            self.start += line.len() + if found { 1 } else { 0 };
+            if found {
+                break;
+            }
+        }
+        let line = std::mem::take(&mut self.line);
+        let too_long = std::mem::replace(&mut self.too_long, false);
+        if !std::mem::replace(&mut self.started, false) {
+            Ok(None)
+        } else if too_long {
+            Err(Error::LineTooLong(limit))
+        } else {
+            read_buf(line).map(Some)
+        }
+    }
+
+    /// Forgets the part of the line read before an interruption
+    pub fn discard_line(&mut self) {
+        self.line.clear();
+        self.too_long = false;
+        self.started = false;
+    }
+
+    /// Reads the next chunk into the buffer, returns false at the end of file
+    fn fill(&mut self) -> Result<bool> {
+        if self.wake_on_signals || self.timeout.is_some() {
+            match wait_input(self.fd, self.timeout)? {
+                Wakeup::Input => {}
+                Wakeup::Signal => return Err(Error::Interrupted),
+                Wakeup::Timeout => return Err(Error::TimedOut),
+            }
+        }
+        let status = unsafe { read(self.fd, self.buf.as_mut_ptr() as *mut c_void, self.buf.len()) };
+        if status < 0 {
+            return Err(Error::from_errno());
+        }
+        self.start = 0;
+        self.end = status as usize;
+        Ok(status > 0)
+    }
+}
+
+/// What has ended waiting for input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wakeup {
+    Input,
+    /// A signal handler has written to the self-pipe, see `signal::open_wakeup_pipe`
+    Signal,
+    Timeout,
+}
+
+/// Waits with poll(2) until the descriptor has input, a watched signal arrives
+/// or the time is over. None means waiting without a time limit.
+pub fn wait_input(fd: RawFd, timeout: Option<Duration>) -> Result<Wakeup> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut requests = vec![pollfd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    }];
+    if let Some(wakeup) = signal::wakeup_fd() {
+        requests.push(pollfd {
+            fd: wakeup,
+            events: POLLIN,
+            revents: 0,
+        });
+    }
     loop {
-        status = unsafe { read(fdi, buf.as_mut_ptr() as *mut c_void, 1) };
-        let c = buf[0];
-        if status <= 0 || c == b'\n' {
+        let milliseconds = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                left.as_millis().min(c_int::MAX as u128) as c_int
+            }
+            None => -1,
+        };
+        let status = unsafe { poll(requests.as_mut_ptr(), requests.len() as nfds_t, milliseconds) };
+        match errno!(status, status) {
+            // the handler has written to the pipe, the next poll sees it
+            Err(ref reason) if reason.is_errno(EINTR) => continue,
+            Err(reason) => return Err(reason),
+            Ok(0) => return Ok(Wakeup::Timeout),
+            Ok(_) => {}
+        }
+        if requests.len() > 1 && requests[1].revents != 0 {
+            signal::drain_wakeups();
+            return Ok(Wakeup::Signal);
+        }
+        return Ok(Wakeup::Input);
+    }
+}
+
+/// Waits with poll(2) until some of the descriptors have input or are closed,
+/// returns which of them are ready. Interrupted waits are repeated.
+pub fn wait_inputs(fds: &[RawFd]) -> Result<Vec<bool>> {
+    let mut requests: Vec<pollfd> = fds
+        .iter()
+        .map(|fd| pollfd {
+            fd: *fd,
+            events: POLLIN,
+            revents: 0,
+        })
+        .collect();
+    loop {
+        let status = unsafe { poll(requests.as_mut_ptr(), requests.len() as nfds_t, -1) };
+        match errno!(status, status) {
+            Err(ref reason) if reason.is_errno(EINTR) => continue,
+            Err(reason) => return Err(reason),
+            Ok(_) => return Ok(requests.iter().map(|request| request.revents != 0).collect()),
+        }
+    }
+}
+
+/// Reads the names of the entries of the directory using readdir(3), `.` and `..` are skipped
+pub fn read_dir_names(path: &Path) -> Result<Vec<String>> {
+    let path = native_path(path)?;
+    let dir = unsafe { opendir(path.as_ptr()) };
+    if dir.is_null() {
+        return Err(Error::from_errno());
+    }
+    let mut names = Vec::new();
+    loop {
+        let entry = unsafe { readdir(dir) };
+        if entry.is_null() {
             break;
         }
-        result.push(c);
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_string_lossy();
+        if name != "." && name != ".." {
+            names.push(name.into_owned());
+        }
     }
-    if status < 0 {
-        Err(Error::from_errno())
-    } else {
-        read_buf(result)
+    unsafe { closedir(dir) };
+    Ok(names)
+}
+
+/// Compares the strings according to LC_COLLATE locale using strcoll(3).
+/// The locale is taken from the environment on the first call.
+pub fn collate(left: &str, right: &str) -> Ordering {
+    static LOCALE: Once = Once::new();
+    LOCALE.call_once(|| unsafe {
+        setlocale(LC_COLLATE, b"\0".as_ptr() as *const c_char);
+    });
+    match (CString::new(left), CString::new(right)) {
+        (Ok(left), Ok(right)) => unsafe { strcoll(left.as_ptr(), right.as_ptr()) }.cmp(&0),
+        // strings with NUL bytes can't be passed to C
+        _ => left.cmp(right),
     }
 }
 
+/// Reads a single byte, returns None at the end of file
+pub fn read_byte(fd: RawFd) -> Result<Option<u8>> {
+    let mut byte = 0u8;
+    let status = unsafe { read(fd, &mut byte as *mut u8 as *mut c_void, 1) };
+    errno!(status, if status == 0 { None } else { Some(byte) })
+}
+
 pub type ExitCode = i32;
 
 /// Writes the provided text to stderr and exits with the provided exit code.
@@ -138,10 +526,16 @@ pub fn write_exit(exit_code: ExitCode, text: &str) -> ! {
 
 /// Makes a copy of a string which was allocated by the system.
 /// Otherwise Rust tries to manage the memory of the string which leads to segfault.
+///
+/// # Safety
+///
+/// The pointer must point to a valid null terminated C string.
 pub unsafe fn copy_string(ptr: *const c_char) -> Result<String> {
     let len = strlen(ptr);
-    let mut buf = vec![0; len];
+    // strcpy copies the terminating null too
+    let mut buf = vec![0; len + 1];
     strcpy(buf.as_mut_ptr() as *mut c_char, ptr);
+    buf.truncate(len);
     read_buf(buf)
 }
 
@@ -161,53 +555,537 @@ fn native_string(string: &str) -> Result<CString> {
 }
 
 /// Creates a null terminated string out of an PathBuf instance
-pub fn native_path(path: &PathBuf) -> Result<CString> {
+pub fn native_path(path: &Path) -> Result<CString> {
     let path = path.to_str().ok_or(Error::InvalidUnicode)?;
     native_string(path)
 }
 
-/// Forks the current process and calls the provided function
+/// Forks the current process and calls the provided function.
+/// Returns the exit code of the child or 128 + signal number if it was killed by a signal.
 pub fn fork_process<F: FnOnce() -> Error>(actions: F) -> Result<i32> {
     match unsafe { fork() } {
-        0 => Err(actions()), // if we returned from actions, something went wrong
+        0 => exit_child(actions()),
+        -1 => Err(Error::from_errno()),
+        pid => wait_process(pid),
+    }
+}
+
+/// Forks the current process and lets both go on, returns 0 in the child
+/// and the pid of the child in the parent like fork(2) itself
+pub fn fork_current() -> Result<pid_t> {
+    let pid = unsafe { fork() };
+    errno!(pid, pid)
+}
+
+/// Exit code of a child which couldn't execute the program, like in sh
+pub const EXEC_FAILED: ExitCode = 127;
+
+/// Ends a forked child whose actions have returned, which means something went wrong.
+/// The child must not go back to the code of the shell, e.g. to read commands.
+fn exit_child(reason: Error) -> ! {
+    write_exit(EXEC_FAILED, &reason.diagnostic())
+}
+
+/// Forks the current process and calls the provided function in the child.
+/// Returns the pid of the child without waiting for it.
+pub fn start_process<F: FnOnce() -> Error>(actions: F) -> Result<pid_t> {
+    match unsafe { fork() } {
+        0 => exit_child(actions()),
         -1 => Err(Error::from_errno()),
-        _ => {
-            let mut status = 0;
-            unsafe {
-                waitpid(-1, &mut status, 0);
+        pid => Ok(pid),
+    }
+}
+
+/// Creates an anonymous pipe, returns its read and write ends
+pub fn create_pipe() -> Result<(RawFd, RawFd)> {
+    let mut fds = [0; 2];
+    let status = unsafe { pipe(fds.as_mut_ptr()) };
+    errno!(status, (fds[0], fds[1]))
+}
+
+/// Sets or clears FD_CLOEXEC of the descriptor, one without it is inherited
+/// by the programs which the process executes
+pub fn set_close_on_exec(fd: RawFd, close: bool) -> Result<()> {
+    let flags = unsafe { fcntl(fd, F_GETFD) };
+    if flags < 0 {
+        return Err(Error::from_errno());
+    }
+    let flags = if close { flags | FD_CLOEXEC } else { flags & !FD_CLOEXEC };
+    let status = unsafe { fcntl(fd, F_SETFD, flags) };
+    errno!(status, ())
+}
+
+/// An anonymous pipe whose one end is given to a program by its `/dev/fd/N` name
+/// like a file, while the shell reads or writes the other end
+#[derive(Debug)]
+pub struct FdPipe {
+    /// The `/dev/fd/N` name of the end inherited by the program
+    pub path: PathBuf,
+    /// The end inherited by the program, the shell closes it once the program is started
+    pub shared: RawFd,
+    /// The end of the shell which programs don't inherit
+    pub own: RawFd,
+}
+
+/// Creates a pipe for a program which reads the `/dev/fd/N` name if `program_reads`
+/// and writes to it otherwise
+pub fn create_fd_pipe(program_reads: bool) -> Result<FdPipe> {
+    let (read_end, write_end) = create_pipe()?;
+    let (shared, own) = if program_reads {
+        (read_end, write_end)
+    } else {
+        (write_end, read_end)
+    };
+    let flags = set_close_on_exec(own, true).and_then(|_| set_close_on_exec(shared, false));
+    if let Err(reason) = flags {
+        close_file(read_end).ok();
+        close_file(write_end).ok();
+        return Err(reason);
+    }
+    let path = PathBuf::from(format!("/dev/fd/{}", shared));
+    Ok(FdPipe { path, shared, own })
+}
+
+/// Starts the program with posix_spawn(3) and waits until it exits or is killed,
+/// returns its pid and how it has ended.
+/// Unlike fork(2) it doesn't copy the page tables of the shell, so it is faster for big shells.
+/// Scripts without `#!` are run by `SCRIPT_SHELL` like in `execute`.
+#[cfg(target_os = "linux")]
+pub fn spawn_process(
+    path: &Path,
+    args: Vec<String>,
+    envp: Vec<String>,
+) -> Result<(pid_t, ChildStatus)> {
+    check_arguments(&args, &envp)?;
+    let path = native_path(path)?;
+    // MUST NOT be shadowed otherwise will be freed
+    let native_args = native_strings(args)?;
+    let native_envp = native_strings(envp)?;
+    let args = null_terminated(&native_args);
+    let envp = null_terminated(&native_envp);
+    let mut pid = 0;
+    let spawn = |pid: &mut pid_t, path: &CString, args: &[*const c_char]| unsafe {
+        posix_spawn(
+            pid,
+            path.as_ptr(),
+            null(),
+            null(),
+            args.as_ptr() as *const *mut c_char,
+            envp.as_ptr() as *const *mut c_char,
+        )
+    };
+    let mut status = spawn(&mut pid, &path, &args);
+    if status == ENOEXEC {
+        let shell = native_string(SCRIPT_SHELL)?;
+        let script_args = script_arguments(&shell, &path, &native_args);
+        status = spawn(&mut pid, &shell, &null_terminated(&script_args));
+    }
+    if status != 0 {
+        // posix_spawn returns the error number instead of setting errno
+        return Err(Error::Errno(Errno::from_code(status)));
+    }
+    wait_exit(pid).map(|status| (pid, status))
+}
+
+/// State change of a child reported by waitpid(2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChildStatus {
+    Exited(ExitCode),
+    /// Killed by the signal, the flag tells whether a core was dumped
+    Signaled(c_int, bool),
+    Stopped(c_int),
+    Continued,
+}
+
+impl ChildStatus {
+    /// Exit code as the shell reports it: 128 + signal number if the child was killed or stopped
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            ChildStatus::Exited(code) => code,
+            ChildStatus::Signaled(signal, _) | ChildStatus::Stopped(signal) => 128 + signal,
+            ChildStatus::Continued => 0,
+        }
+    }
+
+    /// Tells how the child has died if a signal killed it. Deaths by SIGINT and SIGPIPE
+    /// aren't reported, the user has pressed Ctrl-C or the reader of a pipe has exited.
+    pub fn death_message(self) -> Option<String> {
+        match self {
+            ChildStatus::Signaled(SIGINT, _) | ChildStatus::Signaled(SIGPIPE, _) => None,
+            ChildStatus::Signaled(signal, core_dumped) => {
+                Some(signal::death_description(signal, core_dumped))
             }
-            Ok(status)
+            _ => None,
         }
     }
+
+    /// Explains how the child has ended: names the signal which has killed it like
+    /// "Terminated by SIGSEGV (core dumped)" or tells what a well-known exit code means like
+    /// "Exit 127: command not found inside script". Unless `verbose` is set, deaths by SIGINT
+    /// and SIGPIPE aren't reported and other exit codes aren't mentioned.
+    pub fn explanation(self, verbose: bool) -> Option<String> {
+        match self {
+            ChildStatus::Signaled(SIGINT, _) | ChildStatus::Signaled(SIGPIPE, _) if !verbose => {
+                None
+            }
+            ChildStatus::Signaled(signal, core_dumped) => {
+                let text = msg!("Terminated by {}", signal::signal_name(signal));
+                Some(if core_dumped { msg!("{} (core dumped)", text) } else { text })
+            }
+            ChildStatus::Exited(0) => None,
+            ChildStatus::Exited(code) => match exit_code_meaning(code) {
+                Some(meaning) => Some(msg!("Exit {}: {}", code, meaning)),
+                None if verbose => Some(msg!("Exit {}", code)),
+                None => None,
+            },
+            ChildStatus::Stopped(_) | ChildStatus::Continued => None,
+        }
+    }
+
+    fn decode(status: c_int) -> Self {
+        unsafe {
+            if WIFSIGNALED(status) {
+                ChildStatus::Signaled(WTERMSIG(status), WCOREDUMP(status))
+            } else if WIFSTOPPED(status) {
+                ChildStatus::Stopped(WSTOPSIG(status))
+            } else if WIFCONTINUED(status) {
+                ChildStatus::Continued
+            } else {
+                ChildStatus::Exited(WEXITSTATUS(status))
+            }
+        }
+    }
+}
+
+/// Tells what the exit code means if it's set by shells or listed in sysexits.h
+fn exit_code_meaning(code: ExitCode) -> Option<String> {
+    let meaning = match code {
+        64 => msg!("usage error"),
+        65 => msg!("data format error"),
+        66 => msg!("cannot open input"),
+        67 => msg!("addressee unknown"),
+        68 => msg!("host name unknown"),
+        69 => msg!("service unavailable"),
+        70 => msg!("internal software error"),
+        71 => msg!("system error"),
+        72 => msg!("critical OS file missing"),
+        73 => msg!("can't create output file"),
+        74 => msg!("input/output error"),
+        75 => msg!("temporary failure, try again"),
+        76 => msg!("remote error in protocol"),
+        77 => msg!("permission denied"),
+        78 => msg!("configuration error"),
+        126 => msg!("command not executable inside script"),
+        EXEC_FAILED => msg!("command not found inside script"),
+        // a script passes on the status of a command killed by a signal
+        129..=192 => msg!("like a command killed by {}", signal::signal_name(code - 128)),
+        _ => return None,
+    };
+    Some(meaning)
+}
+
+/// Waits for the child and returns its exit code or 128 + signal number if it was killed
+pub fn wait_process(pid: pid_t) -> Result<ExitCode> {
+    wait_exit(pid).map(ChildStatus::exit_code)
+}
+
+/// Waits until the child exits or is killed, stops aren't reported.
+/// The child is killed once the deadline of `signal::start_deadline` passes.
+fn wait_exit(pid: pid_t) -> Result<ChildStatus> {
+    let mut status = 0;
+    loop {
+        kill_after_deadline(pid);
+        if unsafe { waitpid(pid, &mut status, 0) } >= 0 {
+            return Ok(ChildStatus::decode(status));
+        }
+        let reason = Error::from_errno();
+        if !reason.is_errno(EINTR) {
+            return Err(reason);
+        }
+    }
+}
+
+/// Waits until the child exits or stops, returns the state change.
+/// The child is killed once the deadline of `signal::start_deadline` passes.
+pub fn wait_child(pid: pid_t) -> Result<ChildStatus> {
+    let mut status = 0;
+    loop {
+        kill_after_deadline(pid);
+        if unsafe { waitpid(pid, &mut status, WUNTRACED) } >= 0 {
+            return Ok(ChildStatus::decode(status));
+        }
+        let reason = Error::from_errno();
+        if !reason.is_errno(EINTR) {
+            return Err(reason);
+        }
+    }
+}
+
+/// Kills the child which is still running after the deadline
+fn kill_after_deadline(pid: pid_t) {
+    if signal::deadline_passed() {
+        signal::send_signal(pid, SIGKILL).ok();
+    }
 }
 
-/// Creates pointers to arguments readable by C and executes the program
-pub fn execute(path: &PathBuf, args: Vec<String>, envp: Vec<String>) -> Error {
+/// Collects a state change of any child without blocking.
+/// Returns None if no child has changed its state or there are no children at all.
+pub fn poll_children() -> Result<Option<(pid_t, ChildStatus)>> {
+    let mut status = 0;
+    let pid = unsafe { waitpid(-1, &mut status, WNOHANG | WUNTRACED | WCONTINUED) };
+    match pid {
+        0 => Ok(None),
+        -1 => {
+            let reason = Error::from_errno();
+            if reason.is_errno(ECHILD) {
+                Ok(None)
+            } else {
+                Err(reason)
+            }
+        }
+        pid => Ok(Some((pid, ChildStatus::decode(status)))),
+    }
+}
+
+/// Gets the pid of the shell using getpid(2)
+pub fn get_process_id() -> pid_t {
+    unsafe { getpid() }
+}
+
+/// Gets the process group of the shell using getpgrp(2)
+pub fn get_process_group() -> pid_t {
+    unsafe { getpgrp() }
+}
+
+/// Moves the process to the process group using setpgid(2), zeros mean the calling process
+pub fn set_process_group(pid: pid_t, group: pid_t) -> Result<()> {
+    let status = unsafe { setpgid(pid, group) };
+    errno!(status, ())
+}
+
+/// Creates pointers to arguments readable by C and executes the program.
+/// A file which isn't a binary or a script with `#!` is run by `SCRIPT_SHELL`.
+pub fn execute(path: &Path, args: Vec<String>, envp: Vec<String>) -> Error {
+    unwrap_or_return!(check_arguments(&args, &envp));
     let path = unwrap_or_return!(native_path(path));
     // MUST NOT be shadowed otherwise will be freed
-    let mut native_args = Vec::with_capacity(args.len());
-    for arg in args {
-        let native = unwrap_or_return!(native_string(&arg));
-        native_args.push(native);
+    let native_args = unwrap_or_return!(native_strings(args));
+    let native_envp = unwrap_or_return!(native_strings(envp));
+    let args = null_terminated(&native_args);
+    let envp = null_terminated(&native_envp);
+    unsafe {
+        execve(path.as_ptr(), args.as_ptr(), envp.as_ptr());
     }
-    let args: Vec<*const i8> = native_args
-        .iter()
-        .map(|s| s.as_ptr())
-        .chain(once(null()))
-        .collect();
-    // MUST NOT be shadowed otherwise will be freed
-    let mut native_envp = Vec::with_capacity(envp.len());
-    for arg in envp {
-        let native = unwrap_or_return!(native_string(&arg));
-        native_envp.push(native);
+    let error = Error::from_errno();
+    if !error.is_errno(ENOEXEC) {
+        return error;
+    }
+    let shell = unwrap_or_return!(native_string(SCRIPT_SHELL));
+    let script_args = script_arguments(&shell, &path, &native_args);
+    let args = null_terminated(&script_args);
+    unsafe {
+        execve(shell.as_ptr(), args.as_ptr(), envp.as_ptr());
+    }
+    Error::from_errno()
+}
+
+/// Gets how many bytes the arguments and the environment of a program may take using sysconf(3)
+pub fn arguments_limit() -> usize {
+    match unsafe { sysconf(_SC_ARG_MAX) } {
+        limit if limit > 0 => limit as usize,
+        // the minimum POSIX allows
+        _ => 4096,
+    }
+}
+
+/// Linux refuses a single argument or variable longer than 32 pages whatever the total size is
+pub const MAX_ARGUMENT_LENGTH: usize = 32 * 4096;
+
+/// Counts the bytes the strings take in the memory of a new program like the kernel does it:
+/// every string with its NUL and the pointer to it
+pub fn arguments_size(strings: &[String]) -> usize {
+    strings.iter().map(|text| text.len() + 1 + size_of::<usize>()).sum()
+}
+
+/// Checks that the arguments and the environment fit in the limits of the system,
+/// so the error tells how big they are rather than execve(2) failing with E2BIG
+pub fn check_arguments(args: &[String], envp: &[String]) -> Result<()> {
+    if let Some(long) = args.iter().chain(envp).find(|text| text.len() >= MAX_ARGUMENT_LENGTH) {
+        return Err(Error::ArgumentTooLong(long.len()));
+    }
+    let size = arguments_size(args);
+    let environment = arguments_size(envp);
+    let limit = arguments_limit();
+    if size + environment > limit {
+        let count = args.len().saturating_sub(1);
+        return Err(Error::ArgumentListTooLong(count, size, environment, limit));
     }
-    let envp: Vec<*const i8> = native_envp
+    Ok(())
+}
+
+/// Runs files which the system can't execute, i.e. scripts without a `#!` line
+pub const SCRIPT_SHELL: &str = "/bin/sh";
+
+/// Makes arguments to run the script by the shell like execvp(3) does it:
+/// the shell, the path of the script and the arguments after the name of the command
+fn script_arguments(shell: &CString, path: &CString, args: &[CString]) -> Vec<CString> {
+    let mut result = vec![shell.clone(), path.clone()];
+    result.extend(args.iter().skip(1).cloned());
+    result
+}
+
+/// Converts strings to null terminated strings
+fn native_strings(strings: Vec<String>) -> Result<Vec<CString>> {
+    strings.iter().map(|string| native_string(string)).collect()
+}
+
+/// Creates a null terminated array of pointers to the strings
+fn null_terminated(strings: &[CString]) -> Vec<*const c_char> {
+    strings
         .iter()
         .map(|s| s.as_ptr())
         .chain(once(null()))
-        .collect();
-    unsafe {
-        execve(path.as_ptr(), args.as_ptr(), envp.as_ptr());
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_dir::TestDir;
+    use std::fs;
+
+    fn reader_of(dir: &TestDir, contents: &str) -> (PathBuf, Reader) {
+        let path = dir.join("input");
+        fs::write(&path, contents).unwrap();
+        let fd = open_file(&path, libc::O_RDONLY, None).unwrap();
+        (path, Reader::new(fd))
+    }
+
+    #[test]
+    fn programs_read_fd_pipes() {
+        let pipe = create_fd_pipe(true).unwrap();
+        write_all(pipe.own, b"piped\n").unwrap();
+        close_file(pipe.own).unwrap();
+        let output = process::Command::new("cat").arg(&pipe.path).output().unwrap();
+        close_file(pipe.shared).unwrap();
+        assert_eq!(output.stdout, b"piped\n");
+        let pipe = create_fd_pipe(false).unwrap();
+        let own = format!("/dev/fd/{}", pipe.own);
+        let status = process::Command::new("test").args(["-e", &own]).status().unwrap();
+        assert!(!status.success());
+        close_file(pipe.own).unwrap();
+        close_file(pipe.shared).unwrap();
+    }
+
+    #[test]
+    fn pty_and_socket() {
+        let (master, slave) = term::open_pty().unwrap();
+        let size = term::WindowSize {
+            columns: 100,
+            rows: 30,
+        };
+        term::set_window_size(master, size).unwrap();
+        let slave = open_file(&slave, libc::O_RDWR | libc::O_NOCTTY, None).unwrap();
+        assert_eq!(term::get_window_size(slave).unwrap(), size);
+        write_all(slave, b"ok").unwrap();
+        assert_eq!(wait_inputs(&[master]).unwrap(), vec![true]);
+        let mut buf = [0; 16];
+        let count = read_chunk(master, &mut buf).unwrap();
+        assert_eq!(&buf[..count], b"ok");
+        let dir = TestDir::new("socket");
+        let path = dir.join("socket");
+        let listener = listen_socket(&path).unwrap();
+        let client = connect_socket(&path).unwrap();
+        let server = accept_connection(listener).unwrap();
+        write_all(client, b"hi").unwrap();
+        assert_eq!(wait_inputs(&[master, server]).unwrap(), vec![false, true]);
+        let count = read_chunk(server, &mut buf).unwrap();
+        assert_eq!(&buf[..count], b"hi");
+        for fd in &[master, slave, listener, client, server] {
+            close_file(*fd).unwrap();
+        }
+    }
+
+    #[test]
+    fn reader_lines() {
+        let dir = TestDir::new("lines");
+        let (_, mut reader) = reader_of(&dir, "first\n\nlast");
+        assert_eq!(reader.read_line(100).unwrap(), Some(String::from("first")));
+        assert_eq!(reader.read_line(100).unwrap(), Some(String::new()));
+        assert_eq!(reader.read_line(100).unwrap(), Some(String::from("last")));
+        assert_eq!(reader.read_line(100).unwrap(), None);
+        close_file(reader.fd).unwrap();
+    }
+
+    #[test]
+    fn reader_long_lines() {
+        let long = "x".repeat(READER_BUFFER_SIZE * 2 + 10);
+        let dir = TestDir::new("long");
+        let (path, mut reader) = reader_of(&dir, &format!("{}\nok\n", long));
+        assert_eq!(reader.read_line(long.len()).unwrap(), Some(long.clone()));
+        close_file(reader.fd).unwrap();
+        let fd = open_file(&path, libc::O_RDONLY, None).unwrap();
+        let mut reader = Reader::new(fd);
+        assert!(reader.read_line(10).is_err());
+        assert_eq!(reader.read_line(10).unwrap(), Some(String::from("ok")));
+        close_file(fd).unwrap();
+    }
+
+    #[test]
+    fn temp_names_are_unique() {
+        let parent = TestDir::new("temp");
+        let dir = make_temp_dir(&parent.join("dir.XXXXXX")).unwrap();
+        assert!(dir.is_dir());
+        let (fd, first) = make_temp_file(&dir.join("file.XXXXXX")).unwrap();
+        close_file(fd).unwrap();
+        let (fd, second) = make_temp_file(&dir.join("file.XXXXXX")).unwrap();
+        close_file(fd).unwrap();
+        assert_ne!(first, second);
+        assert!(first.starts_with(&dir) && first.is_file());
+    }
+
+    #[test]
+    fn decode_wait_status() {
+        assert_eq!(ChildStatus::decode(3 << 8), ChildStatus::Exited(3));
+        assert_eq!(ChildStatus::decode(libc::SIGSEGV), ChildStatus::Signaled(libc::SIGSEGV, false));
+        let dumped = ChildStatus::decode(libc::SIGABRT | 0x80);
+        assert_eq!(dumped, ChildStatus::Signaled(libc::SIGABRT, true));
+        assert_eq!(dumped.exit_code(), 128 + libc::SIGABRT);
+        assert_eq!(ChildStatus::Signaled(SIGINT, false).death_message(), None);
+    }
+
+    #[test]
+    fn arguments_fit_limits() {
+        let args = vec![String::from("ls"), String::from("-l")];
+        assert!(check_arguments(&args, &[]).is_ok());
+        assert_eq!(arguments_size(&args), 3 + 3 + 2 * size_of::<usize>());
+        let long = vec![String::from("echo"), "x".repeat(MAX_ARGUMENT_LENGTH)];
+        assert!(check_arguments(&long, &[]).is_err_and(|reason| reason.is_too_long()));
+        let many = vec!["x".repeat(1000); arguments_limit() / 1000];
+        match check_arguments(&many, &args) {
+            Err(Error::ArgumentListTooLong(count, size, environment, limit)) => {
+                assert_eq!(count, many.len() - 1);
+                assert!(size + environment > limit);
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn explain_status() {
+        let explain = |status: ChildStatus, verbose| {
+            status.explanation(verbose).unwrap_or_default()
+        };
+        let dumped = ChildStatus::Signaled(libc::SIGSEGV, true);
+        assert_eq!(explain(dumped, false), "Terminated by SIGSEGV (core dumped)");
+        assert_eq!(explain(ChildStatus::Signaled(SIGPIPE, false), false), "");
+        assert_eq!(explain(ChildStatus::Signaled(SIGPIPE, false), true), "Terminated by SIGPIPE");
+        let not_found = "Exit 127: command not found inside script";
+        assert_eq!(explain(ChildStatus::Exited(127), false), not_found);
+        let killed = "Exit 143: like a command killed by SIGTERM";
+        assert_eq!(explain(ChildStatus::Exited(143), false), killed);
+        assert_eq!(explain(ChildStatus::Exited(1), false), "");
+        assert_eq!(explain(ChildStatus::Exited(1), true), "Exit 1");
+        assert_eq!(ChildStatus::Exited(0).explanation(true), None);
     }
-    Error::from_errno()
 }
@@ -0,0 +1,43 @@
+use std::mem::zeroed;
+use std::os::unix::io::RawFd;
+use std::ptr::null_mut;
+
+use libc::{addrinfo, close, connect, freeaddrinfo, gai_strerror, getaddrinfo, socket, AF_UNSPEC,
+           SOCK_STREAM};
+
+use super::error::*;
+use super::{copy_string, native_string};
+
+/// Resolves the host and the port with getaddrinfo(3) and connects a TCP socket
+/// to the first address which accepts the connection
+pub fn connect_tcp(host: &str, port: &str) -> Result<RawFd> {
+    let host = native_string(host)?;
+    let port = native_string(port)?;
+    let mut hints: addrinfo = unsafe { zeroed() };
+    hints.ai_family = AF_UNSPEC;
+    hints.ai_socktype = SOCK_STREAM;
+    let mut list: *mut addrinfo = null_mut();
+    let status = unsafe { getaddrinfo(host.as_ptr(), port.as_ptr(), &hints, &mut list) };
+    if status != 0 {
+        let text = unsafe { copy_string(gai_strerror(status)) }?;
+        return Err(Error::AddressLookup(text));
+    }
+    let mut result = Err(Error::NotFound);
+    let mut current = list;
+    while !current.is_null() {
+        let info = unsafe { &*current };
+        let fd = unsafe { socket(info.ai_family, info.ai_socktype, info.ai_protocol) };
+        if fd < 0 {
+            result = Err(Error::from_errno());
+        } else if unsafe { connect(fd, info.ai_addr, info.ai_addrlen) } == 0 {
+            result = Ok(fd);
+            break;
+        } else {
+            result = Err(Error::from_errno());
+            unsafe { close(fd) };
+        }
+        current = info.ai_next;
+    }
+    unsafe { freeaddrinfo(list) };
+    result
+}
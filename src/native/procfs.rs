@@ -0,0 +1,165 @@
+#[cfg(target_os = "linux")]
+use std::fs::{metadata, read_link, read_to_string};
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+use libc::pid_t;
+
+/// Gets the current directory of the process from /proc, None if it can't be read
+#[cfg(target_os = "linux")]
+pub fn process_cwd(pid: pid_t) -> Option<PathBuf> {
+    read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_cwd(_: pid_t) -> Option<PathBuf> {
+    None
+}
+
+/// Gets the name of the controlling terminal of the process like `pts/3` from /proc.
+/// Returns None if the process has no terminal or it can't be found out.
+#[cfg(target_os = "linux")]
+pub fn process_tty(pid: pid_t) -> Option<String> {
+    let stat = read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name in parentheses may contain spaces, so fields are counted after it
+    let (_, fields) = stat.rsplit_once(')')?;
+    let device: u32 = fields.split_whitespace().nth(5)?.parse().ok()?;
+    if device == 0 {
+        return None;
+    }
+    // the standard descriptors usually point to the terminal, so its path is known exactly
+    for fd in 0..=2 {
+        let path = match read_link(format!("/proc/{}/fd/{}", pid, fd)) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        let same = metadata(&path).is_ok_and(|meta| meta.rdev() == u64::from(device));
+        if let (true, Ok(name)) = (same, path.strip_prefix("/dev")) {
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+    tty_name(device)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_tty(_: pid_t) -> Option<String> {
+    None
+}
+
+/// Where the kernel puts a core dump according to /proc/sys/kernel/core_pattern
+#[derive(Debug, PartialEq)]
+pub enum CoreLocation {
+    /// A file, a relative path is in the current directory of the process
+    File(String),
+    /// A program the core is piped to like systemd-coredump
+    Program(String),
+}
+
+/// Finds out where the core dumped by the process has gone, `name` is the program it ran.
+/// Returns None if the pattern can't be read.
+#[cfg(target_os = "linux")]
+pub fn core_location(pid: pid_t, name: Option<&str>) -> Option<CoreLocation> {
+    let pattern = read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    let uses_pid = read_to_string("/proc/sys/kernel/core_uses_pid")
+        .is_ok_and(|value| value.trim() == "1");
+    Some(expand_core_pattern(pattern.trim_end(), pid, name, uses_pid))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn core_location(_: pid_t, _: Option<&str>) -> Option<CoreLocation> {
+    None
+}
+
+/// Fills in the pid and the program name of the pattern, the specifiers which
+/// can't be known after the process has died, like the time, are left as they are
+#[cfg(target_os = "linux")]
+fn expand_core_pattern(
+    pattern: &str,
+    pid: pid_t,
+    name: Option<&str>,
+    uses_pid: bool,
+) -> CoreLocation {
+    if let Some(command) = pattern.strip_prefix('|') {
+        let program = command.split_whitespace().next().unwrap_or(command);
+        return CoreLocation::Program(String::from(program));
+    }
+    let mut path = String::new();
+    let mut has_pid = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match (chars.next(), name) {
+            (Some('%'), _) => path.push('%'),
+            (Some('p'), _) | (Some('P'), _) => {
+                path.push_str(&pid.to_string());
+                has_pid = true;
+            }
+            // the kernel knows only the first 15 bytes of the name
+            (Some('e'), Some(name)) => {
+                let end = name
+                    .char_indices()
+                    .map(|(index, c)| index + c.len_utf8())
+                    .take_while(|end| *end <= 15)
+                    .last()
+                    .unwrap_or(0);
+                path.push_str(&name[..end]);
+            }
+            (Some(other), _) => {
+                path.push('%');
+                path.push(other);
+            }
+            (None, _) => {}
+        }
+    }
+    if uses_pid && !has_pid {
+        path.push_str(&format!(".{}", pid));
+    }
+    CoreLocation::File(path)
+}
+
+/// Names the terminal by its device number as the kernel encodes it in /proc/PID/stat
+#[cfg(target_os = "linux")]
+fn tty_name(device: u32) -> Option<String> {
+    let major = (device >> 8) & 0xfff;
+    let minor = (device & 0xff) | ((device >> 12) & 0xfff00);
+    match major {
+        0 => None,
+        // Unix98 pseudo-terminals take 8 majors
+        136..=143 => Some(format!("pts/{}", (major - 136) * 256 + minor)),
+        4 if minor < 64 => Some(format!("tty{}", minor)),
+        4 => Some(format!("ttyS{}", minor - 64)),
+        _ => Some(format!("{}:{}", major, minor)),
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tty_names() {
+        assert_eq!(tty_name(0), None);
+        assert_eq!(tty_name(136 << 8 | 3).as_deref(), Some("pts/3"));
+        assert_eq!(tty_name(137 << 8 | 2).as_deref(), Some("pts/258"));
+        assert_eq!(tty_name(4 << 8 | 1).as_deref(), Some("tty1"));
+        assert_eq!(tty_name(4 << 8 | 65).as_deref(), Some("ttyS1"));
+    }
+
+    #[test]
+    fn core_patterns() {
+        let file = |path: &str| CoreLocation::File(String::from(path));
+        assert_eq!(expand_core_pattern("core", 42, Some("a.out"), false), file("core"));
+        assert_eq!(expand_core_pattern("core", 42, Some("a.out"), true), file("core.42"));
+        let pattern = "/var/crash/%e.%p.%t.100%%";
+        let expected = file("/var/crash/very-long-progr.42.%t.100%");
+        assert_eq!(expand_core_pattern(pattern, 42, Some("very-long-program"), true), expected);
+        assert_eq!(expand_core_pattern("%e.core", 7, None, false), file("%e.core"));
+        let piped = expand_core_pattern("|/usr/lib/systemd/systemd-coredump %P %u", 7, None, false);
+        let program = String::from("/usr/lib/systemd/systemd-coredump");
+        assert_eq!(piped, CoreLocation::Program(program));
+    }
+}
@@ -0,0 +1,281 @@
+use std::mem::zeroed;
+use std::os::unix::io::RawFd;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use libc::{alarm, c_char, c_int, c_void, fcntl, getpid, kill, pid_t, read, sigaction, sigemptyset,
+           write, FD_CLOEXEC, F_SETFD, F_SETFL, O_NONBLOCK, SA_RESTART, SIGABRT, SIGALRM, SIGBUS,
+           SIGCHLD, SIGCONT, SIGFPE, SIGHUP, SIGILL, SIGINT, SIGIO, SIGKILL, SIGPIPE, SIGPROF,
+           SIGQUIT, SIGSEGV, SIGSTOP, SIGSYS, SIGTERM, SIGTRAP, SIGTSTP, SIGTTIN, SIGTTOU,
+           SIGURG, SIGUSR1, SIGUSR2, SIGVTALRM, SIGWINCH, SIGXCPU, SIGXFSZ, SIG_DFL,
+           SIG_IGN};
+
+use super::copy_string;
+use super::create_pipe;
+use super::error::*;
+
+extern "C" {
+    // missing in the used version of libc crate
+    fn strsignal(signal: c_int) -> *mut c_char;
+}
+
+/// Set by the SIGCHLD handler and cleared by children_changed()
+static CHILDREN_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Set by the SIGWINCH handler and cleared by window_changed(), the size is unknown at first
+static WINDOW_CHANGED: AtomicBool = AtomicBool::new(true);
+
+/// Set by the SIGINT handler and cleared by interrupted()
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set by the SIGALRM handler armed by start_deadline() and cleared by stop_deadline()
+static DEADLINE_PASSED: AtomicBool = AtomicBool::new(false);
+
+/// Ends of the self-pipe which handlers write to, so waiting for input wakes up.
+/// They are -1 until open_wakeup_pipe() is called.
+static WAKEUP_READ: AtomicI32 = AtomicI32::new(-1);
+static WAKEUP_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+/// Writes a byte to the self-pipe, write(2) is safe to call from a signal handler
+fn wake_up() {
+    let fd = WAKEUP_WRITE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        let byte = 0u8;
+        // the pipe is full only if nobody reads it, then one more byte changes nothing
+        unsafe { write(fd, &byte as *const u8 as *const c_void, 1) };
+    }
+}
+
+extern "C" fn on_child(_: c_int) {
+    CHILDREN_CHANGED.store(true, Ordering::SeqCst);
+    wake_up();
+}
+
+extern "C" fn on_resize(_: c_int) {
+    WINDOW_CHANGED.store(true, Ordering::SeqCst);
+    wake_up();
+}
+
+extern "C" fn on_interrupt(_: c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    wake_up();
+}
+
+extern "C" fn on_stop(_: c_int) {}
+
+extern "C" fn on_alarm(_: c_int) {
+    DEADLINE_PASSED.store(true, Ordering::SeqCst);
+    wake_up();
+}
+
+/// Installs the handler with SA_RESTART, so only waiting for input notices the signal.
+/// Unlike an ignored signal, a caught one is reset to the default action by execve(2).
+fn install_handler(signal: c_int, handler: extern "C" fn(c_int)) -> Result<()> {
+    install_action(signal, handler, SA_RESTART)
+}
+
+fn install_action(signal: c_int, handler: extern "C" fn(c_int), flags: c_int) -> Result<()> {
+    let status = unsafe {
+        let mut action: sigaction = zeroed();
+        action.sa_sigaction = handler as usize;
+        action.sa_flags = flags;
+        sigemptyset(&mut action.sa_mask);
+        sigaction(signal, &action, null_mut())
+    };
+    errno!(status, ())
+}
+
+/// Creates the self-pipe which wakes up wait_input() when a watched signal arrives.
+/// Both ends are non-blocking and closed on exec.
+pub fn open_wakeup_pipe() -> Result<()> {
+    if WAKEUP_READ.load(Ordering::SeqCst) >= 0 {
+        return Ok(());
+    }
+    let (read_end, write_end) = create_pipe()?;
+    for fd in &[read_end, write_end] {
+        let status = unsafe { fcntl(*fd, F_SETFL, O_NONBLOCK) };
+        errno!(status, ())?;
+        let status = unsafe { fcntl(*fd, F_SETFD, FD_CLOEXEC) };
+        errno!(status, ())?;
+    }
+    WAKEUP_READ.store(read_end, Ordering::SeqCst);
+    WAKEUP_WRITE.store(write_end, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Gets the read end of the self-pipe if it is open
+pub fn wakeup_fd() -> Option<RawFd> {
+    let fd = WAKEUP_READ.load(Ordering::SeqCst);
+    if fd >= 0 {
+        Some(fd)
+    } else {
+        None
+    }
+}
+
+/// Reads everything written to the self-pipe, the flags tell which signals have arrived
+pub fn drain_wakeups() {
+    if let Some(fd) = wakeup_fd() {
+        let mut buf = [0u8; 64];
+        while unsafe { read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) } > 0 {}
+    }
+}
+
+/// Installs SIGCHLD handler which remembers that some child has changed its state
+pub fn watch_children() -> Result<()> {
+    install_handler(SIGCHLD, on_child)
+}
+
+/// Installs SIGWINCH handler which remembers that the terminal window has been resized
+pub fn watch_window_size() -> Result<()> {
+    install_handler(SIGWINCH, on_resize)
+}
+
+/// Installs SIGINT handler, so Ctrl-C at the prompt discards the line instead of the shell
+pub fn catch_interrupt() -> Result<()> {
+    install_handler(SIGINT, on_interrupt)
+}
+
+/// Makes Ctrl-Z stop only the children: the shell catches SIGTSTP and does nothing
+pub fn catch_terminal_stop() -> Result<()> {
+    install_handler(SIGTSTP, on_stop)
+}
+
+/// Gives back the default actions of the signals caught by the shell, a forked subshell
+/// is interrupted and stopped like any other program
+pub fn default_signals() -> Result<()> {
+    for signal in &[SIGINT, SIGTSTP, SIGCHLD, SIGWINCH] {
+        let status = unsafe {
+            let mut action: sigaction = zeroed();
+            action.sa_sigaction = SIG_DFL;
+            sigemptyset(&mut action.sa_mask);
+            sigaction(*signal, &action, null_mut())
+        };
+        errno!(status, ())?;
+    }
+    Ok(())
+}
+
+/// Runs the function with SIGPIPE ignored, so writing to a pipe without a reader fails
+/// with EPIPE instead of killing the shell. The action is restored afterwards, children
+/// forked later don't inherit the ignored signal.
+pub fn ignoring_broken_pipe<T, F: FnOnce() -> T>(run: F) -> Result<T> {
+    let mut old: sigaction = unsafe { zeroed() };
+    let status = unsafe {
+        let mut action: sigaction = zeroed();
+        action.sa_sigaction = SIG_IGN;
+        sigemptyset(&mut action.sa_mask);
+        sigaction(SIGPIPE, &action, &mut old)
+    };
+    errno!(status, ())?;
+    let result = run();
+    let status = unsafe { sigaction(SIGPIPE, &old, null_mut()) };
+    errno!(status, result)
+}
+
+/// Arms SIGALRM to arrive in the given number of seconds. Its handler doesn't restart
+/// system calls, so waiting for a child is interrupted and the child is killed,
+/// see `deadline_passed`.
+pub fn start_deadline(seconds: u32) -> Result<()> {
+    DEADLINE_PASSED.store(false, Ordering::SeqCst);
+    install_action(SIGALRM, on_alarm, 0)?;
+    unsafe { alarm(seconds) };
+    Ok(())
+}
+
+/// Disarms SIGALRM armed by `start_deadline` and forgets that it has arrived
+pub fn stop_deadline() {
+    unsafe { alarm(0) };
+    DEADLINE_PASSED.store(false, Ordering::SeqCst);
+}
+
+/// Checks whether the deadline armed by `start_deadline` has passed
+pub fn deadline_passed() -> bool {
+    DEADLINE_PASSED.load(Ordering::SeqCst)
+}
+
+/// Sends the signal to the process or to the process group if pid is negative
+pub fn send_signal(pid: pid_t, signal: c_int) -> Result<()> {
+    let status = unsafe { kill(pid, signal) };
+    errno!(status, ())
+}
+
+/// Stops the shell itself with SIGSTOP which can't be caught
+pub fn stop_self() -> Result<()> {
+    send_signal(unsafe { getpid() }, SIGSTOP)
+}
+
+/// Checks whether SIGCHLD has been received since the last call
+pub fn children_changed() -> bool {
+    CHILDREN_CHANGED.swap(false, Ordering::SeqCst)
+}
+
+/// Checks whether SIGINT has been received since the last call
+pub fn interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// Checks whether the window has been resized since the last call
+pub fn window_changed() -> bool {
+    WINDOW_CHANGED.swap(false, Ordering::SeqCst)
+}
+
+/// Describes the death of a process by the signal like "Segmentation fault (core dumped)"
+pub fn death_description(signal: c_int, core_dumped: bool) -> String {
+    let description = signal_description(signal);
+    if core_dumped {
+        msg!("{} (core dumped)", description)
+    } else {
+        description
+    }
+}
+
+/// Names of the standard signals, their numbers differ between systems
+const SIGNAL_NAMES: &[(c_int, &str)] = &[
+    (SIGHUP, "SIGHUP"),
+    (SIGINT, "SIGINT"),
+    (SIGQUIT, "SIGQUIT"),
+    (SIGILL, "SIGILL"),
+    (SIGTRAP, "SIGTRAP"),
+    (SIGABRT, "SIGABRT"),
+    (SIGBUS, "SIGBUS"),
+    (SIGFPE, "SIGFPE"),
+    (SIGKILL, "SIGKILL"),
+    (SIGUSR1, "SIGUSR1"),
+    (SIGSEGV, "SIGSEGV"),
+    (SIGUSR2, "SIGUSR2"),
+    (SIGPIPE, "SIGPIPE"),
+    (SIGALRM, "SIGALRM"),
+    (SIGTERM, "SIGTERM"),
+    (SIGCHLD, "SIGCHLD"),
+    (SIGCONT, "SIGCONT"),
+    (SIGSTOP, "SIGSTOP"),
+    (SIGTSTP, "SIGTSTP"),
+    (SIGTTIN, "SIGTTIN"),
+    (SIGTTOU, "SIGTTOU"),
+    (SIGURG, "SIGURG"),
+    (SIGXCPU, "SIGXCPU"),
+    (SIGXFSZ, "SIGXFSZ"),
+    (SIGVTALRM, "SIGVTALRM"),
+    (SIGPROF, "SIGPROF"),
+    (SIGWINCH, "SIGWINCH"),
+    (SIGIO, "SIGIO"),
+    (SIGSYS, "SIGSYS"),
+];
+
+/// Gets the name of the signal like "SIGSEGV", unknown ones are called by their numbers
+pub fn signal_name(signal: c_int) -> String {
+    match SIGNAL_NAMES.iter().find(|(number, _)| *number == signal) {
+        Some((_, name)) => String::from(*name),
+        None => format!("SIG{}", signal),
+    }
+}
+
+/// Gets the description of the signal using strsignal(3), e.g. "Terminated"
+pub fn signal_description(signal: c_int) -> String {
+    let text = unsafe { strsignal(signal) };
+    if text.is_null() {
+        return format!("Signal {}", signal);
+    }
+    unsafe { copy_string(text) }.unwrap_or_else(|_| format!("Signal {}", signal))
+}
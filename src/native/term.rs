@@ -0,0 +1,191 @@
+use std::ffi::CStr;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use libc::{c_int, grantpt, ioctl, isatty, pid_t, posix_openpt, ptsname, setsid, sigaddset,
+           sigemptyset, sigprocmask, sigset_t, tcgetattr, tcgetpgrp, tcsetattr, tcsetpgrp,
+           termios, unlockpt, winsize, ECHO, ICANON, ICRNL, IEXTEN, ISIG, IXON, ONLCR, OPOST,
+           O_NOCTTY, O_RDWR, SIGTTOU, SIG_BLOCK, SIG_SETMASK, TCSADRAIN, TIOCGWINSZ, TIOCSCTTY,
+           TIOCSWINSZ, VMIN, VTIME};
+
+use super::close_file;
+use super::error::{Error, Result};
+
+/// Size of a terminal window in characters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+/// Checks whether the file descriptor refers to a terminal using isatty(3)
+pub fn is_terminal(fd: RawFd) -> bool {
+    unsafe { isatty(fd) == 1 }
+}
+
+/// Asks the terminal driver for the window size using TIOCGWINSZ ioctl(2)
+pub fn get_window_size(fd: RawFd) -> Result<WindowSize> {
+    let mut size: winsize = unsafe { std::mem::zeroed() };
+    let status: c_int = unsafe { ioctl(fd, TIOCGWINSZ as _, &mut size) };
+    errno!(
+        status,
+        WindowSize {
+            columns: size.ws_col,
+            rows: size.ws_row,
+        }
+    )
+}
+
+/// Sets the window size of the terminal using TIOCSWINSZ ioctl(2), e.g. of a pseudo-terminal
+pub fn set_window_size(fd: RawFd, size: WindowSize) -> Result<()> {
+    let mut native: winsize = unsafe { std::mem::zeroed() };
+    native.ws_col = size.columns;
+    native.ws_row = size.rows;
+    let status: c_int = unsafe { ioctl(fd, TIOCSWINSZ as _, &native) };
+    errno!(status, ())
+}
+
+/// Opens a new pseudo-terminal, returns its master descriptor and the path of its slave
+pub fn open_pty() -> Result<(RawFd, PathBuf)> {
+    let master = unsafe { posix_openpt(O_RDWR | O_NOCTTY) };
+    let master = errno!(master, master)?;
+    let slave = unsafe {
+        if grantpt(master) != 0 || unlockpt(master) != 0 {
+            None
+        } else {
+            let name = ptsname(master);
+            (!name.is_null()).then(|| PathBuf::from(&*CStr::from_ptr(name).to_string_lossy()))
+        }
+    };
+    match slave {
+        Some(slave) => Ok((master, slave)),
+        None => {
+            let reason = Error::from_errno();
+            close_file(master).ok();
+            Err(reason)
+        }
+    }
+}
+
+/// Starts a new session with setsid(2) and makes the terminal its controlling one
+pub fn take_controlling_terminal(fd: RawFd) -> Result<()> {
+    let status = unsafe { setsid() };
+    errno!(status, ())?;
+    let status = unsafe { ioctl(fd, TIOCSCTTY as _, 0) };
+    errno!(status, ())
+}
+
+/// Gets the foreground process group of the terminal using tcgetpgrp(3)
+pub fn get_terminal_group(fd: RawFd) -> Result<pid_t> {
+    let group = unsafe { tcgetpgrp(fd) };
+    errno!(group, group)
+}
+
+/// Makes the process group the foreground one of the terminal using tcsetpgrp(3).
+/// SIGTTOU is blocked meanwhile, otherwise a caller from a background group would be stopped.
+pub fn set_terminal_group(fd: RawFd, group: pid_t) -> Result<()> {
+    unsafe {
+        let mut blocked: sigset_t = std::mem::zeroed();
+        let mut saved: sigset_t = std::mem::zeroed();
+        sigemptyset(&mut blocked);
+        sigaddset(&mut blocked, SIGTTOU);
+        sigprocmask(SIG_BLOCK, &blocked, &mut saved);
+        let status = tcsetpgrp(fd, group);
+        let result = errno!(status, ());
+        sigprocmask(SIG_SETMASK, &saved, std::ptr::null_mut());
+        result
+    }
+}
+
+/// Settings of a terminal saved with tcgetattr(3) to be restored later
+#[derive(Clone, Copy)]
+pub struct TerminalModes(termios);
+
+impl TerminalModes {
+    /// Gets the current settings of the terminal
+    pub fn get(fd: RawFd) -> Result<Self> {
+        let mut modes: termios = unsafe { std::mem::zeroed() };
+        let status = unsafe { tcgetattr(fd, &mut modes) };
+        errno!(status, TerminalModes(modes))
+    }
+
+    /// Applies the settings after the pending output is written
+    pub fn set(&self, fd: RawFd) -> Result<()> {
+        let status = unsafe { tcsetattr(fd, TCSADRAIN, &self.0) };
+        errno!(status, ())
+    }
+
+    /// Turns on what the line editor and programs expect of a terminal: line input
+    /// with echo and signal keys, Enter read as a line break and output processing
+    /// so a line break starts a new line. Returns whether something was off.
+    pub fn make_sane(&mut self) -> bool {
+        let before = *self;
+        self.0.c_lflag |= ECHO | ICANON | ISIG;
+        self.0.c_iflag |= ICRNL;
+        self.0.c_oflag |= OPOST | ONLCR;
+        *self != before
+    }
+}
+
+/// Only flags and control characters are compared, other fields are platform specific
+impl PartialEq for TerminalModes {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.c_iflag == other.0.c_iflag
+            && self.0.c_oflag == other.0.c_oflag
+            && self.0.c_cflag == other.0.c_cflag
+            && self.0.c_lflag == other.0.c_lflag
+            && self.0.c_cc == other.0.c_cc
+    }
+}
+
+impl std::fmt::Debug for TerminalModes {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "TerminalModes {{ iflag: {:#o}, oflag: {:#o}, cflag: {:#o}, lflag: {:#o} }}",
+            self.0.c_iflag, self.0.c_oflag, self.0.c_cflag, self.0.c_lflag
+        )
+    }
+}
+
+/// Puts the terminal into raw mode: input is not echoed and is passed byte by byte,
+/// signal keys like Ctrl-C are read as characters. Settings are restored on drop.
+pub struct RawMode {
+    fd: RawFd,
+    saved: TerminalModes,
+}
+
+impl RawMode {
+    /// Saves the settings of the terminal and switches it to raw mode using tcsetattr(3)
+    pub fn enable(fd: RawFd) -> Result<Self> {
+        let saved = TerminalModes::get(fd)?;
+        let mut raw = saved;
+        raw.0.c_lflag &= !(ECHO | ICANON | IEXTEN | ISIG);
+        raw.0.c_iflag &= !(ICRNL | IXON);
+        raw.0.c_cc[VMIN] = 1;
+        raw.0.c_cc[VTIME] = 0;
+        raw.set(fd)?;
+        Ok(RawMode { fd, saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        self.saved.set(self.fd).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_modes_are_repaired() {
+        let mut modes = TerminalModes(unsafe { std::mem::zeroed() });
+        modes.0.c_lflag = IEXTEN;
+        assert!(modes.make_sane());
+        assert_eq!(modes.0.c_lflag, IEXTEN | ECHO | ICANON | ISIG);
+        assert_eq!(modes.0.c_oflag, OPOST | ONLCR);
+        assert!(!modes.make_sane());
+    }
+}
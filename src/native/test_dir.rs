@@ -0,0 +1,46 @@
+use std::fs::remove_dir_all;
+use std::path::{Path, PathBuf};
+
+use super::make_temp_dir;
+
+/// A directory with a unique name made for a test, it is removed with everything in it
+/// when the test ends, even if an assertion fails
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// Creates the directory in the temporary one of the system, the name tells the test
+    pub fn new(name: &str) -> TestDir {
+        let template = std::env::temp_dir().join(format!("rsh-{}.XXXXXX", name));
+        TestDir(make_temp_dir(&template).unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Gets the path of the entry of the directory
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        remove_dir_all(&self.0).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_is_removed() {
+        let dir = TestDir::new("test-dir");
+        let path = dir.path().to_path_buf();
+        std::fs::create_dir(dir.join("inner")).unwrap();
+        std::fs::write(dir.join("inner/file"), "").unwrap();
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
@@ -1,5 +1,6 @@
+use std::ffi::CString;
 use std::path::PathBuf;
-use libc::{passwd, getpwuid, getuid, getgid, c_char};
+use libc::{passwd, getpwnam, getpwuid, getuid, getgid, getgroups, c_char, gid_t};
 
 use super::error::{Result, Error};
 use super::copy_string;
@@ -17,6 +18,18 @@ pub fn get_gid() -> GroupId {
     unsafe { getgid() }
 }
 
+/// Gets supplementary groups of the current user using getgroups(2)
+pub fn get_groups() -> Vec<GroupId> {
+    let count = unsafe { getgroups(0, std::ptr::null_mut()) };
+    if count <= 0 {
+        return Vec::new();
+    }
+    let mut groups: Vec<gid_t> = vec![0; count as usize];
+    let count = unsafe { getgroups(count, groups.as_mut_ptr()) };
+    groups.truncate(count.max(0) as usize);
+    groups
+}
+
 /// Gets user's home directory from the corresponding record in passwd.
 pub fn get_home_dir(uid: UserId) -> Result<PathBuf> {
     let entry: *const passwd = unsafe { getpwuid(uid) };
@@ -32,3 +45,32 @@ pub fn get_home_dir(uid: UserId) -> Result<PathBuf> {
         }
     }
 }
+
+/// Gets the home directory of the user with the login name from passwd
+pub fn get_named_home_dir(name: &str) -> Result<PathBuf> {
+    let name = CString::new(name).map_err(|_| Error::InvalidCString)?;
+    let entry: *const passwd = unsafe { getpwnam(name.as_ptr()) };
+    if entry.is_null() {
+        return Err(Error::NotFound);
+    }
+    let dir: *const c_char = unsafe { (*entry).pw_dir };
+    if dir.is_null() {
+        return Err(Error::NotFound);
+    }
+    Ok(PathBuf::from(unsafe { copy_string(dir)? }))
+}
+
+/// Gets user's login name from the corresponding record in passwd.
+pub fn get_user_name(uid: UserId) -> Result<String> {
+    let entry: *const passwd = unsafe { getpwuid(uid) };
+    if entry.is_null() {
+        Err(Error::from_errno())
+    } else {
+        let name: *const c_char = unsafe { (*entry).pw_name };
+        if name.is_null() {
+            Err(Error::NotFound)
+        } else {
+            unsafe { copy_string(name) }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+#[cfg(target_os = "linux")]
+use std::os::unix::io::RawFd;
+
+#[cfg(target_os = "linux")]
+use libc::{c_char, c_int, c_void, read, O_CLOEXEC, O_NONBLOCK};
+
+#[cfg(target_os = "linux")]
+use super::{close_file, native_path};
+use super::file_stat::get_modification_time;
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    // missing in the used version of libc crate
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+}
+
+/// Events of inotify(7): a file opened for writing was closed, moved into or deleted
+/// from the directory
+#[cfg(target_os = "linux")]
+const IN_CLOSE_WRITE: u32 = 0x8;
+#[cfg(target_os = "linux")]
+const IN_MOVED_TO: u32 = 0x80;
+#[cfg(target_os = "linux")]
+const IN_DELETE: u32 = 0x200;
+/// Size of `struct inotify_event` without the name: wd, mask, cookie and len
+#[cfg(target_os = "linux")]
+const EVENT_HEADER: usize = 16;
+
+/// Notices changes of files including their replacement by a rename like editors save them.
+/// inotify(7) is used on Linux, elsewhere or if it fails the modification times are compared.
+pub struct FileWatch {
+    files: Vec<PathBuf>,
+    /// Modification times of the files when they were checked last, None for a missing file
+    times: Vec<Option<(i64, i64)>>,
+    #[cfg(target_os = "linux")]
+    inotify: Option<Inotify>,
+}
+
+impl FileWatch {
+    /// Starts watching the files, the ones which don't exist yet are noticed when created
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let times = modification_times(&files);
+        FileWatch {
+            #[cfg(target_os = "linux")]
+            inotify: Inotify::new(&files),
+            files,
+            times,
+        }
+    }
+
+    /// Checks whether some of the files has changed since the last call
+    pub fn changed(&mut self) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(inotify) = &self.inotify {
+                return inotify.changed();
+            }
+        }
+        let times = modification_times(&self.files);
+        let changed = times != self.times;
+        self.times = times;
+        changed
+    }
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<(i64, i64)>> {
+    files.iter().map(|file| get_modification_time(file).ok()).collect()
+}
+
+/// Watches the directories of the files, so replacing a file is noticed too
+#[cfg(target_os = "linux")]
+struct Inotify {
+    fd: RawFd,
+    /// Watch descriptors of the directories and the names of the files in them
+    watches: Vec<(c_int, Vec<u8>)>,
+}
+
+#[cfg(target_os = "linux")]
+impl Inotify {
+    fn new(files: &[PathBuf]) -> Option<Self> {
+        use std::os::unix::ffi::OsStrExt;
+        let fd = unsafe { inotify_init1(O_NONBLOCK | O_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let mut inotify = Inotify { fd, watches: Vec::new() };
+        for file in files {
+            let (dir, name) = match (file.parent(), file.file_name()) {
+                (Some(dir), Some(name)) => (dir, name),
+                _ => return None,
+            };
+            let dir = native_path(dir).ok()?;
+            let mask = IN_CLOSE_WRITE | IN_MOVED_TO | IN_DELETE;
+            let wd = unsafe { inotify_add_watch(fd, dir.as_ptr(), mask) };
+            if wd < 0 {
+                return None;
+            }
+            inotify.watches.push((wd, name.as_bytes().to_vec()));
+        }
+        Some(inotify)
+    }
+
+    /// Reads the pending events, checks whether some of them is about a watched file
+    fn changed(&self) -> bool {
+        let mut buf = vec![0u8; 4096];
+        let mut changed = false;
+        loop {
+            let count = unsafe { read(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+            if count <= 0 {
+                return changed;
+            }
+            let mut events = &buf[..count as usize];
+            while events.len() >= EVENT_HEADER {
+                let field = |offset: usize| {
+                    let mut bytes = [0u8; 4];
+                    bytes.copy_from_slice(&events[offset..(offset + 4)]);
+                    bytes
+                };
+                let wd = c_int::from_ne_bytes(field(0));
+                let len = u32::from_ne_bytes(field(12)) as usize;
+                let end = (EVENT_HEADER + len).min(events.len());
+                let name = &events[EVENT_HEADER..end];
+                let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+                changed |= self.watches.iter().any(|(watch, file)| *watch == wd && file == name);
+                events = &events[end..];
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        close_file(self.fd).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn replaced_file_is_noticed() {
+        let dir = TestDir::new("watch");
+        let file = dir.join("rc");
+        std::fs::write(&file, "set a = 1\n").unwrap();
+        let mut watch = FileWatch::new(vec![file.clone()]);
+        assert!(!watch.changed());
+        std::fs::write(dir.join("other"), "").unwrap();
+        assert!(!watch.changed());
+        let temporary = dir.join("rc.new");
+        std::fs::write(&temporary, "set a = 2\n").unwrap();
+        std::fs::rename(&temporary, &file).unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+    }
+}
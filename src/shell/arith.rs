@@ -0,0 +1,601 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::env::var;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use native::error::*;
+use native::{collate, write_to_file};
+
+use super::Shell;
+
+/// A value of an arithmetic expression, integers stay exact until they are mixed with floats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn to_float(self) -> f64 {
+        match self {
+            Number::Integer(value) => value as f64,
+            Number::Float(value) => value,
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Number::Integer(value) => write!(formatter, "{}", value),
+            Number::Float(value) => write!(formatter, "{}", value),
+        }
+    }
+}
+
+/// An operand of an expression: a number, a string or the name of an unset variable
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(Number),
+    Text(String),
+    /// It is 0 in arithmetic and the name itself in string comparisons
+    Unset(String),
+}
+
+impl Value {
+    /// Converts the value for arithmetic, strings must be numbers
+    fn number(self) -> Result<Number> {
+        match self {
+            Value::Number(number) => Ok(number),
+            Value::Text(text) => parse_number(text.trim()),
+            Value::Unset(_) => Ok(Number::Integer(0)),
+        }
+    }
+
+    /// Gets the number if the value looks like one, it decides how values are compared
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(text) => parse_number(text.trim()).ok(),
+            Value::Unset(_) => None,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Value::Number(number) => number.to_string(),
+            Value::Text(text) | Value::Unset(text) => text.clone(),
+        }
+    }
+}
+
+/// How strings are compared by `<`, `>`, `==` and others
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Collation {
+    /// Letters differing only in case are equal, it is set by `nocasecmp`
+    pub ignore_case: bool,
+    /// Strings are ordered by LC_COLLATE locale instead of code points, it is set by `collate`
+    pub locale: bool,
+}
+
+/// Evaluates the expression of numbers, strings, variables, `+ - * / % **`,
+/// comparisons `< > <= >= == !=` and parentheses. Comparisons give 1 or 0, numbers are
+/// compared by value and other strings according to the collation.
+/// Integer operations which overflow or divide inexactly give floats, e.g. `7 / 2` is 3.5.
+/// Variables are named with or without `$` and looked up with the function, unset ones are 0.
+/// Strings with spaces are quoted with `'` or `"`.
+pub fn evaluate(
+    text: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    collation: Collation,
+) -> Result<Number> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        lookup,
+        collation,
+    };
+    let value = parser.equality()?;
+    parser.skip_spaces();
+    match parser.chars.next() {
+        Some(_) => Err(Error::SyntaxError),
+        None => value.number(),
+    }
+}
+
+/// Recursive descent parser which computes the value while it reads the expression
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+    collation: Collation,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_spaces(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    /// Consumes the character if it is next after spaces
+    fn accept(&mut self, expected: char) -> bool {
+        self.skip_spaces();
+        if self.chars.peek() == Some(&expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the first of the operators which is next after spaces
+    fn accept_operator(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        self.skip_spaces();
+        for operator in operators {
+            let mut ahead = self.chars.clone();
+            if operator.chars().all(|c| ahead.next() == Some(c)) {
+                self.chars = ahead;
+                return Some(operator);
+            }
+        }
+        None
+    }
+
+    /// equality = relation (('==' | '!=') relation)*
+    fn equality(&mut self) -> Result<Value> {
+        let mut value = self.relation()?;
+        while let Some(operator) = self.accept_operator(&["==", "!="]) {
+            let order = compare(&value, &self.relation()?, self.collation);
+            let result = (order == Ordering::Equal) == (operator == "==");
+            value = Value::Number(Number::Integer(result as i64));
+        }
+        Ok(value)
+    }
+
+    /// relation = sum (('<=' | '>=' | '<' | '>') sum)*
+    fn relation(&mut self) -> Result<Value> {
+        let mut value = self.sum()?;
+        while let Some(operator) = self.accept_operator(&["<=", ">=", "<", ">"]) {
+            let order = compare(&value, &self.sum()?, self.collation);
+            let result = match operator {
+                "<=" => order != Ordering::Greater,
+                ">=" => order != Ordering::Less,
+                "<" => order == Ordering::Less,
+                _ => order == Ordering::Greater,
+            };
+            value = Value::Number(Number::Integer(result as i64));
+        }
+        Ok(value)
+    }
+
+    /// sum = product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Value> {
+        let mut value = self.product()?;
+        loop {
+            if self.accept('+') {
+                value = Value::Number(add(value.number()?, self.product()?.number()?));
+            } else if self.accept('-') {
+                let right = negate(self.product()?.number()?);
+                value = Value::Number(add(value.number()?, right));
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// product = unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<Value> {
+        let mut value = self.unary()?;
+        loop {
+            self.skip_spaces();
+            let operator = match self.chars.peek() {
+                Some(&c) if c == '*' || c == '/' || c == '%' => c,
+                _ => return Ok(value),
+            };
+            self.chars.next();
+            let (left, right) = (value.number()?, self.unary()?.number()?);
+            value = Value::Number(match operator {
+                '*' => multiply(left, right),
+                '/' => divide(left, right)?,
+                _ => remainder(left, right)?,
+            });
+        }
+    }
+
+    /// unary = ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<Value> {
+        if self.accept('-') {
+            Ok(Value::Number(negate(self.unary()?.number()?)))
+        } else if self.accept('+') {
+            Ok(Value::Number(self.unary()?.number()?))
+        } else {
+            self.power()
+        }
+    }
+
+    /// power = primary ('**' unary)?, it is right associative: `2 ** 3 ** 2` is 512
+    fn power(&mut self) -> Result<Value> {
+        let base = self.primary()?;
+        self.skip_spaces();
+        let mut ahead = self.chars.clone();
+        if ahead.next() == Some('*') && ahead.next() == Some('*') {
+            self.chars = ahead;
+            let exponent = self.unary()?.number()?;
+            return Ok(Value::Number(power(base.number()?, exponent)));
+        }
+        Ok(base)
+    }
+
+    /// primary = number | variable | string | '(' equality ')'
+    fn primary(&mut self) -> Result<Value> {
+        if self.accept('(') {
+            let value = self.equality()?;
+            return if self.accept(')') { Ok(value) } else { Err(Error::SyntaxError) };
+        }
+        if self.accept('$') || self.chars.peek().is_some_and(|c| c.is_alphabetic() || *c == '_') {
+            return self.variable();
+        }
+        if let Some(&quote) = self.chars.peek().filter(|c| **c == '\'' || **c == '"') {
+            self.chars.next();
+            let text: String = self.chars.by_ref().take_while(|c| *c != quote).collect();
+            return Ok(Value::Text(text));
+        }
+        self.number().map(Value::Number)
+    }
+
+    /// Reads the name and gets the value of the variable
+    fn variable(&mut self) -> Result<Value> {
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            self.chars.next();
+        }
+        if name.is_empty() {
+            return Err(Error::SyntaxError);
+        }
+        match (self.lookup)(&name) {
+            Some(value) => Ok(Value::Text(value)),
+            None => Ok(Value::Unset(name)),
+        }
+    }
+
+    /// Reads digits with an optional fraction and exponent, like `12`, `.5` or `1.5e3`
+    fn number(&mut self) -> Result<Number> {
+        self.skip_spaces();
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            let sign_of_exponent = (c == '-' || c == '+') && text.ends_with(['e', 'E']);
+            if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || sign_of_exponent {
+                text.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        parse_number(&text)
+    }
+}
+
+/// Compares numbers by value and other operands as strings
+fn compare(left: &Value, right: &Value, collation: Collation) -> Ordering {
+    if let (Some(left), Some(right)) = (left.as_number(), right.as_number()) {
+        return compare_numbers(left, right);
+    }
+    let (mut left, mut right) = (left.text(), right.text());
+    if collation.ignore_case {
+        left = left.to_lowercase();
+        right = right.to_lowercase();
+    }
+    if collation.locale {
+        collate(&left, &right)
+    } else {
+        left.cmp(&right)
+    }
+}
+
+/// Compares numbers by value, integers exactly, NaN is equal to anything
+pub fn compare_numbers(left: Number, right: Number) -> Ordering {
+    if let (Number::Integer(left), Number::Integer(right)) = (left, right) {
+        return left.cmp(&right);
+    }
+    left.to_float().partial_cmp(&right.to_float()).unwrap_or(Ordering::Equal)
+}
+
+/// Converts a literal like `12` or `1.5e3`, names like `inf` aren't accepted
+pub fn parse_number(text: &str) -> Result<Number> {
+    if let Ok(value) = text.parse() {
+        return Ok(Number::Integer(value));
+    }
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return Err(Error::SyntaxError);
+    }
+    text.parse().map(Number::Float).map_err(|_| Error::SyntaxError)
+}
+
+/// Formats the number, floats follow the printf-like format if it is given:
+/// `%f`, `%e` or `%g` with an optional precision like `%.2f`, the default precision is 6.
+/// Without a format floats are printed with as many digits as needed.
+pub fn format_number(value: Number, format: Option<&str>) -> Result<String> {
+    let (value, format) = match (value, format) {
+        (Number::Float(value), Some(format)) => (value, format),
+        (value, _) => return Ok(value.to_string()),
+    };
+    let spec = format.strip_prefix('%').ok_or(Error::SyntaxError)?;
+    let conversion = spec.chars().last().ok_or(Error::SyntaxError)?;
+    let precision = match &spec[..spec.len() - conversion.len_utf8()] {
+        "" => 6,
+        precision => precision
+            .strip_prefix('.')
+            .and_then(|digits| digits.parse().ok())
+            .ok_or(Error::SyntaxError)?,
+    };
+    match conversion {
+        'f' => Ok(format!("{:.*}", precision, value)),
+        'e' => Ok(exponent_form(value, precision)),
+        'g' => Ok(general_form(value, precision)),
+        _ => Err(Error::SyntaxError),
+    }
+}
+
+/// Formats like printf `%e`: the exponent has a sign and at least two digits
+fn exponent_form(value: f64, precision: usize) -> String {
+    let text = format!("{:.*e}", precision, value);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: i32 = exponent.parse().unwrap_or(0);
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+        }
+        // inf and NaN have no exponent
+        None => text,
+    }
+}
+
+/// Formats like printf `%g`: the shorter of `%e` and `%f` for the number of significant
+/// digits, trailing zeros of the fraction are removed
+fn general_form(value: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let rounded = format!("{:.*e}", precision - 1, value);
+    let exponent: i32 = rounded.split_once('e').map_or(0, |(_, e)| e.parse().unwrap_or(0));
+    if exponent < -4 || exponent >= precision as i32 {
+        let text = exponent_form(value, precision - 1);
+        let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, ""));
+        format!("{}e{}", trim_fraction(mantissa), exponent)
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        trim_fraction(&format!("{:.*}", decimals, value))
+    }
+}
+
+fn trim_fraction(text: &str) -> String {
+    if text.contains('.') {
+        String::from(text.trim_end_matches('0').trim_end_matches('.'))
+    } else {
+        String::from(text)
+    }
+}
+
+fn add(left: Number, right: Number) -> Number {
+    match (left, right) {
+        (Number::Integer(a), Number::Integer(b)) => match a.checked_add(b) {
+            Some(sum) => Number::Integer(sum),
+            None => Number::Float(a as f64 + b as f64),
+        },
+        _ => Number::Float(left.to_float() + right.to_float()),
+    }
+}
+
+fn negate(value: Number) -> Number {
+    match value {
+        Number::Integer(a) => match a.checked_neg() {
+            Some(negated) => Number::Integer(negated),
+            None => Number::Float(-(a as f64)),
+        },
+        Number::Float(a) => Number::Float(-a),
+    }
+}
+
+fn multiply(left: Number, right: Number) -> Number {
+    match (left, right) {
+        (Number::Integer(a), Number::Integer(b)) => match a.checked_mul(b) {
+            Some(product) => Number::Integer(product),
+            None => Number::Float(a as f64 * b as f64),
+        },
+        _ => Number::Float(left.to_float() * right.to_float()),
+    }
+}
+
+fn divide(left: Number, right: Number) -> Result<Number> {
+    match (left, right) {
+        (Number::Integer(_), Number::Integer(0)) => Err(Error::DivisionByZero),
+        (Number::Integer(a), Number::Integer(b)) if a.checked_rem(b) == Some(0) => {
+            Ok(Number::Integer(a / b))
+        }
+        _ => Ok(Number::Float(left.to_float() / right.to_float())),
+    }
+}
+
+fn remainder(left: Number, right: Number) -> Result<Number> {
+    match (left, right) {
+        (Number::Integer(_), Number::Integer(0)) => Err(Error::DivisionByZero),
+        (Number::Integer(a), Number::Integer(b)) => {
+            Ok(a.checked_rem(b).map_or(Number::Integer(0), Number::Integer))
+        }
+        _ => Ok(Number::Float(left.to_float() % right.to_float())),
+    }
+}
+
+/// Raises to the power, a negative integer exponent gives a float
+fn power(base: Number, exponent: Number) -> Number {
+    match (base, exponent) {
+        (Number::Integer(a), Number::Integer(b)) if b >= 0 => {
+            match u32::try_from(b).ok().and_then(|b| a.checked_pow(b)) {
+                Some(result) => Number::Integer(result),
+                None => Number::Float((a as f64).powf(b as f64)),
+            }
+        }
+        _ => Number::Float(base.to_float().powf(exponent.to_float())),
+    }
+}
+
+impl Shell {
+    /// Evaluates the expression with shell and environment variables
+    /// and formats the result according to `floatformat`
+    pub fn arithmetic(&self, text: &str) -> Result<String> {
+        let lookup = |name: &str| self.variables.get(name).cloned().or_else(|| var(name).ok());
+        let collation = Collation {
+            ignore_case: self.variables.contains_key("nocasecmp"),
+            locale: self.variables.contains_key("collate"),
+        };
+        let value = evaluate(text, &lookup, collation)?;
+        format_number(value, self.variables.get("floatformat").map(String::as_str))
+    }
+
+    /// Implements `calc expression`: prints the value of the expression
+    pub fn calc_builtin(&mut self, words: &[&str]) -> Result<()> {
+        if words.is_empty() {
+            return Err(Error::MissingArgument("calc"));
+        }
+        let value = self.arithmetic(&words.join(" "))?;
+        write_to_file(1, &format!("{}\n", value))?;
+        Ok(())
+    }
+
+    /// Implements `@ name = expression`, the operator may also be `+= -= *= /= %=`,
+    /// `@ name++` and `@ name--` change the variable by one. Without words prints variables.
+    pub fn at_builtin(&mut self, words: &[&str]) -> Result<()> {
+        if words.is_empty() {
+            return self.set_variables(&[]);
+        }
+        let text = words.join(" ");
+        let name_end = text
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(text.len());
+        let (name, rest) = text.split_at(name_end);
+        if name.is_empty() {
+            return Err(Error::SyntaxError);
+        }
+        let rest = rest.trim();
+        let expression = match rest {
+            "++" => format!("${} + 1", name),
+            "--" => format!("${} - 1", name),
+            _ => match rest.split_once('=') {
+                Some(("", expression)) => format!("({})", expression),
+                Some((operator, expression)) if ["+", "-", "*", "/", "%"].contains(&operator) => {
+                    format!("${} {} ({})", name, operator, expression)
+                }
+                _ => return Err(Error::SyntaxError),
+            },
+        };
+        let value = self.arithmetic(&expression)?;
+        self.variables.insert(String::from(name), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_plain(text: &str) -> Result<Number> {
+        evaluate(text, &|_| None, Collation::default())
+    }
+
+    #[test]
+    fn integer_expressions() {
+        assert_eq!(evaluate_plain("1 + 2 * 3").unwrap(), Number::Integer(7));
+        assert_eq!(evaluate_plain("(1+2)*3 - -1").unwrap(), Number::Integer(10));
+        assert_eq!(evaluate_plain("2 ** 3 ** 2").unwrap(), Number::Integer(512));
+        assert_eq!(evaluate_plain("-2 ** 2").unwrap(), Number::Integer(-4));
+        assert_eq!(evaluate_plain("17 % 5").unwrap(), Number::Integer(2));
+        assert_eq!(evaluate_plain("8 / 4").unwrap(), Number::Integer(2));
+    }
+
+    #[test]
+    fn float_expressions() {
+        assert_eq!(evaluate_plain("7 / 2").unwrap(), Number::Float(3.5));
+        assert_eq!(evaluate_plain(".5 + 1.5e1").unwrap(), Number::Float(15.5));
+        assert_eq!(evaluate_plain("2 ** -1").unwrap(), Number::Float(0.5));
+        let overflow = evaluate_plain("9223372036854775807 + 1").unwrap();
+        assert_eq!(overflow, Number::Float(9223372036854775808.0));
+    }
+
+    #[test]
+    fn variables_in_expressions() {
+        let lookup = |name: &str| match name {
+            "x" => Some(String::from("4")),
+            "half" => Some(String::from(" 0.5 ")),
+            "word" => Some(String::from("abc")),
+            _ => None,
+        };
+        let collation = Collation::default();
+        let value = evaluate("$x * half + unset", &lookup, collation).unwrap();
+        assert_eq!(value, Number::Float(2.0));
+        assert!(evaluate("word + 1", &lookup, collation).is_err());
+        assert!(evaluate("$ + 1", &lookup, collation).is_err());
+    }
+
+    #[test]
+    fn comparisons() {
+        let lookup = |name: &str| match name {
+            "word" => Some(String::from("Apple")),
+            "ten" => Some(String::from("10")),
+            _ => None,
+        };
+        let exact = Collation::default();
+        let check = |text: &str, collation: Collation| evaluate(text, &lookup, collation).unwrap();
+        assert_eq!(check("ten > 9", exact), Number::Integer(1));
+        assert_eq!(check("'10' == 1e1", exact), Number::Integer(1));
+        assert_eq!(check("word < 'apple'", exact), Number::Integer(1));
+        assert_eq!(check("word == apple", exact), Number::Integer(0));
+        assert_eq!(check("1 + 1 == 2 != 0", exact), Number::Integer(1));
+        assert_eq!(check("\"a b\" <= 'a c'", exact), Number::Integer(1));
+        let ignore_case = Collation {
+            ignore_case: true,
+            locale: false,
+        };
+        assert_eq!(check("word == apple", ignore_case), Number::Integer(1));
+        assert_eq!(check("$word >= 'APPLE'", ignore_case), Number::Integer(1));
+    }
+
+    #[test]
+    fn printf_formats() {
+        let value = Number::Float(1234.5678);
+        assert_eq!(format_number(value, None).unwrap(), "1234.5678");
+        assert_eq!(format_number(value, Some("%.2f")).unwrap(), "1234.57");
+        assert_eq!(format_number(value, Some("%e")).unwrap(), "1.234568e+03");
+        assert_eq!(format_number(value, Some("%g")).unwrap(), "1234.57");
+        assert_eq!(format_number(Number::Float(0.00001), Some("%g")).unwrap(), "1e-05");
+        assert_eq!(format_number(Number::Float(2.5), Some("%.3g")).unwrap(), "2.5");
+        assert_eq!(format_number(Number::Integer(3), Some("%.2f")).unwrap(), "3");
+        assert!(format_number(value, Some("%d")).is_err());
+        assert!(format_number(value, Some(".2f")).is_err());
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(evaluate_plain("1 +").is_err());
+        assert!(evaluate_plain("(1").is_err());
+        assert!(evaluate_plain("1 2").is_err());
+        assert!(evaluate_plain("1e").is_err());
+        assert!(evaluate_plain("1 / 0").is_err());
+        assert!(evaluate_plain("1 *** 2").is_err());
+    }
+
+    #[test]
+    fn builtins_need_expressions() {
+        let mut shell = Shell::new().unwrap();
+        assert!(matches!(shell.calc_builtin(&[]), Err(Error::MissingArgument("calc"))));
+        shell.at_builtin(&["n", "=", "2*3"]).unwrap();
+        shell.at_builtin(&["n", "+=", "1"]).unwrap();
+        assert_eq!(shell.variables["n"], "7");
+    }
+}
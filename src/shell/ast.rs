@@ -0,0 +1,517 @@
+use native::error::*;
+
+use super::lexer::{self, glob_pattern, tokenize, word_double_quoted_dollars, word_quoted_dollars};
+use super::quoting::unquote_word;
+use super::pipeline::is_pipe;
+use super::variables::is_variable_name;
+
+/// Words which separate commands, they are operators only if they aren't quoted
+const OPERATORS: &[&str] = &[";", "&&", "||", "&", "|", "|&"];
+
+/// A word of a command with what its expansion needs to know about its quotes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    /// The word as it is written, braces and `~` are expanded only outside quotes
+    pub raw: String,
+    /// The word without quotes and backslashes
+    pub text: String,
+    /// The filename pattern of the word, see `lexer::glob_patterns`
+    pub pattern: Option<String>,
+    /// Offsets of the `$` which aren't substituted, see `lexer::quoted_dollars`
+    pub quoted: Vec<usize>,
+    /// Offsets of the `$` in double quotes, a list substituted there stays one word
+    pub joined: Vec<usize>,
+}
+
+/// A redirection like `> file`, `2>&1` or `<input`, see `Redirection::parse`.
+/// The lexer always makes the operator a separate word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub operator: String,
+    pub target: Word,
+}
+
+/// A program or a builtin with its arguments and redirections,
+/// the `NAME=value` words before it go to its environment.
+/// Without words the assignments set shell variables.
+/// A group like `( cd /tmp ; ls ) > out` has only redirections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Word>,
+    pub words: Vec<Word>,
+    pub group: Option<Group>,
+    pub redirects: Vec<Redirect>,
+    /// The stderr goes to the next command of the pipeline too (`|&`)
+    pub with_stderr: bool,
+}
+
+/// `( list )`: the commands run in a subshell, so they don't change the shell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// The commands as they are written between the parentheses
+    pub text: String,
+    pub list: List,
+}
+
+/// Commands connected with `|` or `|&`, the pipeline runs in the background if it ends with `&`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<SimpleCommand>,
+    pub background: bool,
+}
+
+/// `if (expression) command`: the command runs if the expression isn't zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditional {
+    /// The expression as it is written, its variables are substituted by `Shell::arithmetic`
+    pub condition: String,
+    pub body: Box<Command>,
+}
+
+/// `repeat count command`: the command runs the number of times
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub count: Word,
+    pub body: Box<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Pipeline(Pipeline),
+    Conditional(Conditional),
+    Loop(Loop),
+}
+
+/// Tells whether a command of a list runs after the previous one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// `;` or the `&` of a background pipeline
+    Always,
+    /// `&&`: only if the previous command has succeeded
+    Success,
+    /// `||`: only if the previous command has failed
+    Failure,
+}
+
+/// The commands of a line in the order they run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct List {
+    pub items: Vec<(Connector, Command)>,
+}
+
+impl Word {
+    /// Gets what the expansion needs to know about the word as it is written
+    pub fn new(raw: &str) -> Word {
+        Word {
+            raw: String::from(raw),
+            text: unquote_word(raw),
+            pattern: glob_pattern(raw),
+            quoted: word_quoted_dollars(raw),
+            joined: word_double_quoted_dollars(raw),
+        }
+    }
+}
+
+impl From<lexer::Token> for Word {
+    /// Uses the text unquoted by the lexer instead of unquoting the word again
+    fn from(token: lexer::Token) -> Word {
+        Word {
+            pattern: glob_pattern(&token.raw),
+            quoted: word_quoted_dollars(&token.raw),
+            joined: word_double_quoted_dollars(&token.raw),
+            raw: token.raw,
+            text: token.text,
+        }
+    }
+}
+
+impl Command {
+    /// Finds the first simple command which the command would run
+    pub fn first_simple(&self) -> &SimpleCommand {
+        match self {
+            Command::Pipeline(pipeline) => &pipeline.commands[0],
+            Command::Conditional(conditional) => conditional.body.first_simple(),
+            Command::Loop(repeat) => repeat.body.first_simple(),
+        }
+    }
+}
+
+impl Pipeline {
+    /// Finds the command of a line like `NAME=value` or `NAME=value > file` which has
+    /// no words and isn't a group, its assignments set shell variables instead of going
+    /// to an environment
+    pub fn assignments_only(&self) -> Option<&SimpleCommand> {
+        match self.commands.as_slice() {
+            [command] if !self.background && command.words.is_empty() => {
+                Some(command).filter(|command| command.group.is_none())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A word of the line with its place in the line
+struct Token {
+    /// The byte offset of the word in the line
+    start: usize,
+    word: Word,
+}
+
+impl Token {
+    /// The operator written by the word, quoted operators are plain words
+    fn operator(&self) -> Option<&str> {
+        OPERATORS.iter().find(|operator| **operator == self.word.raw).copied()
+    }
+
+    /// Tells whether the unquoted word is the keyword
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.word.raw == keyword
+    }
+}
+
+/// Builds the tree of the commands of the line. Keywords are separate words, operators
+/// needn't be, a command without words like in `ls | | wc` or `ls &&` is an error.
+pub fn parse_line(line: &str) -> Result<List> {
+    // an unmatched quote is an error here, unlike in `parse_line_lossless`
+    let tokens: Vec<Token> = tokenize(line)?
+        .into_iter()
+        .map(|token| Token {
+            start: token.start,
+            word: Word::from(token),
+        })
+        .collect();
+    Parser {
+        line,
+        tokens: &tokens,
+        position: 0,
+        depth: 0,
+    }
+    .list()
+}
+
+/// Recursive descent parser of the words of a line
+struct Parser<'a> {
+    line: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+    /// How many groups the parser is in, their lists end at `)`
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_operator(&self) -> Option<&'a str> {
+        self.peek().and_then(Token::operator)
+    }
+
+    /// Tells whether the list ends here: at the end of the line or the `)` of a group
+    fn at_end(&self) -> bool {
+        self.peek().is_none_or(|token| self.depth > 0 && token.is_keyword(")"))
+    }
+
+    /// list = command ((';' | '&&' | '||') command)* [';'], a background pipeline
+    /// needs no `;` after its `&`
+    fn list(&mut self) -> Result<List> {
+        let mut list = List::default();
+        let mut connector = Connector::Always;
+        while !self.at_end() {
+            list.items.push((connector, self.command()?));
+            connector = match self.peek_operator() {
+                Some(";") => Connector::Always,
+                Some("&&") => Connector::Success,
+                Some("||") => Connector::Failure,
+                Some(_) => return Err(Error::NullCommand),
+                None => {
+                    connector = Connector::Always;
+                    continue;
+                }
+            };
+            self.position += 1;
+            if connector != Connector::Always && self.at_end() {
+                return Err(Error::NullCommand);
+            }
+        }
+        Ok(list)
+    }
+
+    /// command = 'if' '(' expression ')' command | 'repeat' count command | pipeline
+    fn command(&mut self) -> Result<Command> {
+        let token = self.peek().ok_or(Error::NullCommand)?;
+        if token.is_keyword("if") {
+            self.position += 1;
+            let condition = self.condition()?;
+            let body = Box::new(self.command()?);
+            return Ok(Command::Conditional(Conditional { condition, body }));
+        }
+        if token.is_keyword("repeat") {
+            self.position += 1;
+            let count = match self.peek() {
+                Some(token) if token.operator().is_none() => token.word.clone(),
+                _ => return Err(Error::MissingArgument("repeat")),
+            };
+            self.position += 1;
+            let body = Box::new(self.command()?);
+            return Ok(Command::Loop(Loop { count, body }));
+        }
+        self.pipeline().map(Command::Pipeline)
+    }
+
+    /// The text of the line between the parentheses, they may be nested
+    fn condition(&mut self) -> Result<String> {
+        let open = match self.peek() {
+            Some(token) if token.word.raw == "(" => token.start + 1,
+            _ => return Err(Error::MissingArgument("(")),
+        };
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            self.position += 1;
+            match token.word.raw.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(String::from(&self.line[open..token.start]));
+            }
+        }
+        Err(Error::MissingArgument("("))
+    }
+
+    /// pipeline = simple (('|' | '|&') simple)* ['&']
+    fn pipeline(&mut self) -> Result<Pipeline> {
+        let mut commands = Vec::new();
+        let mut background = false;
+        loop {
+            let mut command = self.simple()?;
+            match self.peek_operator() {
+                Some(operator) if is_pipe(operator) => {
+                    self.position += 1;
+                    command.with_stderr = operator == "|&";
+                    commands.push(command);
+                }
+                Some("&") => {
+                    self.position += 1;
+                    background = true;
+                    commands.push(command);
+                    break;
+                }
+                _ => {
+                    commands.push(command);
+                    break;
+                }
+            }
+        }
+        Ok(Pipeline { commands, background })
+    }
+
+    /// simple = (assignment | redirect)* (word | redirect)+ | '(' list ')' redirect*
+    fn simple(&mut self) -> Result<SimpleCommand> {
+        let mut command = SimpleCommand::default();
+        // parentheses of words like in `set x = (a b)` inside a group
+        let mut parentheses = 0;
+        while let Some(token) = self.peek() {
+            if token.operator().is_some() {
+                break;
+            }
+            if token.is_keyword(")") && parentheses == 0 && self.depth > 0 {
+                break;
+            }
+            self.position += 1;
+            if token.is_keyword("(") && command.words.is_empty() && command.assignments.is_empty()
+                && command.group.is_none()
+            {
+                command.group = Some(self.group(token.start + 1)?);
+            } else if is_redirect(&token.word.raw) {
+                let target = match self.peek() {
+                    Some(target)
+                        if target.operator().is_none() && !is_redirect(&target.word.raw) =>
+                    {
+                        self.position += 1;
+                        target.word.clone()
+                    }
+                    _ => return Err(Error::MissingRedirectName),
+                };
+                command.redirects.push(Redirect {
+                    operator: token.word.raw.clone(),
+                    target,
+                });
+            } else if command.group.is_some() {
+                return Err(Error::SyntaxError.about(&token.word.raw));
+            } else if command.words.is_empty() && is_assignment(&token.word.text) {
+                command.assignments.push(token.word.clone());
+            } else {
+                match token.word.raw.as_str() {
+                    "(" => parentheses += 1,
+                    ")" if parentheses > 0 => parentheses -= 1,
+                    _ => {}
+                }
+                command.words.push(token.word.clone());
+            }
+        }
+        if command.words.is_empty() && command.assignments.is_empty() && command.group.is_none()
+        {
+            return Err(Error::NullCommand);
+        }
+        Ok(command)
+    }
+
+    /// The list of a group after its `(` which is at the offset, up to the matching `)`
+    fn group(&mut self, open: usize) -> Result<Group> {
+        self.depth += 1;
+        let list = self.list();
+        self.depth -= 1;
+        let list = list?;
+        match self.peek() {
+            Some(token) if token.is_keyword(")") => {
+                self.position += 1;
+                if list.items.is_empty() {
+                    return Err(Error::NullCommand);
+                }
+                let text = String::from(&self.line[open..token.start]);
+                Ok(Group { text, list })
+            }
+            _ => Err(Error::UnmatchedQuote('(')),
+        }
+    }
+}
+
+/// Checks whether the word is `NAME=value`
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| is_variable_name(name))
+}
+
+/// Checks whether the word as it is written is a redirection: an unquoted `>` or `<`
+/// after an optional descriptor number, the lexer leaves the target to the next word
+fn is_redirect(raw: &str) -> bool {
+    raw.trim_start_matches(|c: char| c.is_ascii_digit()).starts_with(['>', '<'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(words: &[Word]) -> Vec<&str> {
+        words.iter().map(|word| word.text.as_str()).collect()
+    }
+
+    fn pipeline(command: &Command) -> &Pipeline {
+        match command {
+            Command::Pipeline(pipeline) => pipeline,
+            _ => panic!("not a pipeline: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn lists_of_pipelines() {
+        let line = "LANG=C ls -l 2>&1 |& wc > 'out file' && sleep 1 & echo '|' ;";
+        let list = parse_line(line).unwrap();
+        let connectors: Vec<Connector> = list.items.iter().map(|item| item.0).collect();
+        let expected = vec![Connector::Always, Connector::Success, Connector::Always];
+        assert_eq!(connectors, expected);
+        let first = pipeline(&list.items[0].1);
+        assert!(!first.background);
+        let ls = &first.commands[0];
+        assert_eq!(texts(&ls.assignments), vec!["LANG=C"]);
+        assert_eq!(texts(&ls.words), vec!["ls", "-l"]);
+        assert_eq!(ls.redirects[0].operator, "2>&");
+        assert_eq!(ls.redirects[0].target.text, "1");
+        assert!(ls.with_stderr);
+        let wc = &first.commands[1];
+        assert_eq!(wc.redirects[0].target.text, "out file");
+        assert!(pipeline(&list.items[1].1).background);
+        assert_eq!(texts(&pipeline(&list.items[2].1).commands[0].words), vec!["echo", "|"]);
+        assert_eq!(parse_line("sleep 1 & ls").unwrap().items[1].0, Connector::Always);
+        let list = parse_line("a/b=c x=1").unwrap();
+        assert_eq!(texts(&list.items[0].1.first_simple().words), vec!["a/b=c", "x=1"]);
+        assert_eq!(parse_line("  # comment").unwrap(), List::default());
+    }
+
+    #[test]
+    fn conditionals_and_loops() {
+        let list = parse_line("if ( $x > 1 ) repeat 3 echo '>'").unwrap();
+        let conditional = match &list.items[0].1 {
+            Command::Conditional(conditional) => conditional,
+            command => panic!("not a conditional: {:?}", command),
+        };
+        assert_eq!(conditional.condition, " $x > 1 ");
+        let repeat = match conditional.body.as_ref() {
+            Command::Loop(repeat) => repeat,
+            command => panic!("not a loop: {:?}", command),
+        };
+        assert_eq!(repeat.count.text, "3");
+        assert_eq!(texts(&list.items[0].1.first_simple().words), vec!["echo", ">"]);
+        let list = parse_line("if (($a + 1) * 2 == 4) true").unwrap();
+        match &list.items[0].1 {
+            Command::Conditional(conditional) => {
+                assert_eq!(conditional.condition, "($a + 1) * 2 == 4")
+            }
+            command => panic!("not a conditional: {:?}", command),
+        }
+        let list = parse_line("'if' x").unwrap();
+        assert_eq!(texts(&list.items[0].1.first_simple().words), ["if", "x"]);
+    }
+
+    #[test]
+    fn groups() {
+        let list = parse_line("(cd /tmp ; set x = (a b) && ls) > out | wc ; ( ( pwd ) )").unwrap();
+        let first = pipeline(&list.items[0].1);
+        let group = first.commands[0].group.as_ref().unwrap();
+        assert_eq!(group.text, "cd /tmp ; set x = (a b) && ls");
+        assert_eq!(group.list.items.len(), 3);
+        let set = texts(&group.list.items[1].1.first_simple().words);
+        assert_eq!(set, ["set", "x", "=", "(", "a", "b", ")"]);
+        assert_eq!(first.commands[0].redirects[0].target.text, "out");
+        assert!(first.assignments_only().is_none());
+        let outer = pipeline(&list.items[1].1).commands[0].group.as_ref().unwrap();
+        let inner = outer.list.items[0].1.first_simple().group.as_ref().unwrap();
+        assert_eq!(inner.text, " pwd ");
+        let list = parse_line("ls )").unwrap();
+        assert_eq!(texts(&list.items[0].1.first_simple().words), ["ls", ")"]);
+        for line in &["( ls", "( )", "( ls ) wc", "( ls && )", "( ls ; ) )x"] {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let lines = ["ls |", "ls | | wc", "; ls", "ls &&", "ls & &", "> out", "ls >"];
+        for line in lines.iter().chain(&["if (1", "if (1)", "if 1 true", "repeat"]) {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+        assert!(parse_line("ls > > out").is_err());
+    }
+
+    #[test]
+    fn operators_without_spaces() {
+        let list = parse_line("echo a;echo b&&ls|wc -l>out 2>>errors&").unwrap();
+        assert_eq!(texts(&list.items[0].1.first_simple().words), vec!["echo", "a"]);
+        assert_eq!(list.items[1].0, Connector::Always);
+        assert_eq!(texts(&list.items[1].1.first_simple().words), vec!["echo", "b"]);
+        assert_eq!(list.items[2].0, Connector::Success);
+        let second = pipeline(&list.items[2].1);
+        assert!(second.background);
+        let wc = &second.commands[1];
+        assert_eq!(texts(&wc.words), vec!["wc", "-l"]);
+        let redirects: Vec<(&str, &str)> = wc
+            .redirects
+            .iter()
+            .map(|redirect| (redirect.operator.as_str(), redirect.target.text.as_str()))
+            .collect();
+        assert_eq!(redirects, vec![(">", "out"), ("2>>", "errors")]);
+        let list = parse_line("if ($x>=1)echo '(';repeat 2 ls").unwrap();
+        match &list.items[0].1 {
+            Command::Conditional(conditional) => assert_eq!(conditional.condition, "$x>=1"),
+            command => panic!("not a conditional: {:?}", command),
+        }
+        assert_eq!(texts(&list.items[0].1.first_simple().words), vec!["echo", "("]);
+        assert!(matches!(list.items[1].1, Command::Loop(_)));
+        let list = parse_line("echo $((1+2)) $((2**3))|wc ${x}y").unwrap();
+        let echo = &pipeline(&list.items[0].1).commands;
+        assert_eq!(texts(&echo[0].words), vec!["echo", "$((1+2))", "$((2**3))"]);
+        assert_eq!(echo[0].words[2].pattern, None);
+        assert_eq!(texts(&echo[1].words), vec!["wc", "${x}y"]);
+    }
+}
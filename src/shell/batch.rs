@@ -0,0 +1,108 @@
+use std::mem;
+use std::path::Path;
+use std::process::exit;
+use std::slice;
+
+use native::error::*;
+use native::{arguments_limit, arguments_size, execute, start_process, wait_process, ExitCode,
+             MAX_ARGUMENT_LENGTH};
+
+use super::Shell;
+
+/// Room left for what the system adds to the arguments like the path of the program
+const RESERVED_SPACE: usize = 4096;
+
+impl Shell {
+    /// Handles arguments too big to execute the program in the forked child.
+    /// With `argbatch` set the program is run like xargs on as many arguments at a time
+    /// as the system allows, one batch after another. Leading options are passed to every batch
+    /// and the status is the last failing one. Otherwise, or if even one argument doesn't fit,
+    /// the reason is returned.
+    pub fn run_in_batches(
+        &self,
+        path: &Path,
+        arguments: Vec<String>,
+        environment: Vec<String>,
+        reason: Error,
+    ) -> Error {
+        if !self.variables.contains_key("argbatch") {
+            return reason;
+        }
+        let taken = arguments_size(&environment);
+        let space = arguments_limit().saturating_sub(taken + RESERVED_SPACE);
+        let batches = match split_batches(&arguments, space) {
+            Some(batches) => batches,
+            None => return reason,
+        };
+        let mut status: ExitCode = 0;
+        for batch in batches {
+            let child = start_process(|| execute(path, batch, environment.clone()));
+            match child.and_then(wait_process) {
+                Ok(0) => {}
+                Ok(code) => status = code,
+                Err(reason) => return reason,
+            }
+        }
+        exit(status)
+    }
+}
+
+/// Splits the arguments after the name of the command and its options into batches which fit
+/// in `space` bytes, each one starts with the name and the options.
+/// Returns None if there is nothing to split or one of the arguments doesn't fit alone.
+fn split_batches(arguments: &[String], space: usize) -> Option<Vec<Vec<String>>> {
+    // `--` ends the options, it is repeated too
+    let mut options = arguments.len().min(1);
+    for argument in &arguments[options..] {
+        if !argument.starts_with('-') || argument == "-" {
+            break;
+        }
+        options += 1;
+        if argument == "--" {
+            break;
+        }
+    }
+    let (fixed, rest) = arguments.split_at(options);
+    let fixed_cost = arguments_size(fixed);
+    if rest.is_empty() {
+        return None;
+    }
+    let mut batches = Vec::new();
+    let mut batch = fixed.to_vec();
+    let mut used = fixed_cost;
+    for argument in rest {
+        let cost = arguments_size(slice::from_ref(argument));
+        if fixed_cost + cost > space || argument.len() >= MAX_ARGUMENT_LENGTH {
+            return None;
+        }
+        if used + cost > space {
+            batches.push(mem::replace(&mut batch, fixed.to_vec()));
+            used = fixed_cost;
+        }
+        batch.push(argument.clone());
+        used += cost;
+    }
+    batches.push(batch);
+    Some(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn batches_repeat_options() {
+        let arguments = words("rm -f -- aa bb cc dd");
+        let space = arguments_size(&arguments[..3]) + arguments_size(&arguments[3..5]);
+        let batches = split_batches(&arguments, space).unwrap();
+        assert_eq!(batches, vec![words("rm -f -- aa bb"), words("rm -f -- cc dd")]);
+        assert_eq!(split_batches(&words("rm -f"), space), None);
+        assert_eq!(split_batches(&words("rm aa"), 10), None);
+        let batches = split_batches(&words("echo -n a b c"), 1000).unwrap();
+        assert_eq!(batches, vec![words("echo -n a b c")]);
+    }
+}
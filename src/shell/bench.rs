@@ -0,0 +1,86 @@
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+use native::error::*;
+
+use super::ast::Word;
+use super::Shell;
+
+/// Result of running one benchmark
+#[derive(Debug)]
+pub struct Measurement {
+    pub name: &'static str,
+    pub iterations: u32,
+    pub total: Duration,
+}
+
+impl Measurement {
+    /// Average time of one iteration in nanoseconds
+    pub fn nanos_per_iteration(&self) -> u128 {
+        self.total.as_nanos() / u128::from(self.iterations.max(1))
+    }
+}
+
+impl Display for Measurement {
+    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "{:<12} {:>8} iterations {:>12} ns/iter",
+            self.name,
+            self.iterations,
+            self.nanos_per_iteration()
+        )
+    }
+}
+
+/// Runs the closure the provided number of times and measures the total time
+fn measure<F>(name: &'static str, iterations: u32, mut action: F) -> Result<Measurement>
+where
+    F: FnMut() -> Result<()>,
+{
+    let start = Instant::now();
+    for _ in 0..iterations {
+        action()?;
+    }
+    Ok(Measurement {
+        name,
+        iterations,
+        total: start.elapsed(),
+    })
+}
+
+impl Shell {
+    /// Measures the main paths of command execution: builtins, fork and exec, posix_spawn,
+    /// PATH lookup, variable substitution and prompt rendering.
+    pub fn run_benchmarks(&mut self) -> Result<Vec<Measurement>> {
+        let mut result = Vec::new();
+        result.push(measure("builtin", 10_000, || {
+            self.parse("set bench = value").map(|_| ())
+        })?);
+        self.variables.remove("bench");
+        let spawn = self.variables.remove("spawn");
+        result.push(measure("fork", 100, || self.parse("true").map(|_| ()))?);
+        if cfg!(target_os = "linux") {
+            self.variables.insert(String::from("spawn"), String::new());
+            result.push(measure("spawn", 100, || self.parse("true").map(|_| ()))?);
+            self.variables.remove("spawn");
+        }
+        if let Some(value) = spawn {
+            self.variables.insert_value(String::from("spawn"), value);
+        }
+        result.push(measure("path", 1_000, || {
+            self.find_path("sh").map(|_| ()).ok_or(Error::NotFound)
+        })?);
+        let words: Vec<Word> =
+            ["$HOME", "$RANDOM", "word"].iter().map(|raw| Word::new(raw)).collect();
+        result.push(measure("expansion", 10_000, || {
+            self.expand_words(&words)?;
+            Ok(())
+        })?);
+        result.push(measure("prompt", 10_000, || {
+            self.prompt_text();
+            Ok(())
+        })?);
+        Ok(result)
+    }
+}
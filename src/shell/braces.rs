@@ -0,0 +1,165 @@
+use native::error::*;
+
+/// What a pair of braces expands to
+enum Items<'a> {
+    /// `{a,b,c}`
+    List(Vec<&'a str>),
+    /// `{1..10}`, the bounds are included and may go down
+    Range(i64, i64),
+}
+
+/// Braces of the word which expand, offsets are in bytes of the word as it is written
+struct Group<'a> {
+    open: usize,
+    close: usize,
+    items: Items<'a>,
+}
+
+/// Expands `{a,b}` lists like csh does and `{1..10}` ranges in the word as it is written,
+/// `a{b,c}d` becomes `abd acd` and braces may be nested. Quoted and escaped braces, `${name}`
+/// and braces without a comma or a range like `{}` are plain characters.
+/// Fails as soon as these words and the `before` ones of the command are more than `limit`.
+pub fn expand_braces(word: &str, before: usize, limit: usize) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    expand_into(word, limit.saturating_sub(before), &mut words).map_err(|_| {
+        Error::TooManyWords(limit)
+    })?;
+    Ok(words)
+}
+
+fn expand_into(word: &str, limit: usize, words: &mut Vec<String>) -> Result<()> {
+    let group = match find_group(word) {
+        Some(group) => group,
+        None => {
+            if words.len() == limit {
+                return Err(Error::TooManyWords(limit));
+            }
+            words.push(String::from(word));
+            return Ok(());
+        }
+    };
+    let (prefix, suffix) = (&word[..group.open], &word[(group.close + 1)..]);
+    match group.items {
+        Items::List(items) => {
+            for item in items {
+                expand_into(&format!("{}{}{}", prefix, item, suffix), limit, words)?;
+            }
+        }
+        Items::Range(from, to) => {
+            let step = if from <= to { 1 } else { -1 };
+            let mut number = from;
+            loop {
+                expand_into(&format!("{}{}{}", prefix, number, suffix), limit, words)?;
+                if number == to {
+                    break;
+                }
+                number += step;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the first braces of the word which expand, the outer ones of nested braces
+fn find_group<'a>(word: &'a str) -> Option<Group<'a>> {
+    let chars = unquoted_chars(word);
+    for (position, &(open, c)) in chars.iter().enumerate() {
+        if c != '{' {
+            continue;
+        }
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        for &(index, c) in &chars[position..] {
+            match c {
+                '{' => depth += 1,
+                ',' if depth == 1 => commas.push(index),
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        if let Some(items) = group_items(word, open, &commas, index) {
+                            return Some(Group { open, close: index, items });
+                        }
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Splits the text between the braces at the commas or reads the range
+fn group_items<'a>(
+    word: &'a str,
+    open: usize,
+    commas: &[usize],
+    close: usize,
+) -> Option<Items<'a>> {
+    if commas.is_empty() {
+        let (from, to) = word[(open + 1)..close].split_once("..")?;
+        return Some(Items::Range(from.parse().ok()?, to.parse().ok()?));
+    }
+    let mut items = Vec::with_capacity(commas.len() + 1);
+    let mut start = open + 1;
+    for &comma in commas.iter().chain(Some(&close)) {
+        items.push(&word[start..comma]);
+        start = comma + 1;
+    }
+    Some(Items::List(items))
+}
+
+/// Gets the characters of the word with their offsets which are neither quoted nor escaped
+/// nor a part of `${name}`
+fn unquoted_chars(word: &str) -> Vec<(usize, char)> {
+    let mut result = Vec::new();
+    let mut chars = word.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                chars.by_ref().find(|(_, inner)| *inner == c);
+            }
+            '\\' => {
+                chars.next();
+            }
+            '$' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                chars.by_ref().find(|(_, inner)| *inner == '}');
+            }
+            c => result.push((index, c)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(word: &str) -> Vec<String> {
+        expand_braces(word, 0, 100).unwrap()
+    }
+
+    #[test]
+    fn lists_and_ranges() {
+        assert_eq!(expand("a{b,c}d"), vec!["abd", "acd"]);
+        assert_eq!(expand("{a,b{1,2},}x"), vec!["ax", "b1x", "b2x", "x"]);
+        assert_eq!(expand("{1..3}{a,b}"), vec!["1a", "1b", "2a", "2b", "3a", "3b"]);
+        assert_eq!(expand("v{2..-1}"), vec!["v2", "v1", "v0", "v-1"]);
+    }
+
+    #[test]
+    fn plain_braces() {
+        for word in &["{}", "{x}", "'{a,b}'", "\\{a,b}", "${x}", "{a..z}", "a{b,c"] {
+            assert_eq!(expand(word), vec![String::from(*word)]);
+        }
+        assert_eq!(expand("${x}{1,2}\"{a,b}\""), vec!["${x}1\"{a,b}\"", "${x}2\"{a,b}\""]);
+    }
+
+    #[test]
+    fn words_are_limited() {
+        assert_eq!(expand_braces("{1..5}", 0, 5).unwrap().len(), 5);
+        assert!(expand_braces("{1..5}", 1, 5).is_err());
+        assert!(expand_braces("{1..10000000000}", 0, 5).is_err());
+        assert!(expand_braces("{a,b}{c,d}{e,f}", 0, 7).is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `@ name = expression`, see `Shell::at_builtin`
+pub struct At;
+
+impl Builtin for At {
+    fn name(&self) -> &'static str {
+        "@"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[name = expression]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.at_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
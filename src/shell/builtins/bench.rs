@@ -0,0 +1,25 @@
+use native::error::*;
+use native::write_to_file;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `bench`: measures the main paths of command execution, see `Shell::run_benchmarks`
+pub struct Bench;
+
+impl Builtin for Bench {
+    fn name(&self) -> &'static str {
+        "bench"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        for measurement in shell.run_benchmarks()? {
+            write_to_file(1, &format!("{}\n", measurement))?;
+        }
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,38 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `bg [%n]` and `bg --detach command`, see `Shell::bg_builtin` and `Shell::detach_builtin`
+pub struct Background;
+
+impl Builtin for Background {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[%job | --detach command]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        match arguments.split_first() {
+            Some((&"--detach", command)) => shell.detach_builtin(command)?,
+            _ => shell.bg_builtin(arguments.first().cloned())?,
+        }
+        Ok(Outcome::Status(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_or_command_is_required() {
+        let mut shell = Shell::new().unwrap();
+        assert!(matches!(Background.execute(&mut shell, &["%9"]), Err(Error::NoSuchJob)));
+        let detach = Background.execute(&mut shell, &["--detach"]);
+        assert!(matches!(detach, Err(Error::MissingArgument(_))));
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `bindkey [options] [keys [function]]`, see `Shell::bindkey_builtin`
+pub struct BindKey;
+
+impl Builtin for BindKey {
+    fn name(&self) -> &'static str {
+        "bindkey"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-a | -e | -v | -l | -s] [keys [function]]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.bindkey_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
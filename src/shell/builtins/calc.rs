@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `calc expression`, see `Shell::calc_builtin`
+pub struct Calc;
+
+impl Builtin for Calc {
+    fn name(&self) -> &'static str {
+        "calc"
+    }
+
+    fn usage(&self) -> &'static str {
+        "expression"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.calc_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `cd [dir]` and its synonym `chdir`, see `Shell::change_dir`
+pub struct ChangeDir(pub &'static str);
+
+impl Builtin for ChangeDir {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn usage(&self) -> &'static str {
+        "[dir]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.change_dir(arguments.first().cloned())?;
+        Ok(Outcome::Status(0))
+    }
+}
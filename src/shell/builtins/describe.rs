@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use libc::O_RDONLY;
+
+use native::error::*;
+use native::file_stat::is_executable_file;
+use native::{close_file, open_file, read_chunk, write_to_file, SCRIPT_SHELL};
+
+use super::super::parse_shebang;
+use super::{Builtin, Outcome, KEYWORDS};
+use super::Shell;
+
+/// Bytes of a program read to tell its kind
+const HEADER_SIZE: usize = 256;
+
+/// `describe name ...`: tells how the shell runs every name, a builtin with its usage
+/// or a program with its path and kind. A name which is neither makes the status 1.
+pub struct Describe;
+
+impl Builtin for Describe {
+    fn name(&self) -> &'static str {
+        "describe"
+    }
+
+    fn usage(&self) -> &'static str {
+        "name ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        if arguments.is_empty() {
+            return Err(Error::MissingArgument("describe"));
+        }
+        let mut status = 0;
+        for name in arguments {
+            match describe(shell, name) {
+                Some(text) => write_to_file(1, &format!("{}\n", text))?,
+                None => {
+                    status = 1;
+                    write_to_file(2, &Error::CommandNotFound(String::from(*name)).diagnostic())?
+                }
+            };
+        }
+        Ok(Outcome::Status(status))
+    }
+}
+
+/// Describes the name like `describe` prints it, None if the shell can't run it
+fn describe(shell: &Shell, name: &str) -> Option<String> {
+    let usage = match shell.builtins.get(name) {
+        Some(builtin) => Some(builtin.usage()),
+        None => KEYWORDS.iter().find(|(keyword, _)| *keyword == name).map(|(_, usage)| *usage),
+    };
+    if let Some(usage) = usage {
+        let usage = format!("{} {}", name, usage);
+        return Some(msg!("{} is a builtin: {}", name, usage.trim_end()));
+    }
+    let path = shell.find_path(name).filter(|path| is_executable_file(path))?;
+    let kind = program_kind(&path);
+    Some(match path.canonicalize() {
+        Ok(target) if target != path => {
+            msg!("{} is {} linked to {}, {}", name, path.display(), target.display(), kind)
+        }
+        _ => msg!("{} is {}, {}", name, path.display(), kind),
+    })
+}
+
+/// Tells a binary from a script by the first bytes of the program
+fn program_kind(path: &Path) -> String {
+    let mut header = [0; HEADER_SIZE];
+    let length = open_file(path, O_RDONLY, None)
+        .and_then(|fd| {
+            let length = read_chunk(fd, &mut header);
+            close_file(fd)?;
+            length
+        })
+        .unwrap_or(0);
+    let header = &header[..length];
+    if header.starts_with(b"\x7fELF") {
+        return msg!("an ELF binary");
+    }
+    let line = header.split(|byte| *byte == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    match parse_shebang(&line) {
+        Some((interpreter, _)) => msg!("a script run by {}", interpreter),
+        // execve(2) refuses it, so it is run like in execvp(3)
+        None => msg!("a script run by {}", SCRIPT_SHELL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn names_are_described() {
+        let dir = TestDir::new("describe");
+        let script = dir.join("tool");
+        std::fs::write(&script, "#!/usr/bin/env python3\n").unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.path = vec![dir.path().to_path_buf()];
+        let tool = describe(&shell, "tool");
+        let expected = format!("tool is {}, a script run by /usr/bin/env", script.display());
+        assert_eq!(tool.unwrap(), expected);
+        assert_eq!(describe(&shell, "cd").unwrap(), "cd is a builtin: cd [dir]");
+        assert_eq!(describe(&shell, "logout").unwrap(), "logout is a builtin: logout");
+        assert_eq!(describe(&shell, "missing"), None);
+        let outcome = Describe.execute(&mut shell, &["pwd", "missing"]).unwrap();
+        assert_eq!(outcome, Outcome::Status(1));
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `dirs`, see `Shell::print_dirs`
+pub struct Dirs;
+
+impl Builtin for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.print_dirs()?;
+        Ok(Outcome::Status(0))
+    }
+}
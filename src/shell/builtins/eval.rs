@@ -0,0 +1,46 @@
+use native::error::*;
+
+use super::super::Flow;
+use super::{after_commands, Builtin, Outcome};
+use super::Shell;
+
+/// `eval argument ...`: joins the arguments and runs every line of the result
+/// as a command of this shell, the words are expanded once more
+pub struct Eval;
+
+impl Builtin for Eval {
+    fn name(&self) -> &'static str {
+        "eval"
+    }
+
+    fn usage(&self) -> &'static str {
+        "argument ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.status = 0;
+        for line in arguments.join(" ").lines() {
+            if shell.parse(line)? == Flow::Exit {
+                return Ok(after_commands(shell, Flow::Exit));
+            }
+        }
+        Ok(after_commands(shell, Flow::Continue))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_runs_joined_arguments() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("name"), String::from("$target"));
+        shell.variables.insert(String::from("target"), String::from("x"));
+        assert_eq!(shell.parse("eval set $name = 1").unwrap(), Flow::Continue);
+        assert_eq!(shell.variables["x"], "1");
+        assert_eq!(Eval.execute(&mut shell, &["exit", "4"]).unwrap(), Outcome::Exit(4));
+        assert_eq!(shell.parse("eval exit 4").unwrap(), Flow::Exit);
+        assert_eq!(shell.status, 4);
+    }
+}
@@ -0,0 +1,50 @@
+use native::error::*;
+use native::ExitCode;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `exit [status]`: stops the shell with the status, the one of the previous command by default
+pub struct Exit;
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[status]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        exit_code(shell.status, arguments.first().cloned()).map(Outcome::Exit)
+    }
+}
+
+/// Gets the exit code from the argument of `exit` falling back to the current status
+fn exit_code(status: ExitCode, argument: Option<&str>) -> Result<ExitCode> {
+    match argument {
+        Some(code) => code.parse().map_err(|_| Error::SyntaxError),
+        None => Ok(status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_argument() {
+        assert_eq!(exit_code(1, None).unwrap(), 1);
+        assert_eq!(exit_code(1, Some("3")).unwrap(), 3);
+        assert!(exit_code(1, Some("three")).is_err());
+    }
+
+    #[test]
+    fn previous_status_is_kept() {
+        let mut shell = Shell::new().unwrap();
+        shell.status = 2;
+        assert_eq!(Exit.execute(&mut shell, &[]).unwrap(), Outcome::Exit(2));
+        assert_eq!(Exit.execute(&mut shell, &["0"]).unwrap(), Outcome::Exit(0));
+    }
+}
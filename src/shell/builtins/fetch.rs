@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `fetch URL`, see `Shell::fetch_builtin`
+pub struct Fetch;
+
+impl Builtin for Fetch {
+    fn name(&self) -> &'static str {
+        "fetch"
+    }
+
+    fn usage(&self) -> &'static str {
+        "URL"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.fetch_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,23 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `fg [%n]`, see `Shell::fg_builtin`
+pub struct Foreground;
+
+impl Builtin for Foreground {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[%job]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.fg_builtin(arguments.first().cloned())?;
+        // the status of the job which has been waited for
+        Ok(Outcome::Status(shell.status))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `hashfile file ...` and `hashfile -s text`, see `Shell::hashfile_builtin`
+pub struct HashFile;
+
+impl Builtin for HashFile {
+    fn name(&self) -> &'static str {
+        "hashfile"
+    }
+
+    fn usage(&self) -> &'static str {
+        "file ... | -s text"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.hashfile_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
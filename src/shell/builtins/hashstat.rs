@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `hashstat`, see `Shell::hashstat_builtin`
+pub struct HashStat;
+
+impl Builtin for HashStat {
+    fn name(&self) -> &'static str {
+        "hashstat"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.hashstat_builtin()?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `incognito [on | off]`, see `Shell::incognito_builtin`
+pub struct Incognito;
+
+impl Builtin for Incognito {
+    fn name(&self) -> &'static str {
+        "incognito"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[on | off]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.incognito_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
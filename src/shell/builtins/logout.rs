@@ -0,0 +1,39 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `logout`: stops a login shell like `exit`, other shells refuse it
+pub struct Logout;
+
+impl Builtin for Logout {
+    fn name(&self) -> &'static str {
+        "logout"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        if !shell.is_login {
+            return Err(Error::NotLoginShell);
+        }
+        Ok(Outcome::Exit(shell.status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_login_shell_logs_out() {
+        let mut shell = Shell::new().unwrap();
+        shell.is_login = false;
+        assert!(matches!(Logout.execute(&mut shell, &[]), Err(Error::NotLoginShell)));
+        shell.is_login = true;
+        shell.status = 1;
+        assert_eq!(Logout.execute(&mut shell, &[]).unwrap(), Outcome::Exit(1));
+    }
+}
@@ -0,0 +1,51 @@
+use native::error::*;
+
+use super::super::pipeline::Stage;
+use super::{after_commands, Builtin, Outcome};
+use super::Shell;
+
+/// `ls-F [name ...]`, see `Shell::ls_f_builtin`. Like in tcsh, options are left to ls:
+/// `ls -F` runs with them instead.
+pub struct LsF;
+
+impl Builtin for LsF {
+    fn name(&self) -> &'static str {
+        "ls-F"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[name ...]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        if !arguments.iter().any(|word| word.starts_with('-')) {
+            shell.ls_f_builtin(arguments)?;
+            return Ok(Outcome::Status(0));
+        }
+        let words = ["ls", "-F"].iter().chain(arguments).map(|word| String::from(*word));
+        let stage = Stage {
+            assignments: Vec::new(),
+            words: words.collect(),
+            group: None,
+            redirections: Vec::new(),
+            with_stderr: false,
+        };
+        let flow = shell.run_pipeline(&[stage], false)?;
+        Ok(after_commands(shell, flow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_run_ls() {
+        let mut shell = Shell::new().unwrap();
+        assert!(LsF.execute(&mut shell, &["/nonexistent/rsh"]).is_err());
+        shell.status = 1;
+        assert_eq!(LsF.execute(&mut shell, &["-d", "/"]).unwrap(), Outcome::Status(0));
+        let missing = LsF.execute(&mut shell, &["-d", "/nonexistent/rsh"]).unwrap();
+        assert_ne!(missing, Outcome::Status(0));
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `mkfifo [-m mode] name ...`, see `Shell::mkfifo_builtin`
+pub struct MakeFifo;
+
+impl Builtin for MakeFifo {
+    fn name(&self) -> &'static str {
+        "mkfifo"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-m mode] name ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.mkfifo_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
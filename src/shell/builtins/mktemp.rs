@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `mktemp [-d] name`, see `Shell::mktemp_builtin`
+pub struct MakeTemp;
+
+impl Builtin for MakeTemp {
+    fn name(&self) -> &'static str {
+        "mktemp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-d] name"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.mktemp_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use native::error::*;
+use native::ExitCode;
+
+use super::{Flow, Shell};
+
+mod at;
+mod bench;
+mod bg;
+mod bindkey;
+mod calc;
+mod cd;
+mod describe;
+mod dirs;
+mod eval;
+mod exit;
+#[cfg(feature = "fetch")]
+mod fetch;
+mod fg;
+mod hashfile;
+mod hashstat;
+mod history;
+mod incognito;
+mod jobs;
+mod logout;
+mod ls_f;
+mod mkfifo;
+mod mktemp;
+mod notify;
+mod pick;
+mod popd;
+mod prompt;
+mod pushd;
+mod pwd;
+mod read;
+mod reattach;
+mod rehash;
+mod reload;
+mod set;
+mod shift;
+mod shuffle;
+mod stats;
+mod suspend;
+mod test;
+mod unset;
+
+/// What the shell does after a builtin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Goes on, the status of the builtin becomes the status of the shell
+    Status(ExitCode),
+    /// Stops reading commands and exits with the status, see `exit` and `logout`
+    Exit(ExitCode),
+}
+
+/// A command which the shell runs itself without forking
+pub trait Builtin {
+    /// The word which calls the command
+    fn name(&self) -> &'static str;
+
+    /// The arguments it takes like `[-m mode] name ...`, empty if none
+    fn usage(&self) -> &'static str;
+
+    /// Runs the command with the expanded arguments and tells how the shell goes on.
+    /// The status of the shell is the one of the previous command meanwhile,
+    /// an error makes it 1.
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome>;
+}
+
+/// The keywords `if` and `repeat` of `ast::parse_line`, which aren't commands of the registry,
+/// with their usages
+pub const KEYWORDS: &[(&str, &str)] =
+    &[("if", "(expression) command"), ("repeat", "count command")];
+
+/// Every builtin of the registry, see `KEYWORDS` for the others
+const ALL: &[&dyn Builtin] = &[
+    &describe::Describe,
+    &exit::Exit,
+    &logout::Logout,
+    &eval::Eval,
+    &reload::Reload,
+    &ls_f::LsF,
+    &pwd::Pwd,
+    &cd::ChangeDir("cd"),
+    &cd::ChangeDir("chdir"),
+    &pushd::PushDir,
+    &popd::PopDir,
+    &dirs::Dirs,
+    &set::Set,
+    &unset::Unset,
+    &shift::Shift,
+    &at::At,
+    &calc::Calc,
+    &read::Read,
+    &history::History,
+    &incognito::Incognito,
+    &stats::Stats,
+    &jobs::Jobs,
+    &fg::Foreground,
+    &bg::Background,
+    &reattach::Reattach,
+    &suspend::Suspend,
+    &notify::Notify,
+    &test::Test("test"),
+    &test::Test("["),
+    &mkfifo::MakeFifo,
+    &mktemp::MakeTemp,
+    &hashfile::HashFile,
+    #[cfg(feature = "fetch")]
+    &fetch::Fetch,
+    &bench::Bench,
+    &bindkey::BindKey,
+    &prompt::Prompt,
+    &shuffle::Shuffle,
+    &pick::Pick,
+    &hashstat::HashStat,
+    &rehash::Rehash,
+];
+
+/// Maps the names of the builtins to them, see `Builtin`
+pub fn registry() -> HashMap<&'static str, &'static dyn Builtin> {
+    ALL.iter().map(|builtin| (builtin.name(), *builtin)).collect()
+}
+
+/// Tells how the shell goes on after the commands run by a builtin like `eval`,
+/// the status is the one of the last command
+fn after_commands(shell: &Shell, flow: Flow) -> Outcome {
+    match flow {
+        Flow::Continue => Outcome::Status(shell.status),
+        Flow::Exit => Outcome::Exit(shell.status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::complete::BUILTINS;
+    use super::*;
+
+    #[test]
+    fn registered_builtins_are_completed() {
+        let registry = registry();
+        assert_eq!(registry.len(), ALL.len());
+        let keywords = KEYWORDS.iter().map(|(name, _)| name);
+        for name in registry.keys().chain(keywords) {
+            assert!(BUILTINS.contains(name), "{} isn't completed", name);
+        }
+        assert_eq!(registry.len() + KEYWORDS.len(), BUILTINS.len());
+    }
+}
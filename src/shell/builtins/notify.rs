@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `notify [%n]`, see `Shell::notify_builtin`
+pub struct Notify;
+
+impl Builtin for Notify {
+    fn name(&self) -> &'static str {
+        "notify"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[%job]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.notify_builtin(arguments.first().cloned())?;
+        Ok(Outcome::Status(0))
+    }
+}
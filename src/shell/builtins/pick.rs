@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `pick words`, see `Shell::pick_builtin`
+pub struct Pick;
+
+impl Builtin for Pick {
+    fn name(&self) -> &'static str {
+        "pick"
+    }
+
+    fn usage(&self) -> &'static str {
+        "word ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.pick_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `popd`, see `Shell::pop_dir`
+pub struct PopDir;
+
+impl Builtin for PopDir {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.pop_dir()?;
+        Ok(Outcome::Status(0))
+    }
+}
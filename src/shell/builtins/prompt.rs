@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `prompt theme [name]` and `prompt preview`, see `Shell::prompt_builtin`
+pub struct Prompt;
+
+impl Builtin for Prompt {
+    fn name(&self) -> &'static str {
+        "prompt"
+    }
+
+    fn usage(&self) -> &'static str {
+        "theme [name] | preview"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.prompt_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `pushd [dir]`, see `Shell::push_dir`
+pub struct PushDir;
+
+impl Builtin for PushDir {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[dir]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.push_dir(arguments.first().cloned())?;
+        Ok(Outcome::Status(0))
+    }
+}
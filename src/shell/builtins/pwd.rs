@@ -0,0 +1,41 @@
+use native::error::*;
+use native::write_to_file;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `pwd`: prints the current directory
+pub struct Pwd;
+
+impl Builtin for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        let cwd = shell.cwd.to_str().ok_or(Error::InvalidUnicode)?;
+        write_to_file(1, &format!("{}\n", cwd))?;
+        Ok(Outcome::Status(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn directory_is_printed() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(Pwd.execute(&mut shell, &[]).unwrap(), Outcome::Status(0));
+        shell.cwd = PathBuf::from(OsStr::from_bytes(b"/\xff"));
+        assert!(matches!(Pwd.execute(&mut shell, &[]), Err(Error::InvalidUnicode)));
+    }
+}
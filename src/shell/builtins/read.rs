@@ -0,0 +1,36 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `read [-0] name ...`, see `Shell::read_builtin`
+pub struct Read;
+
+impl Builtin for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-0] name ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        // the end of input makes the status 1
+        shell.status = 0;
+        shell.read_builtin(arguments)?;
+        Ok(Outcome::Status(shell.status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_required() {
+        let mut shell = Shell::new().unwrap();
+        assert!(matches!(Read.execute(&mut shell, &[]), Err(Error::MissingArgument("read"))));
+        assert!(matches!(Read.execute(&mut shell, &["-0"]), Err(Error::MissingArgument("read"))));
+    }
+}
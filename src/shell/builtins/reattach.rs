@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `reattach [%n]`, see `Shell::reattach_builtin`
+pub struct Reattach;
+
+impl Builtin for Reattach {
+    fn name(&self) -> &'static str {
+        "reattach"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[%job]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.reattach_builtin(arguments.first().cloned())?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,36 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `rehash`: forgets where programs were found in PATH
+pub struct Rehash;
+
+impl Builtin for Rehash {
+    fn name(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.hash.clear();
+        Ok(Outcome::Status(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn found_programs_are_forgotten() {
+        let mut shell = Shell::new().unwrap();
+        shell.hash_lookup("sh").unwrap();
+        assert!(format!("{:?}", shell.hash).contains("\"sh\""));
+        assert_eq!(Rehash.execute(&mut shell, &[]).unwrap(), Outcome::Status(0));
+        assert!(!format!("{:?}", shell.hash).contains("\"sh\""));
+    }
+}
@@ -0,0 +1,40 @@
+use native::error::*;
+
+use super::{after_commands, Builtin, Outcome};
+use super::Shell;
+
+/// `reload`, see `Shell::reload_builtin`
+pub struct Reload;
+
+impl Builtin for Reload {
+    fn name(&self) -> &'static str {
+        "reload"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.status = 0;
+        let flow = shell.reload_builtin()?;
+        Ok(after_commands(shell, flow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn exit_in_rc_file_stops_shell() {
+        let dir = TestDir::new("reload-exit");
+        let path = dir.join("cshrc");
+        std::fs::write(&path, "exit 5\n").unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.options.rc = Some(path.display().to_string());
+        assert_eq!(Reload.execute(&mut shell, &[]).unwrap(), Outcome::Exit(5));
+    }
+}
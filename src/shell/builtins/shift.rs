@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `shift [name]`, see `Shell::shift_variable`
+pub struct Shift;
+
+impl Builtin for Shift {
+    fn name(&self) -> &'static str {
+        "shift"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[name]"
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.shift_variable(arguments.first().cloned())?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `shuffle words`, see `Shell::shuffle_builtin`
+pub struct Shuffle;
+
+impl Builtin for Shuffle {
+    fn name(&self) -> &'static str {
+        "shuffle"
+    }
+
+    fn usage(&self) -> &'static str {
+        "word ..."
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.shuffle_builtin(arguments)?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `stats`, see `Shell::stats_builtin`
+pub struct Stats;
+
+impl Builtin for Stats {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.stats_builtin()?;
+        Ok(Outcome::Status(0))
+    }
+}
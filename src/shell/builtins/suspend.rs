@@ -0,0 +1,22 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `suspend`, see `Shell::suspend_builtin`
+pub struct Suspend;
+
+impl Builtin for Suspend {
+    fn name(&self) -> &'static str {
+        "suspend"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(&self, shell: &mut Shell, _: &[&str]) -> Result<Outcome> {
+        shell.suspend_builtin()?;
+        Ok(Outcome::Status(0))
+    }
+}
@@ -0,0 +1,39 @@
+use native::error::*;
+
+use super::{Builtin, Outcome};
+use super::Shell;
+
+/// `test expression` and its form `[ expression ]`, see `Shell::test_builtin`
+pub struct Test(pub &'static str);
+
+impl Builtin for Test {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn usage(&self) -> &'static str {
+        if self.0 == "[" {
+            "expression ]"
+        } else {
+            "expression"
+        }
+    }
+
+    fn execute(&self, shell: &mut Shell, arguments: &[&str]) -> Result<Outcome> {
+        shell.test_builtin(self.0 == "[", arguments).map(Outcome::Status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_returns_status() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(Test("test").execute(&mut shell, &["-d", "/"]).unwrap(), Outcome::Status(0));
+        let result = Test("[").execute(&mut shell, &["a", "=", "b", "]"]);
+        assert_eq!(result.unwrap(), Outcome::Status(1));
+        assert!(Test("[").execute(&mut shell, &["a"]).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::Path;
+
+use libc::O_RDONLY;
+
+use native::error::*;
+use native::{close_file, open_file, read_at, write_to_file};
+
+use super::Shell;
+
+/// How many bytes of a file are hashed at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Initial hash values: the first 32 bits of the fractional parts of square roots of 8 primes
+const INITIAL: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Round constants: the first 32 bits of the fractional parts of cube roots of 64 primes
+const ROUNDS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 as described in FIPS 180-4, the data may be given in parts
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    /// The incomplete block, it is hashed when 64 bytes are collected
+    block: Vec<u8>,
+    /// Length of all the data in bytes
+    length: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            state: INITIAL,
+            block: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+}
+
+impl Sha256 {
+    /// Adds the bytes to the hashed data
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            let taken = data.len().min(64 - self.block.len());
+            self.block.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+            if self.block.len() == 64 {
+                let block = std::mem::take(&mut self.block);
+                self.compress(&block);
+                self.block = block;
+                self.block.clear();
+            }
+        }
+    }
+
+    /// Pads the data and returns the digest as lowercase hex
+    pub fn finish(self) -> String {
+        self.digest().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Pads the data and returns the digest as 32 bytes
+    pub fn digest(mut self) -> [u8; 32] {
+        let bits = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80u8];
+        let used = (self.block.len() + 1) % 64;
+        let zeros = if used <= 56 { 56 - used } else { 120 - used };
+        padding.resize(1 + zeros, 0);
+        padding.extend_from_slice(&bits.to_be_bytes());
+        let length = self.length;
+        self.update(&padding);
+        self.length = length;
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    /// Mixes a 64 byte block into the state
+    fn compress(&mut self, block: &[u8]) {
+        let mut schedule = [0u32; 64];
+        for (index, word) in block.chunks(4).enumerate() {
+            schedule[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..64 {
+            let before = schedule[index - 15];
+            let s0 = before.rotate_right(7) ^ before.rotate_right(18) ^ (before >> 3);
+            let before = schedule[index - 2];
+            let s1 = before.rotate_right(17) ^ before.rotate_right(19) ^ (before >> 10);
+            schedule[index] = schedule[index - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[index - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for index in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(ROUNDS[index])
+                .wrapping_add(schedule[index]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+/// Hashes the contents of the file reading it in chunks
+fn hash_file(path: &Path) -> Result<String> {
+    let fd = open_file(path, O_RDONLY, None)?;
+    let mut hasher = Sha256::default();
+    let mut offset = 0;
+    let result = loop {
+        match read_at(fd, offset, CHUNK_SIZE) {
+            Ok(ref chunk) if chunk.is_empty() => break Ok(hasher.finish()),
+            Ok(chunk) => {
+                hasher.update(&chunk);
+                offset += chunk.len() as u64;
+            }
+            Err(reason) => break Err(reason),
+        }
+    };
+    close_file(fd)?;
+    result
+}
+
+impl Shell {
+    /// Implements `hashfile file ...` which prints SHA-256 of every file like sha256sum(1)
+    /// and `hashfile -s text` which prints SHA-256 of the text
+    pub fn hashfile_builtin(&self, arguments: &[&str]) -> Result<()> {
+        let text = match arguments {
+            [] | ["-s"] => return Err(Error::MissingArgument("hashfile")),
+            ["-s", words @ ..] => {
+                let mut hasher = Sha256::default();
+                hasher.update(words.join(" ").as_bytes());
+                format!("{}\n", hasher.finish())
+            }
+            files => {
+                let mut text = String::new();
+                for file in files {
+                    let digest = hash_file(Path::new(file)).map_err(|e| e.about(file))?;
+                    text.push_str(&format!("{}  {}\n", digest, file));
+                }
+                text
+            }
+        };
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(data: &[u8]) -> String {
+        let mut hasher = Sha256::default();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn data_in_parts() {
+        let data = vec![b'a'; 1000];
+        let mut hasher = Sha256::default();
+        for part in data.chunks(7) {
+            hasher.update(part);
+        }
+        assert_eq!(hasher.finish(), sha256(&data));
+    }
+
+    #[test]
+    fn files_or_text_are_required() {
+        let shell = Shell::new().unwrap();
+        let missing = shell.hashfile_builtin(&["-s"]);
+        assert!(matches!(missing, Err(Error::MissingArgument("hashfile"))));
+        assert!(shell.hashfile_builtin(&["/nonexistent/rsh"]).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use native::file_stat::is_executable_file;
+
+use super::editor::{Completer, Completion};
+use super::Shell;
+
+/// Names of builtin commands offered when the command word is completed
+pub const BUILTINS: &[&str] = &[
+    "@", "[", "bench", "bg", "bindkey", "calc", "cd", "chdir", "describe", "dirs", "eval", "exit",
+    #[cfg(feature = "fetch")]
+    "fetch",
+    "fg", "hashfile", "hashstat", "history", "if", "incognito", "jobs", "logout", "ls-F",
+    "mkfifo", "mktemp", "notify", "pick", "popd", "prompt", "pushd", "pwd", "read", "reattach",
+    "rehash", "reload", "repeat", "set", "shift", "shuffle", "stats", "suspend", "test", "unset",
+];
+
+impl Completer for Shell {
+    fn complete(&self, word: &str, command: bool) -> Vec<Completion> {
+        if command && !word.contains('/') {
+            self.complete_command(word)
+        } else {
+            self.complete_file(word)
+        }
+    }
+
+    fn predict(&self, command: &str) -> Vec<String> {
+        self.history.arguments(command).to_vec()
+    }
+}
+
+impl Shell {
+    /// Finds builtins and programs in PATH starting with the prefix.
+    /// The most frequently used commands in history go first, others are sorted by name.
+    /// Directories in `nohash` are skipped.
+    fn complete_command(&self, prefix: &str) -> Vec<Completion> {
+        let mut names: Vec<String> = BUILTINS
+            .iter()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| String::from(*name))
+            .collect();
+        for dir in &self.path {
+            if self.is_unhashed(dir) {
+                continue;
+            }
+            let entries = match dir.read_dir() {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                if name.starts_with(prefix) && is_executable_file(&entry.path()) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        rank_by_usage(names, &command_usage(&self.history.entries))
+    }
+
+    /// Finds files starting with the word, directories get a trailing slash.
+    /// Hidden files are offered only if the name starts with a dot.
+    fn complete_file(&self, word: &str) -> Vec<Completion> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(index) => word.split_at(index + 1),
+            None => ("", word),
+        };
+        let path = if dir.is_empty() {
+            self.cwd.clone()
+        } else {
+            self.cwd.join(self.expand_home(dir))
+        };
+        let entries = match path.read_dir() {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let hidden = name.starts_with('.') && !prefix.starts_with('.');
+                if hidden || !name.starts_with(prefix) {
+                    return None;
+                }
+                let is_dir = entry.path().is_dir();
+                Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+            })
+            .collect();
+        names.sort();
+        names.into_iter().map(|text| Completion { text, uses: 0 }).collect()
+    }
+}
+
+/// Counts how many times every command was run according to the history
+pub fn command_usage(history: &[String]) -> HashMap<&str, usize> {
+    let mut usage = HashMap::new();
+    for entry in history {
+        if let Some(command) = entry.split_whitespace().next() {
+            *usage.entry(command).or_insert(0) += 1;
+        }
+    }
+    usage
+}
+
+/// Sorts the names by usage keeping the order of names used equally often
+fn rank_by_usage(names: Vec<String>, usage: &HashMap<&str, usize>) -> Vec<Completion> {
+    let mut completions: Vec<Completion> = names
+        .into_iter()
+        .map(|text| {
+            let uses = usage.get(text.as_str()).cloned().unwrap_or(0);
+            Completion { text, uses }
+        })
+        .collect();
+    completions.sort_by_key(|completion| Reverse(completion.uses));
+    completions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_commands_by_usage() {
+        let history = vec![
+            String::from("git status"),
+            String::from("gcc main.c"),
+            String::from("git commit"),
+        ];
+        let usage = command_usage(&history);
+        let names = vec![String::from("gcc"), String::from("gdb"), String::from("git")];
+        let ranked: Vec<String> = rank_by_usage(names, &usage)
+            .into_iter()
+            .map(|completion| completion.text)
+            .collect();
+        assert_eq!(ranked, vec!["git", "gcc", "gdb"]);
+    }
+
+    #[test]
+    fn builtins_are_sorted() {
+        let mut sorted = BUILTINS.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, BUILTINS);
+    }
+}
@@ -0,0 +1,206 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use libc::{S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG};
+
+use native::error::*;
+use native::file_stat::{get_file_mode, get_file_size, get_link_mode, may_access, Access};
+use native::term::is_terminal;
+use native::ExitCode;
+
+use super::arith::{compare_numbers, parse_number};
+use super::Shell;
+
+impl Shell {
+    /// Implements `test expression` and `[ expression ]` without forking:
+    /// returns the status 0 if the expression is true and 1 otherwise.
+    /// An empty expression is false, a malformed one is an error.
+    pub fn test_builtin(&self, bracket: bool, words: &[&str]) -> Result<ExitCode> {
+        let words = match (bracket, words.split_last()) {
+            (true, Some((&"]", words))) => words,
+            (true, _) => return Err(Error::MissingArgument("]")),
+            (false, _) => words,
+        };
+        if words.is_empty() {
+            return Ok(1);
+        }
+        let mut condition = Condition { words, position: 0, cwd: &self.cwd };
+        let value = condition.any()?;
+        if let Some(word) = condition.peek() {
+            return Err(Error::SyntaxError.about(word));
+        }
+        Ok(if value { 0 } else { 1 })
+    }
+}
+
+/// Parses the words of `test` and evaluates them at once.
+/// `-o` binds weaker than `-a` which binds weaker than `!`, parentheses group.
+struct Condition<'a, 'b> {
+    words: &'b [&'a str],
+    position: usize,
+    /// Relative names of files are looked up in it
+    cwd: &'b Path,
+}
+
+impl<'a, 'b> Condition<'a, 'b> {
+    fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Result<&'a str> {
+        let word = self.peek().ok_or(Error::NoMoreWords)?;
+        self.position += 1;
+        Ok(word)
+    }
+
+    fn accept(&mut self, word: &str) -> bool {
+        let found = self.peek() == Some(word);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn any(&mut self) -> Result<bool> {
+        let mut value = self.all()?;
+        while self.accept("-o") {
+            // both sides are parsed to find syntax errors
+            value |= self.all()?;
+        }
+        Ok(value)
+    }
+
+    fn all(&mut self) -> Result<bool> {
+        let mut value = self.negation()?;
+        while self.accept("-a") {
+            value &= self.negation()?;
+        }
+        Ok(value)
+    }
+
+    fn negation(&mut self) -> Result<bool> {
+        // `! = x` compares the string "!"
+        if self.words.get(self.position + 1).is_none_or(|word| !is_binary(word))
+            && self.accept("!")
+        {
+            return Ok(!self.negation()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool> {
+        let left = self.next()?;
+        if let Some(operator) = self.peek().filter(|word| is_binary(word)) {
+            self.position += 1;
+            let right = self.next()?;
+            return compare(left, operator, right);
+        }
+        if left == "(" {
+            let value = self.any()?;
+            return match self.next() {
+                Ok(")") => Ok(value),
+                Ok(word) => Err(Error::SyntaxError.about(word)),
+                Err(_) => Err(Error::MissingArgument("(")),
+            };
+        }
+        if is_unary(left) {
+            if let Some(operand) = self.peek() {
+                self.position += 1;
+                return Ok(self.check(left, operand));
+            }
+        }
+        // a lone word is true unless it is empty
+        Ok(!left.is_empty())
+    }
+
+    /// Applies a unary operator like `-f name`
+    fn check(&self, operator: &str, operand: &str) -> bool {
+        let path = self.cwd.join(operand);
+        let has_type = |kind| get_file_mode(&path).is_ok_and(|mode| mode & S_IFMT == kind);
+        match operator {
+            "-n" => !operand.is_empty(),
+            "-z" => operand.is_empty(),
+            "-e" => get_file_mode(&path).is_ok(),
+            "-f" => has_type(S_IFREG),
+            "-d" => has_type(S_IFDIR),
+            "-p" => has_type(S_IFIFO),
+            "-L" | "-h" => get_link_mode(&path).is_ok_and(|mode| mode & S_IFMT == S_IFLNK),
+            "-r" => may_access(&path, Access::Read),
+            "-w" => may_access(&path, Access::Write),
+            "-x" => may_access(&path, Access::Execute),
+            "-s" => get_file_size(&path).is_ok_and(|size| size > 0),
+            "-t" => operand.parse().is_ok_and(is_terminal),
+            _ => unreachable!("{} isn't a unary operator", operator),
+        }
+    }
+}
+
+fn is_unary(word: &str) -> bool {
+    matches!(
+        word,
+        "-n" | "-z" | "-e" | "-f" | "-d" | "-p" | "-L" | "-h" | "-r" | "-w" | "-x" | "-s" | "-t"
+    )
+}
+
+fn is_binary(word: &str) -> bool {
+    matches!(
+        word,
+        "=" | "==" | "!=" | "<" | ">" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge"
+    )
+}
+
+/// Applies a binary operator, strings are compared byte by byte and numbers by value
+fn compare(left: &str, operator: &str, right: &str) -> Result<bool> {
+    let ordering = match operator {
+        "=" | "==" => return Ok(left == right),
+        "!=" => return Ok(left != right),
+        "<" => return Ok(left < right),
+        ">" => return Ok(left > right),
+        _ => {
+            let left = parse_number(left).map_err(|reason| reason.about(left))?;
+            let right = parse_number(right).map_err(|reason| reason.about(right))?;
+            compare_numbers(left, right)
+        }
+    };
+    Ok(match operator {
+        "-eq" => ordering == Ordering::Equal,
+        "-ne" => ordering != Ordering::Equal,
+        "-lt" => ordering == Ordering::Less,
+        "-le" => ordering != Ordering::Greater,
+        "-gt" => ordering == Ordering::Greater,
+        _ => ordering != Ordering::Less,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(line: &str) -> Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut condition = Condition { words: &words, position: 0, cwd: Path::new("/") };
+        condition.any()
+    }
+
+    #[test]
+    fn conditions() {
+        assert!(evaluate("3 -lt 10").unwrap());
+        assert!(!evaluate("3 -gt 10").unwrap());
+        assert!(evaluate("3 > 10").unwrap());
+        assert!(evaluate("1.5 -le 2 -a abc = abc").unwrap());
+        assert!(evaluate("! -z x -o -f /nonexistent").unwrap());
+        assert!(evaluate("( -d /nonexistent -o -d tmp ) -a ! -f tmp").unwrap());
+        assert!(evaluate("-e").unwrap());
+        assert!(evaluate("! = !").unwrap());
+        assert!(evaluate("x -eq 1").is_err());
+        assert!(evaluate("( -n x").is_err());
+    }
+
+    #[test]
+    fn empty_expression_is_false() {
+        let shell = Shell::new().unwrap();
+        assert_eq!(shell.test_builtin(false, &[]).unwrap(), 1);
+        assert_eq!(shell.test_builtin(true, &["]"]).unwrap(), 1);
+        assert!(shell.test_builtin(true, &[]).is_err());
+    }
+}
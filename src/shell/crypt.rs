@@ -0,0 +1,357 @@
+use std::env::var_os;
+use std::fmt::{self, Debug, Formatter};
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+use libc::{EINTR, O_RDONLY, O_RDWR, S_IRUSR, S_IWUSR};
+
+use native::error::*;
+use native::term::RawMode;
+use native::{close_file, connect_socket, open_file, read_byte, read_bytes, read_chunk,
+             read_file, write_file_atomically, write_to_file};
+
+use super::checksum::Sha256;
+use super::history::CRYPT_MAGIC as MAGIC;
+use super::Shell;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 32;
+/// Rounds of PBKDF2, they make every guess of the passphrase expensive
+const ITERATIONS: u32 = 100_000;
+/// Environment variable with the Unix socket of an agent which sends the passphrase
+const KEY_SOCKET: &str = "RSH_KEY_SOCKET";
+/// Keys which the passphrase prompt handles in raw mode
+const INTERRUPT_KEY: u8 = 3;
+const END_OF_FILE_KEY: u8 = 4;
+const BACKSPACE_KEY: u8 = 8;
+const DELETE_KEY: u8 = 127;
+
+/// Keys for the history file derived from the passphrase and the salt stored in the file
+#[derive(Clone)]
+pub struct HistoryKey {
+    salt: [u8; SALT_SIZE],
+    cipher: [u8; 32],
+    mac: [u8; 32],
+}
+
+/// The keys must never get into a debug dump
+impl Debug for HistoryKey {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "HistoryKey {{ .. }}")
+    }
+}
+
+impl HistoryKey {
+    /// Stretches the passphrase with PBKDF2-HMAC-SHA256 into the cipher and the MAC keys
+    fn derive(passphrase: &[u8], salt: [u8; SALT_SIZE], iterations: u32) -> Self {
+        let mut keys = [0; 64];
+        pbkdf2(passphrase, &salt, iterations, &mut keys);
+        let mut key = HistoryKey {
+            salt,
+            cipher: [0; 32],
+            mac: [0; 32],
+        };
+        key.cipher.copy_from_slice(&keys[..32]);
+        key.mac.copy_from_slice(&keys[32..]);
+        key
+    }
+
+    /// Encrypts the data with ChaCha20 and appends HMAC-SHA256 of everything before the tag
+    fn seal(&self, data: &[u8], nonce: [u8; NONCE_SIZE]) -> Vec<u8> {
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend_from_slice(&self.salt);
+        sealed.extend_from_slice(&nonce);
+        let start = sealed.len();
+        sealed.extend_from_slice(data);
+        chacha20(&self.cipher, &nonce, &mut sealed[start..]);
+        let tag = Hmac::new(&self.mac).compute(&sealed);
+        sealed.extend_from_slice(&tag);
+        sealed
+    }
+
+    /// Checks the tag of the sealed data and decrypts it
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let (signed, tag) = sealed.split_at(sealed.len() - TAG_SIZE);
+        if !same(&Hmac::new(&self.mac).compute(signed), tag) {
+            return Err(Error::DecryptionFailed);
+        }
+        let start = MAGIC.len() + SALT_SIZE;
+        let mut nonce = [0; NONCE_SIZE];
+        nonce.copy_from_slice(&signed[start..start + NONCE_SIZE]);
+        let mut data = signed[start + NONCE_SIZE..].to_vec();
+        chacha20(&self.cipher, &nonce, &mut data);
+        Ok(data)
+    }
+}
+
+/// Gets the salt of the sealed data checking that it's long enough and starts with the magic
+fn salt_of(sealed: &[u8]) -> Result<[u8; SALT_SIZE]> {
+    if sealed.len() < MAGIC.len() + SALT_SIZE + NONCE_SIZE + TAG_SIZE || !sealed.starts_with(MAGIC)
+    {
+        return Err(Error::DecryptionFailed);
+    }
+    let mut salt = [0; SALT_SIZE];
+    salt.copy_from_slice(&sealed[MAGIC.len()..MAGIC.len() + SALT_SIZE]);
+    Ok(salt)
+}
+
+/// Compares the tags in constant time, so the time doesn't tell how many bytes match
+fn same(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len() && left.iter().zip(right).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// HMAC-SHA256 as described in RFC 2104, the padded keys are hashed once
+struct Hmac {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl Hmac {
+    fn new(key: &[u8]) -> Self {
+        let mut block = [0; 64];
+        if key.len() > block.len() {
+            let mut hasher = Sha256::default();
+            hasher.update(key);
+            block[..32].copy_from_slice(&hasher.digest());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Sha256::default();
+        inner.update(&block.map(|byte| byte ^ 0x36));
+        let mut outer = Sha256::default();
+        outer.update(&block.map(|byte| byte ^ 0x5c));
+        Hmac { inner, outer }
+    }
+
+    fn compute(&self, data: &[u8]) -> [u8; 32] {
+        let mut inner = self.inner.clone();
+        inner.update(data);
+        let mut outer = self.outer.clone();
+        outer.update(&inner.digest());
+        outer.digest()
+    }
+}
+
+/// PBKDF2 with HMAC-SHA256 as described in RFC 8018, fills the output with the derived key
+fn pbkdf2(passphrase: &[u8], salt: &[u8], iterations: u32, output: &mut [u8]) {
+    let hmac = Hmac::new(passphrase);
+    for (index, chunk) in output.chunks_mut(32).enumerate() {
+        let mut first = salt.to_vec();
+        first.extend_from_slice(&(index as u32 + 1).to_be_bytes());
+        let mut block = hmac.compute(&first);
+        let mut result = block;
+        for _ in 1..iterations {
+            block = hmac.compute(&block);
+            for (byte, mixed) in result.iter_mut().zip(&block) {
+                *byte ^= mixed;
+            }
+        }
+        chunk.copy_from_slice(&result[..chunk.len()]);
+    }
+}
+
+/// Mixes four words of the ChaCha state
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// The ChaCha20 block function as described in RFC 8439
+fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; NONCE_SIZE]) -> [u8; 64] {
+    let word = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    // "expand 32-byte k"
+    let mut initial = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0, 0, 0, 0, 0, 0, 0, 0,
+                       counter, 0, 0, 0];
+    for (index, bytes) in key.chunks(4).enumerate() {
+        initial[4 + index] = word(bytes);
+    }
+    for (index, bytes) in nonce.chunks(4).enumerate() {
+        initial[13 + index] = word(bytes);
+    }
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    let mut block = [0; 64];
+    for (index, bytes) in block.chunks_mut(4).enumerate() {
+        bytes.copy_from_slice(&state[index].wrapping_add(initial[index]).to_le_bytes());
+    }
+    block
+}
+
+/// Encrypts or decrypts the data in place, the block counter starts at 1 like in RFC 8439
+fn chacha20(key: &[u8; 32], nonce: &[u8; NONCE_SIZE], data: &mut [u8]) {
+    for (index, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = chacha20_block(key, index as u32 + 1, nonce);
+        for (byte, mask) in chunk.iter_mut().zip(stream.iter()) {
+            *byte ^= mask;
+        }
+    }
+}
+
+/// Fills the buffer with bytes of the system random number generator
+fn random_bytes(buf: &mut [u8]) -> Result<()> {
+    let fd = open_file(Path::new("/dev/urandom"), O_RDONLY, None)?;
+    let mut filled = 0;
+    let result = loop {
+        if filled == buf.len() {
+            break Ok(());
+        }
+        match read_chunk(fd, &mut buf[filled..]) {
+            Ok(0) => break Err(Error::NotFound),
+            Ok(count) => filled += count,
+            Err(reason) => break Err(reason),
+        }
+    };
+    close_file(fd)?;
+    result
+}
+
+/// Gets the passphrase from the agent at `RSH_KEY_SOCKET` or asks the user on the terminal
+fn read_passphrase() -> Result<Vec<u8>> {
+    match var_os(KEY_SOCKET) {
+        Some(socket) => {
+            let fd = connect_socket(Path::new(&socket))?;
+            let text = read_file(fd);
+            close_file(fd)?;
+            let text = text?;
+            Ok(text.lines().next().unwrap_or("").as_bytes().to_vec())
+        }
+        None => {
+            let tty = open_file(Path::new("/dev/tty"), O_RDWR, None)?;
+            let passphrase = prompt_on(tty);
+            close_file(tty)?;
+            passphrase
+        }
+    }
+}
+
+/// Prints the prompt and reads the passphrase without echo till Enter
+fn prompt_on(tty: RawFd) -> Result<Vec<u8>> {
+    write_to_file(tty, &msg!("History passphrase: "))?;
+    let mut passphrase = Vec::new();
+    let result = {
+        let _raw = RawMode::enable(tty)?;
+        loop {
+            match read_byte(tty) {
+                Ok(Some(b'\r')) | Ok(Some(b'\n')) => break Ok(()),
+                Ok(Some(INTERRUPT_KEY)) | Ok(Some(END_OF_FILE_KEY)) | Ok(None) => {
+                    break Err(Error::Aborted)
+                }
+                Ok(Some(BACKSPACE_KEY)) | Ok(Some(DELETE_KEY)) => {
+                    passphrase.pop();
+                }
+                Ok(Some(byte)) => passphrase.push(byte),
+                Err(ref reason) if reason.is_errno(EINTR) => {}
+                Err(reason) => break Err(reason),
+            }
+        }
+    };
+    write_to_file(tty, "\r\n")?;
+    result.map(|_| passphrase)
+}
+
+impl Shell {
+    /// Decrypts the history file with `histcrypt` set, the passphrase is asked for once.
+    /// A missing file is an empty history, the passphrase is asked for to encrypt it later.
+    pub fn read_encrypted_history(&mut self, path: &Path) -> Result<String> {
+        if !path.exists() {
+            if self.history.key.is_none() {
+                let mut salt = [0; SALT_SIZE];
+                random_bytes(&mut salt)?;
+                self.history.key = Some(HistoryKey::derive(&read_passphrase()?, salt, ITERATIONS));
+            }
+            return Ok(String::new());
+        }
+        let fd = open_file(path, O_RDONLY, None)?;
+        let sealed = read_bytes(fd);
+        close_file(fd)?;
+        let sealed = sealed?;
+        let salt = salt_of(&sealed)?;
+        let key = match self.history.key {
+            Some(ref key) if key.salt == salt => key.clone(),
+            _ => HistoryKey::derive(&read_passphrase()?, salt, ITERATIONS),
+        };
+        let data = key.open(&sealed)?;
+        self.history.key = Some(key);
+        String::from_utf8(data).map_err(|_| Error::InvalidUnicode)
+    }
+
+    /// Encrypts the history with a fresh nonce and replaces the file atomically.
+    /// The file must have been read first, so the key is known.
+    pub fn write_encrypted_history(&mut self, path: &Path, text: &str) -> Result<()> {
+        let key = match self.history.key {
+            Some(ref key) => key,
+            None => return Err(Error::DecryptionFailed),
+        };
+        let mut nonce = [0; NONCE_SIZE];
+        random_bytes(&mut nonce)?;
+        write_file_atomically(path, &key.seal(text.as_bytes(), nonce), S_IRUSR | S_IWUSR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn known_vectors() {
+        // RFC 8439, 2.3.2 and 2.4.2
+        let mut key = [0; 32];
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        let nonce = [0, 0, 0, 9, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        assert_eq!(
+            hex(&chacha20_block(&key, 1, &nonce)[..16]),
+            "10f1e7e4d13b5915500fdd1fa32071c4"
+        );
+        let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let mut text = b"Ladies and Gentlemen of the class of '99".to_vec();
+        chacha20(&key, &nonce, &mut text);
+        assert_eq!(hex(&text[..16]), "6e2e359a2568f98041ba0728dd0d6981");
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex(&Hmac::new(b"Jefe").compute(b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let mut derived = [0; 32];
+        pbkdf2(b"password", b"salt", 2, &mut derived);
+        assert_eq!(
+            hex(&derived),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+        );
+    }
+
+    #[test]
+    fn sealed_round_trip() {
+        let key = HistoryKey::derive(b"secret", [7; SALT_SIZE], 2);
+        let sealed = key.seal(b"ls -l\n", [1; NONCE_SIZE]);
+        assert_eq!(salt_of(&sealed).unwrap(), [7; SALT_SIZE]);
+        assert_eq!(key.open(&sealed).unwrap(), b"ls -l\n");
+        let wrong = HistoryKey::derive(b"guess", [7; SALT_SIZE], 2);
+        assert!(wrong.open(&sealed).is_err());
+        let mut damaged = sealed.clone();
+        damaged[MAGIC.len() + SALT_SIZE + NONCE_SIZE] ^= 1;
+        assert!(key.open(&damaged).is_err());
+        assert!(salt_of(b"ls -l\n").is_err());
+    }
+}
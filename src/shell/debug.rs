@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use libc::{O_APPEND, O_CREAT, O_WRONLY, S_IRUSR, S_IWUSR};
+
+use native::error::*;
+use native::{close_file, open_file, write_to_file};
+
+use super::Shell;
+
+/// Kinds of internal diagnostics enabled with `set debug = parser,exec,jobs`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Debug {
+    /// Words of the command before and after expansion
+    Parser,
+    /// Builtins and resolved paths of programs
+    Exec,
+    /// Forks of child processes and their exit codes
+    Jobs,
+}
+
+impl Debug {
+    fn name(self) -> &'static str {
+        match self {
+            Debug::Parser => "parser",
+            Debug::Exec => "exec",
+            Debug::Jobs => "jobs",
+        }
+    }
+}
+
+impl Shell {
+    /// Writes the message to stderr or to the `debuglog` file if the category is enabled.
+    /// The message is built lazily, failures to write it are ignored.
+    pub fn debug<F>(&self, category: Debug, message: F)
+    where
+        F: FnOnce() -> String,
+    {
+        let enabled = match self.variables.get("debug") {
+            Some(value) => is_enabled(value, category),
+            None => false,
+        };
+        if !enabled {
+            return;
+        }
+        let text = format!("rsh[{}]: {}\n", category.name(), message());
+        let result = match self.variables.get("debuglog") {
+            Some(path) => append_to_file(Path::new(path), &text),
+            None => write_to_file(2, &text).map(|_| ()),
+        };
+        result.ok();
+    }
+}
+
+/// Checks whether the comma or space separated list enables the category,
+/// an empty value and `all` enable every category
+fn is_enabled(value: &str, category: Debug) -> bool {
+    let mut names = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .peekable();
+    if names.peek().is_none() {
+        return true;
+    }
+    names.any(|name| name == "all" || name == category.name())
+}
+
+/// Appends the text to the file creating it if needed
+fn append_to_file(path: &Path, text: &str) -> Result<()> {
+    let fd = open_file(path, O_WRONLY | O_CREAT | O_APPEND, Some(S_IRUSR | S_IWUSR))?;
+    let result = write_to_file(fd, text);
+    close_file(fd)?;
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_categories() {
+        assert!(is_enabled("parser,exec", Debug::Exec));
+        assert!(is_enabled("parser exec", Debug::Parser));
+        assert!(!is_enabled("parser,exec", Debug::Jobs));
+        assert!(is_enabled("all", Debug::Jobs));
+        assert!(is_enabled("", Debug::Jobs));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::process::exit;
+
+use libc::{EIO, O_RDWR};
+
+use native::error::*;
+use native::term::{is_terminal, open_pty, set_window_size, take_controlling_terminal, RawMode};
+use native::{accept_connection, close_file, connect_socket, listen_socket, open_file, read_chunk,
+             remove_file, replace_fdi, set_process_group, start_process, wait_inputs,
+             wait_process, write_all, write_to_file, ExitCode};
+
+use super::quoting::quote_word;
+use super::Shell;
+
+/// How much of the latest output of a detached job is kept to be shown on reattaching
+const SCROLLBACK_SIZE: usize = 64 * 1024;
+/// Size of the buffer used to pass data between the terminal and the job
+const RELAY_BUFFER_SIZE: usize = 4096;
+/// Ctrl-\ detaches from the job like in dtach
+const DETACH_KEY: u8 = 0x1c;
+
+impl Shell {
+    /// Implements `bg --detach command ...`: starts the command as a background job
+    /// on a pseudo-terminal of its own. A holder process keeps the master side, buffers
+    /// the output and lets `reattach` connect to it through a socket in the temporary directory.
+    pub fn detach_builtin(&mut self, words: &[&str]) -> Result<()> {
+        if words.is_empty() {
+            return Err(Error::MissingArgument("bg --detach"));
+        }
+        let line: Vec<String> = words.iter().map(|word| quote_word(word)).collect();
+        let line = line.join(" ");
+        let socket = self.temp_dir()?.join(format!("job.{}", self.jobs.next_id()));
+        // a holder which was killed leaves its socket behind
+        remove_file(&socket).ok();
+        let (master, slave) = open_pty()?;
+        if let Some(size) = self.window {
+            set_window_size(master, size).ok();
+        }
+        let listener = match listen_socket(&socket) {
+            Ok(fd) => fd,
+            Err(reason) => {
+                close_file(master)?;
+                return Err(reason);
+            }
+        };
+        let holder = start_process(|| {
+            match self.hold_terminal(master, listener, &slave, &socket, &line) {
+                Ok(status) => exit(status),
+                Err(reason) => reason,
+            }
+        });
+        close_file(master)?;
+        close_file(listener)?;
+        let holder = holder?;
+        // the holder does it too, but `reattach` may come earlier
+        set_process_group(holder, holder).ok();
+        let id = self.start_job(vec![holder], line)?;
+        if let Some(job) = self.jobs.get(id) {
+            job.socket = Some(socket);
+        }
+        Ok(())
+    }
+
+    /// Implements `reattach [%n]`: connects the terminal to the detached job.
+    /// The buffered output is shown first, Ctrl-\ detaches again.
+    pub fn reattach_builtin(&mut self, spec: Option<&str>) -> Result<()> {
+        self.update_jobs()?;
+        let job = self.jobs.find(spec).ok_or(Error::NoSuchJob)?;
+        let socket = job.socket.clone().ok_or(Error::NotDetached)?;
+        write_to_file(1, &format!("{}\n", job.command))?;
+        let fd = connect_socket(&socket)?;
+        write_to_file(2, &format!("{}\n", msg!("Press Ctrl-\\ to detach")))?;
+        let result = if is_terminal(0) {
+            let _raw = RawMode::enable(0)?;
+            relay_terminal(fd)
+        } else {
+            relay_terminal(fd)
+        };
+        close_file(fd)?;
+        write_to_file(1, "\n")?;
+        result?;
+        self.update_jobs()
+    }
+
+    /// Runs in the holder: starts the command on the pseudo-terminal and relays its output
+    /// till every process of the job closes the terminal. Returns the status of the command.
+    fn hold_terminal(
+        &mut self,
+        master: RawFd,
+        listener: RawFd,
+        slave: &Path,
+        socket: &Path,
+        line: &str,
+    ) -> Result<ExitCode> {
+        // terminal signals of the shell must not reach the holder
+        set_process_group(0, 0)?;
+        let command = start_process(|| {
+            close_file(master).ok();
+            close_file(listener).ok();
+            if let Err(reason) = attach_terminal(slave) {
+                return reason;
+            }
+            // the pseudo-terminal belongs to the job, there is nobody to give it back to
+            self.jobs.control = false;
+            match self.parse(line) {
+                Ok(_) => exit(self.status),
+                Err(reason) => reason,
+            }
+        })?;
+        let result = hold_output(master, listener);
+        remove_file(socket).ok();
+        let status = wait_process(command)?;
+        result?;
+        Ok(status)
+    }
+}
+
+/// Opens the pseudo-terminal as the controlling terminal of a new session and makes it
+/// stdin, stdout and stderr of the process
+fn attach_terminal(slave: &Path) -> Result<()> {
+    let fd = open_file(slave, O_RDWR, None)?;
+    take_controlling_terminal(fd)?;
+    for target in 0..=2 {
+        replace_fdi(target, fd)?;
+    }
+    if fd > 2 {
+        close_file(fd)?;
+    }
+    Ok(())
+}
+
+/// Keeps the latest output of the job and passes it to the attached client, the client's input
+/// goes to the job. A new client takes the job over and gets the kept output first.
+fn hold_output(master: RawFd, listener: RawFd) -> Result<()> {
+    let mut scrollback: VecDeque<u8> = VecDeque::with_capacity(SCROLLBACK_SIZE);
+    let mut client: Option<RawFd> = None;
+    let mut buf = vec![0; RELAY_BUFFER_SIZE];
+    loop {
+        let mut fds = vec![master, listener];
+        fds.extend(client);
+        let ready = wait_inputs(&fds)?;
+        if ready[0] {
+            let count = match read_chunk(master, &mut buf) {
+                Ok(count) => count,
+                // every process of the job has closed the terminal
+                Err(ref reason) if reason.is_errno(EIO) => 0,
+                Err(reason) => return Err(reason),
+            };
+            if count == 0 {
+                break;
+            }
+            scrollback.extend(&buf[..count]);
+            let extra = scrollback.len().saturating_sub(SCROLLBACK_SIZE);
+            scrollback.drain(..extra);
+            if let Some(fd) = client {
+                if write_all(fd, &buf[..count]).is_err() {
+                    close_file(fd)?;
+                    client = None;
+                }
+            }
+        }
+        if let (Some(fd), Some(true)) = (client, ready.get(2)) {
+            match read_chunk(fd, &mut buf) {
+                Ok(0) | Err(_) => {
+                    close_file(fd)?;
+                    client = None;
+                }
+                Ok(count) => write_all(master, &buf[..count])?,
+            }
+        }
+        if ready[1] {
+            let fd = accept_connection(listener)?;
+            if let Some(previous) = client.replace(fd) {
+                close_file(previous)?;
+            }
+            let (first, second) = scrollback.as_slices();
+            if write_all(fd, first).and_then(|_| write_all(fd, second)).is_err() {
+                close_file(fd)?;
+                client = None;
+            }
+        }
+    }
+    if let Some(fd) = client {
+        close_file(fd)?;
+    }
+    Ok(())
+}
+
+/// Passes the keys typed on the terminal to the job and its output back
+/// till Ctrl-\ is typed or the job finishes
+fn relay_terminal(job: RawFd) -> Result<()> {
+    let mut buf = vec![0; RELAY_BUFFER_SIZE];
+    loop {
+        let ready = wait_inputs(&[0, job])?;
+        if ready[1] {
+            let count = read_chunk(job, &mut buf)?;
+            if count == 0 {
+                return Ok(());
+            }
+            write_all(1, &buf[..count])?;
+        }
+        if ready[0] {
+            let count = read_chunk(0, &mut buf)?;
+            if count == 0 {
+                return Ok(());
+            }
+            let data = &buf[..count];
+            match data.iter().position(|byte| *byte == DETACH_KEY) {
+                Some(index) => return write_all(job, &data[..index]),
+                None => write_all(job, data)?,
+            }
+        }
+    }
+}
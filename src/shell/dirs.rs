@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use native::{change_dir, get_current_dir, write_to_file};
+use native::error::*;
+
+use super::Shell;
+
+impl Shell {
+    /// Changes the current directory to the provided one or to the home directory if none.
+    /// Relative names which do not exist in the current directory are looked up in `cdpath`.
+    pub fn change_dir(&mut self, dir: Option<&str>) -> Result<()> {
+        match dir {
+            Some(dir) => self.enter_word(dir),
+            None => {
+                let home = self.home.clone();
+                self.enter_dir(&home)
+            }
+        }
+    }
+
+    /// Pushes the current directory onto the stack and changes to the provided one.
+    /// Without an argument it exchanges the current directory with the top of the stack.
+    pub fn push_dir(&mut self, dir: Option<&str>) -> Result<()> {
+        let previous = self.cwd.clone();
+        match dir {
+            Some(dir) => self.enter_word(dir)?,
+            None => {
+                let top = self.dir_stack.pop().ok_or(Error::NoOtherDirectory)?;
+                if let Err(reason) = self.enter_dir(&top) {
+                    self.dir_stack.push(top);
+                    return Err(reason);
+                }
+            }
+        }
+        self.dir_stack.push(previous);
+        self.print_dirs()
+    }
+
+    /// Pops the top of the stack and changes to it.
+    pub fn pop_dir(&mut self) -> Result<()> {
+        let top = self.dir_stack.pop().ok_or(Error::DirStackEmpty)?;
+        if let Err(reason) = self.enter_dir(&top) {
+            self.dir_stack.push(top);
+            return Err(reason);
+        }
+        self.print_dirs()
+    }
+
+    /// Prints the current directory followed by the stack, the most recent entry first.
+    pub fn print_dirs(&self) -> Result<()> {
+        let dirs: Vec<String> = Some(&self.cwd)
+            .into_iter()
+            .chain(self.dir_stack.iter().rev())
+            .map(|dir| abbreviate_home(dir, &self.home))
+            .collect();
+        write_to_file(1, &format!("{}\n", dirs.join(" ")))?;
+        Ok(())
+    }
+
+    /// Changes to the directory named by the word searching `cdpath` if needed.
+    /// The resulting directory is printed when it was found using `cdpath`.
+    fn enter_word(&mut self, word: &str) -> Result<()> {
+        let target = self.expand_home(word);
+        if let Some(found) = self.search_cdpath(&target) {
+            self.enter_dir(&found)?;
+            let cwd = abbreviate_home(&self.cwd, &self.home);
+            write_to_file(1, &format!("{}\n", cwd))?;
+            Ok(())
+        } else {
+            self.enter_dir(&target)
+        }
+    }
+
+    /// Looks for the directory in `cdpath` entries unless it exists relative to cwd
+    fn search_cdpath(&self, dir: &Path) -> Option<PathBuf> {
+        let explicit = dir.is_absolute()
+            || dir.starts_with(".")
+            || dir.starts_with("..")
+            || self.cwd.join(dir).is_dir();
+        if explicit {
+            return None;
+        }
+        let cdpath = self.variables.get("cdpath")?;
+        cdpath
+            .split_whitespace()
+            .map(|entry| self.expand_home(entry).join(dir))
+            .find(|candidate| candidate.is_dir())
+    }
+
+    /// Calls chdir(2) and updates cwd with the path reported by the system
+    fn enter_dir(&mut self, dir: &Path) -> Result<()> {
+        change_dir(&self.cwd.join(dir)).map_err(|reason| reason.about(dir.display()))?;
+        self.cwd = get_current_dir()?;
+        Ok(())
+    }
+
+    /// Replaces the leading `~` of the word with the home directory
+    pub fn expand_home(&self, word: &str) -> PathBuf {
+        if word == "~" {
+            self.home.clone()
+        } else if let Some(rest) = word.strip_prefix("~/") {
+            self.home.join(rest)
+        } else {
+            PathBuf::from(word)
+        }
+    }
+}
+
+/// Replaces the home directory prefix of the path with `~`
+pub fn abbreviate_home(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) if home.parent().is_some() => {
+            if rest.as_os_str().is_empty() {
+                String::from("~")
+            } else {
+                format!("~/{}", rest.display())
+            }
+        }
+        _ => format!("{}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abbreviate_home_itself() {
+        let home = Path::new("/home/user");
+        assert_eq!(abbreviate_home(home, home), "~");
+    }
+
+    #[test]
+    fn abbreviate_home_subdir() {
+        let home = Path::new("/home/user");
+        let path = Path::new("/home/user/src/rsh");
+        assert_eq!(abbreviate_home(path, home), "~/src/rsh");
+    }
+
+    #[test]
+    fn abbreviate_home_outside() {
+        let home = Path::new("/home/user");
+        assert_eq!(abbreviate_home(Path::new("/home/username"), home), "/home/username");
+        assert_eq!(abbreviate_home(Path::new("/tmp"), home), "/tmp");
+    }
+
+    #[test]
+    fn abbreviate_home_root() {
+        let home = Path::new("/");
+        assert_eq!(abbreviate_home(Path::new("/tmp"), home), "/tmp");
+    }
+
+    #[test]
+    fn stack_keeps_directory_on_failure() {
+        let mut shell = Shell::new().unwrap();
+        let cwd = shell.cwd.clone();
+        assert!(shell.change_dir(Some("/nonexistent/rsh")).is_err());
+        assert_eq!(shell.cwd, cwd);
+        assert!(matches!(shell.pop_dir(), Err(Error::DirStackEmpty)));
+        assert!(matches!(shell.push_dir(None), Err(Error::NoOtherDirectory)));
+        shell.push_dir(cwd.to_str()).unwrap();
+        assert_eq!(shell.dir_stack, vec![cwd.clone()]);
+        shell.dir_stack = vec![PathBuf::from("/nonexistent/rsh")];
+        assert!(shell.pop_dir().is_err());
+        assert_eq!(shell.dir_stack.len(), 1);
+    }
+}
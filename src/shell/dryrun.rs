@@ -0,0 +1,145 @@
+use native::error::*;
+use native::write_to_file;
+
+use super::complete::BUILTINS;
+use super::ast::{parse_line, Command, Connector, Pipeline};
+use super::quoting::quote_word;
+use super::Shell;
+
+/// Word which makes the rest of the line only shown, unless `drysigil` sets another one
+const DEFAULT_SIGIL: &str = "?";
+
+impl Shell {
+    /// Finds the command after the dry run sigil like `? rm *.o`, see `dry_run`
+    pub fn dry_run_command<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let sigil = self.variables.get("drysigil").map_or(DEFAULT_SIGIL, String::as_str);
+        if sigil.is_empty() {
+            return None;
+        }
+        let rest = line.trim_start().strip_prefix(sigil)?;
+        rest.starts_with(char::is_whitespace).then_some(rest)
+    }
+
+    /// Prints what the command would run instead of running it: the expanded arguments
+    /// of every command of the pipeline, the program or builtin which would run them
+    /// and their redirections. Nothing is opened or created.
+    pub fn dry_run(&mut self, line: &str) -> Result<()> {
+        let text = self.describe(line)?;
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+
+    fn describe(&mut self, line: &str) -> Result<String> {
+        let list = parse_line(line)?;
+        if list.items.is_empty() {
+            return Err(Error::NullCommand);
+        }
+        let mut text = String::new();
+        for (index, (connector, command)) in list.items.iter().enumerate() {
+            if index > 0 {
+                let next = match connector {
+                    Connector::Always => msg!("then"),
+                    Connector::Success => msg!("then if it succeeds"),
+                    Connector::Failure => msg!("then if it fails"),
+                };
+                text.push_str(&format!("{}\n", next));
+            }
+            self.describe_command(command, &mut text)?;
+        }
+        Ok(text)
+    }
+
+    fn describe_command(&mut self, command: &Command, text: &mut String) -> Result<()> {
+        match command {
+            Command::Pipeline(pipeline) => self.describe_pipeline(pipeline, text),
+            Command::Conditional(conditional) => {
+                let condition = conditional.condition.trim();
+                text.push_str(&format!("{}\n", msg!("if: {}", condition)));
+                self.describe_command(&conditional.body, text)
+            }
+            Command::Loop(repeat) => {
+                let count = self.expand_words(std::slice::from_ref(&repeat.count))?.remove(0);
+                text.push_str(&format!("{}\n", msg!("repeat: {}", count)));
+                self.describe_command(&repeat.body, text)
+            }
+        }
+    }
+
+    fn describe_pipeline(&mut self, pipeline: &Pipeline, text: &mut String) -> Result<()> {
+        if let Some(command) = pipeline.assignments_only() {
+            for assignment in self.expand_words(&command.assignments)? {
+                text.push_str(&format!("{}\n", msg!("variable: {}", assignment)));
+            }
+            for redirection in self.expand_redirects(command)? {
+                text.push_str(&format!("    {}\n", msg!("redirection: {}", redirection)));
+            }
+            return Ok(());
+        }
+        for (index, command) in pipeline.commands.iter().enumerate() {
+            let stage = self.expand_command(command)?;
+            // builtins of a pipeline and groups run in subshells, see `run_pipeline`
+            let program = match (&stage.group, stage.words.first()) {
+                (Some(_), _) => msg!("subshell"),
+                (None, Some(name)) if BUILTINS.contains(&name.as_str()) => msg!("builtin"),
+                (None, name) => match self.hash_lookup(name.map_or("", String::as_str)) {
+                    Some(path) => path.display().to_string(),
+                    None => msg!("not found"),
+                },
+            };
+            let mut arguments: Vec<String> =
+                stage.words.iter().map(|word| quote_word(word)).collect();
+            if let Some(group) = &stage.group {
+                arguments.push(format!("({})", group.text));
+            }
+            text.push_str(&format!("{}\n", arguments.join(" ")));
+            text.push_str(&format!("    {}\n", msg!("program: {}", program)));
+            for redirection in &stage.redirections {
+                text.push_str(&format!("    {}\n", msg!("redirection: {}", redirection)));
+            }
+            for assignment in &stage.assignments {
+                text.push_str(&format!("    {}\n", msg!("environment: {}", assignment)));
+            }
+            if index + 1 < pipeline.commands.len() {
+                let pipe = if stage.with_stderr { "|&" } else { "|" };
+                text.push_str(&format!("    {}\n", msg!("pipe: {}", pipe)));
+            }
+        }
+        if pipeline.background {
+            text.push_str(&format!("{}\n", msg!("in the background")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_shows_commands() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("name"), String::from("a b"));
+        assert_eq!(shell.dry_run_command(" ? ls"), Some(" ls"));
+        assert_eq!(shell.dry_run_command("?ls"), None);
+        let text = shell.describe("LANG=C cd $name > out 2>&1").unwrap();
+        let expected = "cd 'a b'\n    program: builtin\n    redirection: > out\n    \
+                        redirection: 2>&1\n    environment: LANG=C\n";
+        assert_eq!(text, expected);
+        let text = shell.describe("no-such-program |& no-such-other &").unwrap();
+        let expected = "no-such-program\n    program: not found\n    pipe: |&\n\
+                        no-such-other\n    program: not found\nin the background\n";
+        assert_eq!(text, expected);
+        let text = shell.describe("(cd /; ls) > out | wc").unwrap();
+        assert!(text.starts_with("(cd /; ls)\n    program: subshell\n    redirection: > out\n"));
+        let text = shell.describe("if (1) repeat 2 pwd && no-such-program").unwrap();
+        let expected = "if: 1\nrepeat: 2\npwd\n    program: builtin\nthen if it succeeds\n\
+                        no-such-program\n    program: not found\n";
+        assert_eq!(text, expected);
+        assert_eq!(shell.describe("dir=$name/x").unwrap(), "variable: dir=a b/x\n");
+        let text = shell.describe("x=1 > out").unwrap();
+        assert_eq!(text, "variable: x=1\n    redirection: > out\n");
+        shell.variables.insert(String::from("drysigil"), String::from(":n"));
+        assert_eq!(shell.dry_run_command(":n ls >"), Some(" ls >"));
+        assert!(shell.describe("ls >").is_err());
+    }
+}
@@ -0,0 +1,1208 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::time::Duration;
+
+use native::error::*;
+use native::term::RawMode;
+use native::{read_byte, wait_input, write_to_file, Wakeup};
+
+use super::Shell;
+
+/// Functions of the line editor which keys are bound to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    BackwardChar,
+    ForwardChar,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardDeleteChar,
+    /// Deletes the character under the cursor, the end of input on an empty line
+    DeleteCharOrEof,
+    DeleteChar,
+    KillLine,
+    BackwardKillLine,
+    ForwardWord,
+    BackwardWord,
+    UpHistory,
+    DownHistory,
+    ClearScreen,
+    /// Drops the line and starts a new one
+    Abort,
+    AcceptLine,
+    StartKbdMacro,
+    EndKbdMacro,
+    CallLastKbdMacro,
+    /// Completes the word under the cursor, cycles through ranked candidates when repeated
+    Complete,
+    /// Inserts an argument used with the command before, cycles through them when repeated
+    PredictArgument,
+    /// Completes the word as a file name, ESC runs it when `filec` is set
+    CompleteFilename,
+    /// Reported by the terminal when its window gets focus if focus reporting is enabled
+    FocusIn,
+    FocusOut,
+    /// Leaves vi insert mode, keys run the functions of the command keymap
+    ViCommandMode,
+    /// Goes back to vi insert mode before the cursor, after it, at the start or the end of the line
+    ViInsert,
+    ViAdd,
+    ViInsertAtStart,
+    ViAddAtEnd,
+    /// Operators applied to the text the next motion moves over, repeated they take the line
+    ViDelete,
+    ViChange,
+    /// Changes the text till the end of the line, the whole line or the character under the cursor
+    ViChangeToEnd,
+    ViChangeLine,
+    ViSubstitute,
+}
+
+/// Names of the editor functions used by `bindkey`
+const FUNCTION_NAMES: &[(&str, Command)] = &[
+    ("backward-char", Command::BackwardChar),
+    ("forward-char", Command::ForwardChar),
+    ("beginning-of-line", Command::BeginningOfLine),
+    ("end-of-line", Command::EndOfLine),
+    ("backward-delete-char", Command::BackwardDeleteChar),
+    ("delete-char-or-eof", Command::DeleteCharOrEof),
+    ("delete-char", Command::DeleteChar),
+    ("kill-line", Command::KillLine),
+    ("backward-kill-line", Command::BackwardKillLine),
+    ("forward-word", Command::ForwardWord),
+    ("backward-word", Command::BackwardWord),
+    ("up-history", Command::UpHistory),
+    ("down-history", Command::DownHistory),
+    ("clear-screen", Command::ClearScreen),
+    ("abort", Command::Abort),
+    ("accept-line", Command::AcceptLine),
+    ("start-kbd-macro", Command::StartKbdMacro),
+    ("end-kbd-macro", Command::EndKbdMacro),
+    ("call-last-kbd-macro", Command::CallLastKbdMacro),
+    ("complete-word", Command::Complete),
+    ("predict-argument", Command::PredictArgument),
+    ("complete-filename", Command::CompleteFilename),
+    ("focus-in", Command::FocusIn),
+    ("focus-out", Command::FocusOut),
+    ("vi-cmd-mode", Command::ViCommandMode),
+    ("vi-insert", Command::ViInsert),
+    ("vi-add", Command::ViAdd),
+    ("vi-insert-at-bol", Command::ViInsertAtStart),
+    ("vi-add-at-eol", Command::ViAddAtEnd),
+    ("vi-delete-meta", Command::ViDelete),
+    ("vi-change-meta", Command::ViChange),
+    ("vi-change-to-eol", Command::ViChangeToEnd),
+    ("vi-change-whole-line", Command::ViChangeLine),
+    ("vi-substitute", Command::ViSubstitute),
+];
+
+impl Command {
+    /// Finds the function by its `bindkey` name
+    pub fn from_name(name: &str) -> Option<Command> {
+        FUNCTION_NAMES.iter().find(|(known, _)| *known == name).map(|(_, command)| *command)
+    }
+
+    /// Returns the `bindkey` name of the function
+    pub fn name(self) -> &'static str {
+        FUNCTION_NAMES
+            .iter()
+            .find(|(_, command)| *command == self)
+            .map_or("undefined-key", |(name, _)| name)
+    }
+}
+
+/// A possible replacement of the word being completed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    /// How many times the user has run it, candidates used before are offered first
+    pub uses: usize,
+}
+
+/// Source of completions for the word under the cursor
+pub trait Completer {
+    /// Returns the candidates in the order they should be offered,
+    /// `command` tells whether the word is the name of a command
+    fn complete(&self, word: &str, command: bool) -> Vec<Completion>;
+
+    /// Returns arguments used with the command before, the most recent one first
+    fn predict(&self, command: &str) -> Vec<String>;
+}
+
+/// What a key sequence does: runs an editor function or types the text as if it was entered
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Command(Command),
+    Macro(Vec<u8>),
+}
+
+/// Key sequences of the default emacs-like keymap
+const EMACS_KEYS: &[(&[u8], Command)] = &[
+    (b"\x02", Command::BackwardChar),
+    (b"\x1b[D", Command::BackwardChar),
+    (b"\x06", Command::ForwardChar),
+    (b"\x1b[C", Command::ForwardChar),
+    (b"\x01", Command::BeginningOfLine),
+    (b"\x1b[H", Command::BeginningOfLine),
+    (b"\x05", Command::EndOfLine),
+    (b"\x1b[F", Command::EndOfLine),
+    (b"\x7f", Command::BackwardDeleteChar),
+    (b"\x08", Command::BackwardDeleteChar),
+    (b"\x04", Command::DeleteCharOrEof),
+    (b"\x1b[3~", Command::DeleteCharOrEof),
+    (b"\x0b", Command::KillLine),
+    (b"\x15", Command::BackwardKillLine),
+    (b"\x10", Command::UpHistory),
+    (b"\x1b[A", Command::UpHistory),
+    (b"\x0e", Command::DownHistory),
+    (b"\x1b[B", Command::DownHistory),
+    (b"\x0c", Command::ClearScreen),
+    (b"\x03", Command::Abort),
+    (b"\r", Command::AcceptLine),
+    (b"\n", Command::AcceptLine),
+    (b"\x18(", Command::StartKbdMacro),
+    (b"\x18)", Command::EndKbdMacro),
+    (b"\x18e", Command::CallLastKbdMacro),
+    (b"\t", Command::Complete),
+    (b"\x1b/", Command::PredictArgument),
+    (b"\x1b[I", Command::FocusIn),
+    (b"\x1b[O", Command::FocusOut),
+];
+
+/// Key sequences of vi insert mode, ESC switches to the command keymap
+const VI_INSERT_KEYS: &[(&[u8], Command)] = &[
+    (b"\x1b", Command::ViCommandMode),
+    (b"\x1b[D", Command::BackwardChar),
+    (b"\x1b[C", Command::ForwardChar),
+    (b"\x1b[H", Command::BeginningOfLine),
+    (b"\x1b[F", Command::EndOfLine),
+    (b"\x7f", Command::BackwardDeleteChar),
+    (b"\x08", Command::BackwardDeleteChar),
+    (b"\x04", Command::DeleteCharOrEof),
+    (b"\x1b[3~", Command::DeleteCharOrEof),
+    (b"\x15", Command::BackwardKillLine),
+    (b"\x1b[A", Command::UpHistory),
+    (b"\x1b[B", Command::DownHistory),
+    (b"\x0c", Command::ClearScreen),
+    (b"\x03", Command::Abort),
+    (b"\r", Command::AcceptLine),
+    (b"\n", Command::AcceptLine),
+    (b"\t", Command::Complete),
+    (b"\x1b[I", Command::FocusIn),
+    (b"\x1b[O", Command::FocusOut),
+];
+
+/// Key sequences of vi command mode
+const VI_COMMAND_KEYS: &[(&[u8], Command)] = &[
+    (b"h", Command::BackwardChar),
+    (b"\x7f", Command::BackwardChar),
+    (b"\x1b[D", Command::BackwardChar),
+    (b"l", Command::ForwardChar),
+    (b" ", Command::ForwardChar),
+    (b"\x1b[C", Command::ForwardChar),
+    (b"0", Command::BeginningOfLine),
+    (b"^", Command::BeginningOfLine),
+    (b"$", Command::EndOfLine),
+    (b"w", Command::ForwardWord),
+    (b"b", Command::BackwardWord),
+    (b"x", Command::DeleteChar),
+    (b"X", Command::BackwardDeleteChar),
+    (b"D", Command::KillLine),
+    (b"k", Command::UpHistory),
+    (b"-", Command::UpHistory),
+    (b"\x1b[A", Command::UpHistory),
+    (b"j", Command::DownHistory),
+    (b"+", Command::DownHistory),
+    (b"\x1b[B", Command::DownHistory),
+    (b"i", Command::ViInsert),
+    (b"a", Command::ViAdd),
+    (b"I", Command::ViInsertAtStart),
+    (b"A", Command::ViAddAtEnd),
+    (b"d", Command::ViDelete),
+    (b"c", Command::ViChange),
+    (b"C", Command::ViChangeToEnd),
+    (b"S", Command::ViChangeLine),
+    (b"s", Command::ViSubstitute),
+    (b"\x04", Command::DeleteCharOrEof),
+    (b"\x0c", Command::ClearScreen),
+    (b"\x03", Command::Abort),
+    (b"\r", Command::AcceptLine),
+    (b"\n", Command::AcceptLine),
+    (b"\x1b[I", Command::FocusIn),
+    (b"\x1b[O", Command::FocusOut),
+];
+
+/// A lone ESC, with `filec` set it completes a file name
+const ESCAPE: &[u8] = b"\x1b";
+/// How long ESC waits for the rest of a sequence like an arrow key before it counts alone
+const ESCAPE_DELAY: Duration = Duration::from_millis(100);
+
+/// Reads lines from the terminal in raw mode and lets the user edit them.
+/// The line being edited is kept if reading is interrupted by a signal.
+#[derive(Debug)]
+pub struct Editor {
+    keymap: HashMap<Vec<u8>, Binding>,
+    /// Keys of vi command mode, empty with the emacs keymap
+    command_keymap: HashMap<Vec<u8>, Binding>,
+    /// vi command mode is on, keys are looked up in `command_keymap`
+    vi_command: bool,
+    /// A vi operator like `d` waiting for its motion
+    operator: Option<Command>,
+    /// Keys typed since `start-kbd-macro`
+    recording: Option<Vec<u8>>,
+    last_macro: Vec<u8>,
+    /// Keys of macros being played, they are handled before reading the terminal
+    pending: VecDeque<u8>,
+    line: Vec<char>,
+    cursor: usize,
+    /// Position in history while browsing it and the line typed before browsing
+    history_index: Option<usize>,
+    saved_line: Vec<char>,
+    /// Number of characters drawn after the prompt, None if the prompt has to be drawn
+    drawn: Option<usize>,
+    /// Candidates offered by repeated completion, the start of the word and the current one
+    cycle: Option<(Vec<String>, usize, usize)>,
+    /// Ask the terminal to report focus changes while reading
+    pub track_focus: bool,
+    /// The terminal has reported that its window lost focus
+    pub unfocused: bool,
+    /// Width of the terminal, zero if it is unknown
+    pub columns: usize,
+    /// How long to wait for a key before failing with TimedOut, None means forever
+    pub timeout: Option<Duration>,
+    /// Ctrl-C fails with Aborted instead of starting the line over
+    pub abort_ends: bool,
+    /// csh `filec`: ESC completes a file name and Ctrl-D in a word lists the matching files
+    pub filec: bool,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            keymap: keymap_of(EMACS_KEYS),
+            command_keymap: HashMap::new(),
+            vi_command: false,
+            operator: None,
+            recording: None,
+            last_macro: Vec::new(),
+            pending: VecDeque::new(),
+            line: Vec::new(),
+            cursor: 0,
+            history_index: None,
+            saved_line: Vec::new(),
+            drawn: None,
+            cycle: None,
+            track_focus: false,
+            unfocused: false,
+            columns: 0,
+            timeout: None,
+            abort_ends: false,
+            filec: false,
+        }
+    }
+}
+
+impl Editor {
+    /// Reads a line from the terminal showing the prompt, returns None on Ctrl-D at an empty line
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        history: &[String],
+        completer: &dyn Completer,
+    ) -> Result<Option<String>> {
+        let _raw = RawMode::enable(0)?;
+        if !self.track_focus {
+            return self.edit(prompt, history, completer);
+        }
+        write_to_file(1, "\x1b[?1004h")?;
+        let result = self.edit(prompt, history, completer);
+        write_to_file(1, "\x1b[?1004l")?;
+        result
+    }
+
+    fn edit(
+        &mut self,
+        prompt: &str,
+        history: &[String],
+        completer: &dyn Completer,
+    ) -> Result<Option<String>> {
+        loop {
+            self.redraw(prompt)?;
+            let keys = self.read_keys()?;
+            let binding = match keys {
+                None => return Ok(self.finish(None)),
+                Some(ref keys) => match self.keys().get(keys).cloned() {
+                    // vi keys bind ESC themselves
+                    None if self.filec && keys == ESCAPE => {
+                        Some(Binding::Command(Command::CompleteFilename))
+                    }
+                    binding => binding,
+                },
+            };
+            let keys = keys.unwrap_or_default();
+            match binding {
+                Some(Binding::Command(Command::Complete))
+                | Some(Binding::Command(Command::CompleteFilename))
+                | Some(Binding::Command(Command::PredictArgument)) => {}
+                _ => self.cycle = None,
+            }
+            match binding {
+                Some(Binding::Command(Command::Complete)) => self.complete(completer, false)?,
+                Some(Binding::Command(Command::CompleteFilename)) => {
+                    self.complete(completer, true)?
+                }
+                Some(Binding::Command(Command::PredictArgument)) => self.predict(completer)?,
+                Some(Binding::Command(Command::DeleteCharOrEof))
+                    if self.filec && self.in_word() =>
+                {
+                    self.list_files(completer)?
+                }
+                Some(Binding::Command(command)) => {
+                    if let Some(result) = self.run(command, &keys, history)? {
+                        return Ok(result);
+                    }
+                }
+                Some(Binding::Macro(text)) => self.play(&text),
+                None => match String::from_utf8(keys) {
+                    Ok(ref text) if !self.vi_command && !text.chars().any(char::is_control) => {
+                        self.insert(text)
+                    }
+                    _ => {
+                        self.operator = None;
+                        write_to_file(1, "\x07")?;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Makes the next read_line draw the prompt again, e.g. after other output
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// Binds the keys to the text typed when they are pressed.
+    /// If the text is None, the last recorded keyboard macro is used.
+    pub fn bind_macro(&mut self, keys: Vec<u8>, text: Option<Vec<u8>>) {
+        let text = text.unwrap_or_else(|| self.last_macro.clone());
+        self.keymap.insert(keys, Binding::Macro(text));
+    }
+
+    /// Binds the keys to the editor function, in vi command mode if `command_mode` is set
+    pub fn bind_command(&mut self, keys: Vec<u8>, command: Command, command_mode: bool) {
+        let keymap = if command_mode { &mut self.command_keymap } else { &mut self.keymap };
+        keymap.insert(keys, Binding::Command(command));
+    }
+
+    /// Returns what the keys are bound to
+    pub fn binding(&self, keys: &[u8], command_mode: bool) -> Option<&Binding> {
+        let keymap = if command_mode { &self.command_keymap } else { &self.keymap };
+        keymap.get(keys)
+    }
+
+    /// Replaces the bindings with the default emacs-like keymap
+    pub fn use_emacs_keys(&mut self) {
+        self.keymap = keymap_of(EMACS_KEYS);
+        self.command_keymap.clear();
+        self.vi_command = false;
+        self.operator = None;
+    }
+
+    /// Replaces the bindings with the vi keymaps, lines are started in insert mode
+    pub fn use_vi_keys(&mut self) {
+        self.keymap = keymap_of(VI_INSERT_KEYS);
+        self.command_keymap = keymap_of(VI_COMMAND_KEYS);
+        self.vi_command = false;
+        self.operator = None;
+    }
+
+    /// Lists the bindings in `bindkey` notation sorted by keys,
+    /// the ones of vi command mode are marked with `-a`
+    pub fn bindings(&self) -> String {
+        let mut lines = Vec::new();
+        for (prefix, keymap) in [("", &self.keymap), ("-a ", &self.command_keymap)] {
+            let mut keys: Vec<&Vec<u8>> = keymap.keys().collect();
+            keys.sort();
+            for keys in keys {
+                lines.push(format!("{}{}", prefix, describe_binding(keys, keymap.get(keys))));
+            }
+        }
+        lines.join("")
+    }
+
+    /// The keymap used for the next key
+    fn keys(&self) -> &HashMap<Vec<u8>, Binding> {
+        if self.vi_command {
+            &self.command_keymap
+        } else {
+            &self.keymap
+        }
+    }
+
+    /// Runs the editor function, returns the result of read_line if the line is finished
+    fn run(
+        &mut self,
+        command: Command,
+        keys: &[u8],
+        history: &[String],
+    ) -> Result<Option<Option<String>>> {
+        if let Some(operator) = self.operator.take() {
+            if self.apply_operator(operator, command) {
+                return Ok(None);
+            }
+        }
+        match command {
+            Command::BackwardChar
+            | Command::ForwardChar
+            | Command::BeginningOfLine
+            | Command::EndOfLine
+            | Command::ForwardWord
+            | Command::BackwardWord => {
+                self.cursor = self.motion_target(command).unwrap_or(self.cursor)
+            }
+            Command::BackwardDeleteChar => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+            }
+            Command::DeleteCharOrEof => {
+                if self.line.is_empty() {
+                    write_to_file(1, "\n")?;
+                    return Ok(Some(self.finish(None)));
+                }
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+            }
+            Command::DeleteChar => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+            }
+            Command::KillLine => self.line.truncate(self.cursor),
+            Command::BackwardKillLine => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Command::UpHistory => self.browse_history(history, true),
+            Command::DownHistory => self.browse_history(history, false),
+            Command::ClearScreen => {
+                write_to_file(1, "\x1b[H\x1b[2J")?;
+                self.drawn = None;
+            }
+            Command::Abort => {
+                write_to_file(1, "^C\n")?;
+                self.finish(None);
+                if self.abort_ends {
+                    return Err(Error::Aborted);
+                }
+            }
+            Command::AcceptLine => {
+                write_to_file(1, "\n")?;
+                let line = self.line.iter().collect();
+                return Ok(Some(self.finish(Some(line))));
+            }
+            Command::StartKbdMacro => self.recording = Some(Vec::new()),
+            Command::EndKbdMacro => {
+                if let Some(mut keys_typed) = self.recording.take() {
+                    // the keys which have finished the recording aren't a part of the macro
+                    let length = keys_typed.len().saturating_sub(keys.len());
+                    keys_typed.truncate(length);
+                    self.last_macro = keys_typed;
+                }
+            }
+            Command::CallLastKbdMacro => {
+                let keys = self.last_macro.clone();
+                self.play(&keys);
+            }
+            Command::FocusIn => self.unfocused = false,
+            Command::FocusOut => self.unfocused = true,
+            Command::ViCommandMode => {
+                // like in vi the cursor moves onto the last character typed
+                self.cursor = self.cursor.saturating_sub(1);
+                self.vi_command = true;
+            }
+            Command::ViInsert => self.vi_command = false,
+            Command::ViAdd => {
+                self.cursor = (self.cursor + 1).min(self.line.len());
+                self.vi_command = false;
+            }
+            Command::ViInsertAtStart => {
+                self.cursor = 0;
+                self.vi_command = false;
+            }
+            Command::ViAddAtEnd => {
+                self.cursor = self.line.len();
+                self.vi_command = false;
+            }
+            Command::ViDelete | Command::ViChange => self.operator = Some(command),
+            Command::ViChangeToEnd => {
+                self.line.truncate(self.cursor);
+                self.vi_command = false;
+            }
+            Command::ViChangeLine => {
+                self.line.clear();
+                self.cursor = 0;
+                self.vi_command = false;
+            }
+            Command::ViSubstitute => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+                self.vi_command = false;
+            }
+            // they need the completer, so read_line runs them
+            Command::Complete | Command::CompleteFilename | Command::PredictArgument => {}
+        }
+        Ok(None)
+    }
+
+    /// Where the motion moves the cursor, None if the command isn't a motion
+    fn motion_target(&self, command: Command) -> Option<usize> {
+        let line = &self.line;
+        let mut cursor = self.cursor;
+        match command {
+            Command::BackwardChar => cursor = cursor.saturating_sub(1),
+            Command::ForwardChar => cursor = (cursor + 1).min(line.len()),
+            Command::BeginningOfLine => cursor = 0,
+            Command::EndOfLine => cursor = line.len(),
+            Command::ForwardWord => {
+                while cursor < line.len() && !line[cursor].is_whitespace() {
+                    cursor += 1;
+                }
+                while cursor < line.len() && line[cursor].is_whitespace() {
+                    cursor += 1;
+                }
+            }
+            Command::BackwardWord => {
+                while cursor > 0 && line[cursor - 1].is_whitespace() {
+                    cursor -= 1;
+                }
+                while cursor > 0 && !line[cursor - 1].is_whitespace() {
+                    cursor -= 1;
+                }
+            }
+            _ => return None,
+        }
+        Some(cursor)
+    }
+
+    /// Deletes the text between the cursor and the target of the motion, the whole line
+    /// if the operator is repeated like `dd`. The change operator goes to insert mode then.
+    /// Returns false if the command is neither, so it runs as usual.
+    fn apply_operator(&mut self, operator: Command, command: Command) -> bool {
+        let (start, mut end) = if command == operator {
+            (0, self.line.len())
+        } else {
+            match self.motion_target(command) {
+                Some(target) => (self.cursor.min(target), self.cursor.max(target)),
+                None => return false,
+            }
+        };
+        // like in vi `cw` keeps the space after the word
+        if operator == Command::ViChange && command == Command::ForwardWord {
+            while end > start && self.line[end - 1].is_whitespace() {
+                end -= 1;
+            }
+        }
+        self.line.drain(start..end);
+        self.cursor = start;
+        if operator == Command::ViChange {
+            self.vi_command = false;
+        }
+        true
+    }
+
+    /// Completes the word before the cursor, as a file name if `files` is set.
+    /// A single candidate is inserted, otherwise the common prefix is. If there is no common part,
+    /// the most used candidate is inserted and repeated completion goes through the others,
+    /// unused candidates are listed instead.
+    fn complete(&mut self, completer: &dyn Completer, files: bool) -> Result<()> {
+        if let Some((candidates, start, index)) = self.cycle.take() {
+            let index = (index + 1) % candidates.len();
+            self.replace_word(start, &candidates[index]);
+            self.cycle = Some((candidates, start, index));
+            return Ok(());
+        }
+        let start = self.word_start();
+        let word: String = self.line[start..self.cursor].iter().collect();
+        let before: String = self.line[..start].iter().collect();
+        let command = before.trim_end().is_empty() || before.trim_end().ends_with(['|', '&', ';']);
+        let candidates = completer.complete(&word, command && !files);
+        match candidates.len() {
+            0 => {
+                write_to_file(1, "\x07")?;
+            }
+            1 => {
+                let text = &candidates[0].text;
+                let suffix = if text.ends_with('/') { "" } else { " " };
+                self.replace_word(start, &format!("{}{}", text, suffix));
+            }
+            _ => {
+                let texts: Vec<String> = candidates.iter().map(|c| c.text.clone()).collect();
+                let prefix = common_prefix(&texts);
+                if prefix.chars().count() > word.chars().count() {
+                    self.replace_word(start, &prefix);
+                } else if candidates[0].uses > 0 {
+                    self.replace_word(start, &texts[0]);
+                    self.cycle = Some((texts, start, 0));
+                } else {
+                    self.list(&texts)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the word before the cursor with an argument used with the same command before.
+    /// Only arguments starting with the word are offered, repeated calls go through them.
+    fn predict(&mut self, completer: &dyn Completer) -> Result<()> {
+        if self.cycle.is_some() {
+            return self.complete(completer, false);
+        }
+        let start = self.word_start();
+        let word: String = self.line[start..self.cursor].iter().collect();
+        let before: String = self.line[..start].iter().collect();
+        let command = match before.split_whitespace().next() {
+            Some(command) => command,
+            None => return write_to_file(1, "\x07").map(|_| ()),
+        };
+        let candidates: Vec<String> = completer
+            .predict(command)
+            .into_iter()
+            .filter(|argument| argument.starts_with(&word))
+            .collect();
+        if candidates.is_empty() {
+            return write_to_file(1, "\x07").map(|_| ());
+        }
+        self.replace_word(start, &candidates[0]);
+        self.cycle = Some((candidates, start, 0));
+        Ok(())
+    }
+
+    /// Lists the files starting with the part of the word before the cursor like csh `filec`
+    fn list_files(&mut self, completer: &dyn Completer) -> Result<()> {
+        let start = self.word_start();
+        let word: String = self.line[start..self.cursor].iter().collect();
+        let candidates: Vec<String> = completer
+            .complete(&word, false)
+            .into_iter()
+            .map(|c| c.text)
+            .collect();
+        if candidates.is_empty() {
+            return write_to_file(1, "\x07").map(|_| ());
+        }
+        self.list(&candidates)
+    }
+
+    /// Checks whether the cursor is in a word or right after it
+    fn in_word(&self) -> bool {
+        self.cursor > 0 && !self.line[self.cursor - 1].is_whitespace()
+    }
+
+    /// Finds where the word before the cursor starts
+    fn word_start(&self) -> usize {
+        self.line[..self.cursor]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Replaces the text from the start till the cursor
+    fn replace_word(&mut self, start: usize, text: &str) {
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+        self.insert(text);
+    }
+
+    /// Prints the candidates in columns below the line, the prompt is drawn again after them
+    fn list(&mut self, candidates: &[String]) -> Result<()> {
+        let columns = if self.columns > 0 { self.columns } else { 80 };
+        let width = candidates.iter().map(|text| text.chars().count()).max().unwrap_or(0) + 2;
+        let per_line = (columns / width).max(1);
+        let mut text = String::from("\n");
+        for (index, candidate) in candidates.iter().enumerate() {
+            text.push_str(candidate);
+            if (index + 1) % per_line == 0 || index + 1 == candidates.len() {
+                text.push('\n');
+            } else {
+                text.push_str(&" ".repeat(width - candidate.chars().count()));
+            }
+        }
+        write_to_file(1, &text)?;
+        self.drawn = None;
+        Ok(())
+    }
+
+    /// Reads the bytes of one key: a bound sequence, a character or an unknown byte.
+    /// Returns None at the end of input.
+    fn read_keys(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut keys = Vec::new();
+        loop {
+            let byte = match self.next_byte()? {
+                Some(byte) => byte,
+                None if keys.is_empty() => return Ok(None),
+                None => return Ok(Some(keys)),
+            };
+            keys.push(byte);
+            let is_prefix = self
+                .keys()
+                .keys()
+                .any(|bound| bound.len() > keys.len() && bound.starts_with(&keys));
+            let alone = self.keys().contains_key(&keys) || self.filec && keys == ESCAPE;
+            // a key like vi ESC which starts longer sequences counts alone if nothing follows
+            if alone && (!is_prefix || !self.more_keys_soon()?) {
+                return Ok(Some(keys));
+            }
+            if !is_prefix && keys.len() == 1 && byte >= 0x80 {
+                return self.read_char(keys).map(Some);
+            }
+            if !is_prefix {
+                return Ok(Some(keys));
+            }
+        }
+    }
+
+    /// Checks whether the next key arrives at once like the rest of a sequence sent by the terminal
+    fn more_keys_soon(&self) -> Result<bool> {
+        if !self.pending.is_empty() {
+            return Ok(true);
+        }
+        match wait_input(0, Some(ESCAPE_DELAY))? {
+            Wakeup::Input => Ok(true),
+            Wakeup::Signal => Err(Error::Interrupted),
+            Wakeup::Timeout => Ok(false),
+        }
+    }
+
+    /// Reads the continuation bytes of a UTF-8 character
+    fn read_char(&mut self, mut keys: Vec<u8>) -> Result<Vec<u8>> {
+        let length = match keys[0] {
+            byte if byte >> 5 == 0b110 => 2,
+            byte if byte >> 4 == 0b1110 => 3,
+            byte if byte >> 3 == 0b11110 => 4,
+            _ => 1,
+        };
+        while keys.len() < length {
+            match self.next_byte()? {
+                Some(byte) => keys.push(byte),
+                None => break,
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Takes the next key of a played macro or reads it from the terminal recording it
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+        match wait_input(0, self.timeout)? {
+            Wakeup::Input => {}
+            Wakeup::Signal => return Err(Error::Interrupted),
+            Wakeup::Timeout => return Err(Error::TimedOut),
+        }
+        let byte = read_byte(0)?;
+        if let (Some(recording), Some(byte)) = (self.recording.as_mut(), byte) {
+            recording.push(byte);
+        }
+        Ok(byte)
+    }
+
+    /// Queues the keys so they are handled as if they were typed
+    fn play(&mut self, keys: &[u8]) {
+        for (index, byte) in keys.iter().enumerate() {
+            self.pending.insert(index, *byte);
+        }
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.line.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    /// Replaces the line with an older (`up`) or a newer entry of the history
+    fn browse_history(&mut self, history: &[String], up: bool) {
+        let index = match (self.history_index, up) {
+            (None, true) if !history.is_empty() => history.len() - 1,
+            (Some(index), true) if index > 0 => index - 1,
+            (Some(index), false) if index + 1 < history.len() => index + 1,
+            (Some(_), false) => {
+                self.history_index = None;
+                self.line = mem::take(&mut self.saved_line);
+                self.cursor = self.line.len();
+                return;
+            }
+            _ => return,
+        };
+        if self.history_index.is_none() {
+            self.saved_line = mem::take(&mut self.line);
+        }
+        self.history_index = Some(index);
+        self.line = history[index].chars().collect();
+        self.cursor = self.line.len();
+    }
+
+    /// Draws the line after the prompt and puts the cursor at its place.
+    /// Only the last line of the prompt is drawn again after the first time.
+    fn redraw(&mut self, prompt: &str) -> Result<()> {
+        let mut text = match self.drawn {
+            None => String::from(prompt),
+            Some(_) => format!("\r{}", prompt.rsplit('\n').next().unwrap_or(prompt)),
+        };
+        text.extend(self.line.iter());
+        // erase what is left from a longer line
+        let extra = self.drawn.unwrap_or(0).saturating_sub(self.line.len());
+        text.push_str(&" ".repeat(extra));
+        let back = extra + self.line.len() - self.cursor;
+        if back > 0 {
+            text.push_str(&format!("\x1b[{}D", back));
+        }
+        write_to_file(1, &text)?;
+        self.drawn = Some(self.line.len());
+        Ok(())
+    }
+
+    /// Resets the state for the next line
+    fn finish(&mut self, result: Option<String>) -> Option<String> {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_index = None;
+        self.saved_line.clear();
+        self.drawn = None;
+        self.vi_command = false;
+        self.operator = None;
+        result
+    }
+}
+
+/// Makes a keymap of the default bindings
+fn keymap_of(keys: &[(&[u8], Command)]) -> HashMap<Vec<u8>, Binding> {
+    keys.iter()
+        .map(|(keys, command)| (keys.to_vec(), Binding::Command(*command)))
+        .collect()
+}
+
+/// Formats a line of the `bindkey` listing like `"^A" -> beginning-of-line`
+/// or `"^R" -> undefined-key` if the keys aren't bound
+fn describe_binding(keys: &[u8], binding: Option<&Binding>) -> String {
+    let target = match binding {
+        Some(Binding::Command(command)) => String::from(command.name()),
+        Some(Binding::Macro(text)) => format!("\"{}\"", key_notation(text)),
+        None => String::from("undefined-key"),
+    };
+    format!("{:<12} -> {}\n", format!("\"{}\"", key_notation(keys)), target)
+}
+
+/// Finds the longest common prefix of the texts
+fn common_prefix(texts: &[String]) -> String {
+    let mut prefix: Vec<char> = match texts.first() {
+        Some(first) => first.chars().collect(),
+        None => return String::new(),
+    };
+    for text in &texts[1..] {
+        let same = prefix.iter().zip(text.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(same);
+    }
+    prefix.into_iter().collect()
+}
+
+impl Shell {
+    /// Implements `bindkey`: without arguments lists the bindings, `bindkey [-a] keys` shows
+    /// one of them and `bindkey [-a] keys function` binds the keys to an editor function,
+    /// `-a` meaning vi command mode. `bindkey -e` and `bindkey -v` switch to the emacs or vi
+    /// keymaps, `bindkey -l` lists the functions. `bindkey -s keys [text]` binds the keys
+    /// to the text or to the last recorded keyboard macro if the text is omitted.
+    pub fn bindkey_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        match arguments {
+            [] => write_to_file(1, &self.editor.bindings()).map(|_| ())?,
+            ["-e"] => self.editor.use_emacs_keys(),
+            ["-v"] => self.editor.use_vi_keys(),
+            ["-l"] => {
+                let names: Vec<&str> = FUNCTION_NAMES.iter().map(|(name, _)| *name).collect();
+                write_to_file(1, &format!("{}\n", names.join("\n")))?;
+            }
+            ["-s", keys] => self.editor.bind_macro(parse_keys(keys), None),
+            ["-s", keys, text] => self.editor.bind_macro(parse_keys(keys), Some(parse_keys(text))),
+            ["-a", keys] => self.show_binding(keys, true)?,
+            ["-a", keys, function] => self.bind_function(keys, function, true)?,
+            [keys] if !keys.starts_with('-') => self.show_binding(keys, false)?,
+            [keys, function] if !keys.starts_with('-') => {
+                self.bind_function(keys, function, false)?
+            }
+            _ => return Err(Error::SyntaxError),
+        }
+        Ok(())
+    }
+
+    fn show_binding(&self, keys: &str, command_mode: bool) -> Result<()> {
+        let keys = parse_keys(keys);
+        let line = describe_binding(&keys, self.editor.binding(&keys, command_mode));
+        write_to_file(1, &line).map(|_| ())
+    }
+
+    fn bind_function(&mut self, keys: &str, function: &str, command_mode: bool) -> Result<()> {
+        let command = Command::from_name(function)
+            .ok_or_else(|| Error::NoSuchFunction(String::from(function)))?;
+        self.editor.bind_command(parse_keys(keys), command, command_mode);
+        Ok(())
+    }
+}
+
+/// Converts the key notation of bindkey to bytes: `^X` is Ctrl-X, `^?` is DEL,
+/// `\e` is ESC, `\n`, `\r` and `\t` are the usual escapes, `\^` and `\\` are literal.
+pub fn parse_keys(spec: &str) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut chars = spec.chars();
+    while let Some(c) = chars.next() {
+        let byte = match c {
+            '^' => match chars.next() {
+                Some('?') => 0x7f,
+                Some(next) if next.is_ascii() => next.to_ascii_uppercase() as u8 & 0x1f,
+                Some(next) => {
+                    result.push(b'^');
+                    result.extend_from_slice(next.encode_utf8(&mut [0; 4]).as_bytes());
+                    continue;
+                }
+                None => b'^',
+            },
+            '\\' => match chars.next() {
+                Some('e') | Some('E') => 0x1b,
+                Some('n') => b'\n',
+                Some('r') => b'\r',
+                Some('t') => b'\t',
+                Some(next) => {
+                    result.extend_from_slice(next.encode_utf8(&mut [0; 4]).as_bytes());
+                    continue;
+                }
+                None => b'\\',
+            },
+            c => {
+                result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                continue;
+            }
+        };
+        result.push(byte);
+    }
+    result
+}
+
+/// Converts bytes to the key notation of bindkey, the reverse of parse_keys
+pub fn key_notation(keys: &[u8]) -> String {
+    let mut result = String::new();
+    for c in String::from_utf8_lossy(keys).chars() {
+        match c {
+            '\x7f' => result.push_str("^?"),
+            '^' => result.push_str("\\^"),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                result.push('^');
+                result.push((c as u8 | 0x40) as char);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_notation() {
+        assert_eq!(parse_keys("^X("), b"\x18(");
+        assert_eq!(parse_keys("^a^?"), b"\x01\x7f");
+        assert_eq!(parse_keys("\\e[A"), b"\x1b[A");
+        assert_eq!(parse_keys("ls -l\\n"), b"ls -l\n");
+        assert_eq!(parse_keys("\\^\\\\"), b"^\\");
+    }
+
+    #[test]
+    fn key_notation_round_trip() {
+        for keys in ["^R", "^[[A", "^?", "\\^x\\\\", "ls -l^J"] {
+            assert_eq!(key_notation(&parse_keys(keys)), keys);
+        }
+        for (name, command) in FUNCTION_NAMES {
+            assert_eq!(Command::from_name(name), Some(*command));
+            assert_eq!(command.name(), *name);
+        }
+        assert_eq!(Command::from_name("no-such-function"), None);
+    }
+
+    #[test]
+    fn vi_command_mode() {
+        let mut editor = Editor::default();
+        editor.use_vi_keys();
+        editor.insert("echo one two");
+        editor.run(Command::ViCommandMode, b"\x1b", &[]).unwrap();
+        assert_eq!(editor.keys().get(&b"b"[..]), Some(&Binding::Command(Command::BackwardWord)));
+        editor.run(Command::BackwardWord, b"b", &[]).unwrap();
+        assert_eq!(editor.cursor, 9);
+        editor.run(Command::DeleteChar, b"x", &[]).unwrap();
+        editor.run(Command::ViInsertAtStart, b"I", &[]).unwrap();
+        assert!(!editor.vi_command);
+        assert_eq!(editor.cursor, 0);
+        editor.run(Command::ForwardWord, b"", &[]).unwrap();
+        assert_eq!(editor.cursor, 5);
+        assert_eq!(editor.line.iter().collect::<String>(), "echo one wo");
+        editor.use_emacs_keys();
+        assert!(editor.command_keymap.is_empty());
+    }
+
+    #[test]
+    fn vi_operators() {
+        let mut editor = Editor::default();
+        editor.use_vi_keys();
+        editor.insert("echo one two");
+        editor.run(Command::ViCommandMode, b"\x1b", &[]).unwrap();
+        editor.run(Command::BackwardWord, b"b", &[]).unwrap();
+        editor.run(Command::BackwardWord, b"b", &[]).unwrap();
+        editor.run(Command::ViDelete, b"d", &[]).unwrap();
+        editor.run(Command::ForwardWord, b"w", &[]).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "echo two");
+        assert!(editor.vi_command);
+        editor.run(Command::ViChange, b"c", &[]).unwrap();
+        editor.run(Command::ForwardWord, b"w", &[]).unwrap();
+        assert!(!editor.vi_command);
+        editor.insert("three");
+        assert_eq!(editor.line.iter().collect::<String>(), "echo three");
+        editor.run(Command::ViCommandMode, b"\x1b", &[]).unwrap();
+        editor.run(Command::ViDelete, b"d", &[]).unwrap();
+        editor.run(Command::BeginningOfLine, b"0", &[]).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "e");
+        editor.run(Command::ViDelete, b"d", &[]).unwrap();
+        editor.run(Command::ViDelete, b"d", &[]).unwrap();
+        assert!(editor.line.is_empty());
+    }
+
+    #[test]
+    fn edit_commands() {
+        let mut editor = Editor::default();
+        editor.insert("echo world");
+        editor.run(Command::BeginningOfLine, b"", &[]).unwrap();
+        for _ in 0..5 {
+            editor.run(Command::ForwardChar, b"", &[]).unwrap();
+        }
+        editor.insert("hello ");
+        editor.run(Command::KillLine, b"", &[]).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "echo hello ");
+        editor.run(Command::BackwardKillLine, b"", &[]).unwrap();
+        assert!(editor.line.is_empty());
+    }
+
+    #[test]
+    fn browse_history_restores_line() {
+        let history = vec![String::from("ls"), String::from("pwd")];
+        let mut editor = Editor::default();
+        editor.insert("ec");
+        editor.browse_history(&history, true);
+        editor.browse_history(&history, true);
+        assert_eq!(editor.line.iter().collect::<String>(), "ls");
+        editor.browse_history(&history, false);
+        editor.browse_history(&history, false);
+        assert_eq!(editor.line.iter().collect::<String>(), "ec");
+    }
+
+    struct Words(Vec<Completion>);
+
+    impl Completer for Words {
+        fn complete(&self, word: &str, _: bool) -> Vec<Completion> {
+            self.0.iter().filter(|c| c.text.starts_with(word)).cloned().collect()
+        }
+
+        fn predict(&self, _: &str) -> Vec<String> {
+            self.0.iter().map(|c| c.text.clone()).collect()
+        }
+    }
+
+    fn completion(text: &str, uses: usize) -> Completion {
+        Completion {
+            text: String::from(text),
+            uses,
+        }
+    }
+
+    #[test]
+    fn complete_common_prefix_and_cycle() {
+        let words = Words(vec![
+            completion("git", 3),
+            completion("gitk", 0),
+            completion("grep", 1),
+        ]);
+        let mut editor = Editor::default();
+        editor.insert("gi");
+        editor.complete(&words, false).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "git");
+        editor.complete(&words, false).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "git");
+        editor.complete(&words, false).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "gitk");
+        editor.cycle = None;
+        editor.run(Command::BackwardKillLine, b"", &[]).unwrap();
+        editor.insert("g");
+        editor.complete(&words, false).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "git");
+    }
+
+    #[test]
+    fn complete_filename_in_word() {
+        let words = Words(vec![completion("make", 2), completion("makefile", 0)]);
+        let mut editor = Editor::default();
+        editor.insert("mak");
+        editor.complete(&words, true).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "make");
+        assert!(editor.in_word());
+        editor.insert(" ");
+        assert!(!editor.in_word());
+    }
+
+    #[test]
+    fn predict_cycles_arguments() {
+        let words = Words(vec![
+            completion("beta", 0),
+            completion("alpha", 0),
+            completion("bar", 0),
+        ]);
+        let mut editor = Editor::default();
+        editor.insert("ssh b");
+        editor.predict(&words).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "ssh beta");
+        editor.predict(&words).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "ssh bar");
+        editor.predict(&words).unwrap();
+        assert_eq!(editor.line.iter().collect::<String>(), "ssh beta");
+    }
+
+    #[test]
+    fn common_prefix_of_words() {
+        let words = vec![String::from("pushd"), String::from("pusher")];
+        assert_eq!(common_prefix(&words), "push");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn macro_is_recorded_and_played() {
+        let mut editor = Editor {
+            recording: Some(b"ab\x18)".to_vec()),
+            ..Editor::default()
+        };
+        editor.run(Command::EndKbdMacro, b"\x18)", &[]).unwrap();
+        assert_eq!(editor.last_macro, b"ab");
+        editor.pending.push_back(b'z');
+        editor.run(Command::CallLastKbdMacro, b"\x18e", &[]).unwrap();
+        assert_eq!(editor.pending, vec![b'a', b'b', b'z']);
+    }
+
+    #[test]
+    fn bindkey_checks_functions() {
+        let mut shell = Shell::new().unwrap();
+        assert!(shell.bindkey_builtin(&["^A", "no-such-function"]).is_err());
+        shell.bindkey_builtin(&["-v"]).unwrap();
+        let vi = shell.editor.bindings();
+        shell.bindkey_builtin(&["-e"]).unwrap();
+        assert_ne!(shell.editor.bindings(), vi);
+    }
+}
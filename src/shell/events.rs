@@ -0,0 +1,241 @@
+use native::error::*;
+
+use super::lexer::raw_words;
+use super::modifiers::{modify, parse_modifiers, Modifier};
+use super::Shell;
+
+impl Shell {
+    /// Replaces the history references of the interactive line with the commands they name:
+    /// `!!` is the previous command, `!n` is the one numbered n by `history`, `!-n` is the n-th
+    /// previous one and `!prefix` is the latest one starting with the prefix. Modifiers like
+    /// `!!:gs/old/new/` may follow, see `modifiers::parse_modifiers`. `^old^new^` at the start
+    /// of the line stands for `!!:s^old^new^`. A `!` before a blank, `=` or `(`, in single quotes
+    /// or after a backslash is a plain character.
+    /// Returns None if the line has no references, otherwise the new line and whether `:p`
+    /// asks to print it instead of running.
+    pub fn expand_history(&mut self, line: &str) -> Result<Option<(String, bool)>> {
+        let quick;
+        let line = match line.strip_prefix('^') {
+            Some(rest) => {
+                quick = format!("!!:s^{}", rest);
+                &quick
+            }
+            None => line,
+        };
+        let mut result = String::with_capacity(line.len());
+        let mut found = false;
+        let mut print = false;
+        let mut in_quotes = false;
+        let mut index = 0;
+        while let Some(c) = line[index..].chars().next() {
+            index += c.len_utf8();
+            match c {
+                '\'' => in_quotes = !in_quotes,
+                '\\' if !in_quotes => {
+                    result.push(c);
+                    if let Some(escaped) = line[index..].chars().next() {
+                        result.push(escaped);
+                        index += escaped.len_utf8();
+                    }
+                    continue;
+                }
+                '!' if !in_quotes => {
+                    let reference = self.reference(&line[index..], &result)?;
+                    if let Some((text, length, only_print)) = reference {
+                        result.push_str(&text);
+                        index += length;
+                        found = true;
+                        print |= only_print;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            result.push(c);
+        }
+        Ok(if found { Some((result, print)) } else { None })
+    }
+
+    /// Reads the reference which follows `!` at the start of the text, returns the words it
+    /// names with the modifiers applied, the length of the reference and whether it has `:p`.
+    /// `current` is the line before the reference which `!#` names.
+    /// None means that the `!` is a plain character.
+    fn reference(&mut self, text: &str, current: &str) -> Result<Option<(String, usize, bool)>> {
+        let length = match text.chars().next() {
+            None => return Ok(None),
+            Some(c) if c.is_whitespace() || c == '=' || c == '(' => return Ok(None),
+            Some('!') | Some('#') => 1,
+            // `!$` or `!:2` select words of the previous command
+            Some('^') | Some('$') | Some('*') | Some(':') => 0,
+            Some('-') => {
+                let digits = &text[1..];
+                1 + digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len())
+            }
+            Some(c) if c.is_ascii_digit() => {
+                text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len())
+            }
+            Some(_) => text
+                .find(|c: char| c.is_whitespace() || ":^$*;|&<>()'\"".contains(c))
+                .unwrap_or(text.len()),
+        };
+        let event = &text[..length];
+        let words = match event {
+            "#" => raw_words(current),
+            _ => {
+                let index = self.find_event(event);
+                let index = index.ok_or_else(|| Error::EventNotFound(event.into()))?;
+                self.history.words(index).to_vec()
+            }
+        };
+        let (words, designator_length) = match parse_designator(&text[length..]) {
+            Some((designator, designator_length)) => {
+                (select_words(words, designator)?, designator_length)
+            }
+            None => (words, 0),
+        };
+        let length = length + designator_length;
+        let (modifiers, modifiers_length) = parse_modifiers(&text[length..]);
+        let print = modifiers.iter().any(|(modifier, _)| *modifier == Modifier::Print);
+        let words = modify(words, &modifiers, &mut self.history.last_substitution)?;
+        Ok(Some((words.join(" "), length + modifiers_length, print)))
+    }
+
+    /// Finds the entry named by the text of a reference after `!`, see `expand_history`.
+    /// An empty text means the previous command.
+    fn find_event(&self, event: &str) -> Option<usize> {
+        let entries = &self.history.entries;
+        let index = match event {
+            "!" | "" => entries.len().checked_sub(1),
+            _ if event.starts_with('-') => {
+                let back: usize = event[1..].parse().ok()?;
+                entries.len().checked_sub(back).filter(|_| back > 0)
+            }
+            _ if event.starts_with(|c: char| c.is_ascii_digit()) => {
+                let number: usize = event.parse().ok()?;
+                number.checked_sub(1)
+            }
+            _ => entries.iter().rposition(|entry| entry.starts_with(event)),
+        };
+        index.filter(|index| *index < entries.len())
+    }
+}
+
+/// A word of an event in a designator: its number, the last one or the one before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Word {
+    Number(usize),
+    Last,
+    BeforeLast,
+}
+
+/// Reads the word designator at the start of the text like `:2`, `^`, `$`, `*`, `:1-3`,
+/// `2-` (till the one before the last) or `-2` (from the command), returns the first and
+/// the last selected word and the length of the designator. The words are numbered from 0,
+/// the command. The colon may be omitted before `^`, `$`, `*` and `-`.
+fn parse_designator(text: &str) -> Option<((Word, Word), usize)> {
+    let (text, colon) = match text.strip_prefix(':') {
+        Some(rest) => (rest, 1),
+        None => (text, 0),
+    };
+    let word = |text: &str| -> Option<(Word, usize)> {
+        match text.chars().next()? {
+            '^' => Some((Word::Number(1), 1)),
+            '$' => Some((Word::Last, 1)),
+            c if c.is_ascii_digit() => {
+                let length = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+                Some((Word::Number(text[..length].parse().ok()?), length))
+            }
+            _ => None,
+        }
+    };
+    if text.starts_with('*') {
+        return Some(((Word::Number(1), Word::Last), colon + 1));
+    }
+    if let Some(rest) = text.strip_prefix('-') {
+        let (last, length) = word(rest)?;
+        return Some(((Word::Number(0), last), colon + 1 + length));
+    }
+    let (first, length) = word(text)?;
+    if colon == 0 && matches!(first, Word::Number(_)) && !text.starts_with('^') {
+        // `!12` is an event, a number needs a colon to be a word
+        return None;
+    }
+    let rest = &text[length..];
+    let designator = if rest.starts_with('*') {
+        ((first, Word::Last), length + 1)
+    } else if let Some(after) = rest.strip_prefix('-') {
+        match word(after) {
+            Some((last, last_length)) => ((first, last), length + 1 + last_length),
+            None => ((first, Word::BeforeLast), length + 1),
+        }
+    } else {
+        ((first, first), length)
+    };
+    Some((designator.0, colon + designator.1))
+}
+
+/// Takes the words selected by the designator, `*` of a command without arguments is empty
+fn select_words(mut words: Vec<String>, (first, last): (Word, Word)) -> Result<Vec<String>> {
+    let position = |word| match word {
+        Word::Number(number) => Some(number),
+        Word::Last => words.len().checked_sub(1),
+        Word::BeforeLast => words.len().checked_sub(2),
+    };
+    let (first, last) = match (position(first), position(last)) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(Error::BadWordDesignator),
+    };
+    if first == words.len() && first == last + 1 {
+        return Ok(Vec::new());
+    }
+    if first > last || last >= words.len() {
+        return Err(Error::BadWordDesignator);
+    }
+    words.truncate(last + 1);
+    Ok(words.split_off(first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_with(entries: &[&str]) -> Shell {
+        let mut shell = Shell::new().unwrap();
+        for entry in entries {
+            shell.history.push(entry, 100);
+        }
+        shell
+    }
+
+    fn expand(shell: &mut Shell, line: &str) -> Option<String> {
+        shell.expand_history(line).unwrap().map(|(line, _)| line)
+    }
+
+    #[test]
+    fn references_name_commands() {
+        let mut shell = shell_with(&["ls /tmp", "cat foo.c", "make"]);
+        assert_eq!(expand(&mut shell, "!! all").unwrap(), "make all");
+        assert_eq!(expand(&mut shell, "!1; !-2").unwrap(), "ls /tmp; cat foo.c");
+        assert_eq!(expand(&mut shell, "vi !ca:gs/foo/bar/").unwrap(), "vi cat bar.c");
+        assert_eq!(expand(&mut shell, "!cat:s/foo/&2/:p"), Some(String::from("cat foo2.c")));
+        assert_eq!(expand(&mut shell, "^ma^fa^ -j2").unwrap(), "fake -j2");
+        assert_eq!(expand(&mut shell, "test ! -e x != '!!' \\!!"), None);
+        assert!(matches!(shell.expand_history("!vi"), Err(Error::EventNotFound(_))));
+        assert!(matches!(shell.expand_history("^x^y"), Err(Error::ModifierFailed)));
+        assert!(shell.expand_history("!cat:p").unwrap().unwrap().1);
+    }
+
+    #[test]
+    fn designators_select_words() {
+        let mut shell = shell_with(&["cp 'a b' x/y.c /tmp", "make"]);
+        assert_eq!(expand(&mut shell, "vi !-2$ !cp^").unwrap(), "vi /tmp 'a b'");
+        let line = expand(&mut shell, "echo !cp:2-$:t !cp*").unwrap();
+        assert_eq!(line, "echo y.c /tmp 'a b' x/y.c /tmp");
+        assert_eq!(expand(&mut shell, "!1:0 !1:1- !1:-1").unwrap(), "cp 'a b' x/y.c cp 'a b'");
+        assert_eq!(expand(&mut shell, "echo !*|").unwrap(), "echo |");
+        assert_eq!(expand(&mut shell, "!:0 -k !$").unwrap(), "make -k make");
+        assert_eq!(expand(&mut shell, "mv file.txt !#:1.bak").unwrap(), "mv file.txt file.txt.bak");
+        assert!(matches!(shell.expand_history("!!:2"), Err(Error::BadWordDesignator)));
+        assert!(matches!(shell.expand_history("!cp:3-1"), Err(Error::BadWordDesignator)));
+    }
+}
@@ -0,0 +1,405 @@
+use std::env::var;
+use std::iter::once;
+use std::path::PathBuf;
+
+use native::error::*;
+use native::users::get_named_home_dir;
+
+use super::ast::{parse_line, Command, SimpleCommand, Word};
+use super::braces::expand_braces;
+use super::lexer::escape_pattern;
+use super::modifiers::{modify, parse_modifiers, Modification};
+use super::pipeline::Stage;
+use super::redirect::Redirection;
+use super::variables::Value;
+use super::Shell;
+
+impl Shell {
+    /// Expands the line like it is expanded before running: history references, braces,
+    /// `~`, variables and filename patterns, there are no aliases. Returns the words which
+    /// the command would get, the command first, without running anything. The line is
+    /// a single command, `if` and `repeat` give the words of their command, while a list
+    /// or a pipeline is an error since it has more than one.
+    pub fn expand_line(&mut self, line: &str) -> Result<Vec<String>> {
+        let line = match self.expand_history(line)? {
+            Some((line, _)) => line,
+            None => String::from(line),
+        };
+        let list = parse_line(&line)?;
+        let command = match list.items.as_slice() {
+            [] => return Ok(Vec::new()),
+            [(_, command)] => command,
+            _ => return Err(Error::SyntaxError.about(line)),
+        };
+        if let Command::Pipeline(pipeline) = command {
+            if pipeline.commands.len() > 1 || pipeline.background {
+                return Err(Error::SyntaxError.about(line));
+            }
+        }
+        Ok(self.expand_command(command.first_simple())?.words)
+    }
+
+    /// Expands braces in the words of the command, then `~` and variables in them
+    /// and filename patterns in its arguments. Variables and `~` are expanded in its
+    /// assignments and redirections too, the latter are checked but not opened.
+    /// A command of assignments only is an error, a group is left to its subshell.
+    pub fn expand_command(&mut self, command: &SimpleCommand) -> Result<Stage> {
+        if let Some(group) = &command.group {
+            return Ok(Stage {
+                group: Some(group.clone()),
+                redirections: self.expand_redirects(command)?,
+                with_stderr: command.with_stderr,
+                ..Stage::default()
+            });
+        }
+        let mut words = Vec::with_capacity(command.words.len());
+        let mut patterns = Vec::with_capacity(command.words.len());
+        for word in self.expand_braces(&command.words)? {
+            let fields = self.expand_fields(&word)?;
+            // a list variable makes several words, they aren't patterns
+            let pattern = match fields.len() {
+                1 => self.home_pattern(&word)?,
+                _ => None,
+            };
+            patterns.resize(patterns.len() + fields.len(), pattern);
+            words.extend(fields);
+        }
+        if words.is_empty() {
+            return Err(Error::NotFound);
+        }
+        let name = words.remove(0);
+        let arguments = self.expand_globs(&name, words, &patterns[1..])?;
+        Ok(Stage {
+            assignments: self.expand_words(&command.assignments)?,
+            words: once(name).chain(arguments).collect(),
+            group: None,
+            redirections: self.expand_redirects(command)?,
+            with_stderr: command.with_stderr,
+        })
+    }
+
+    /// Expands `~` and variables in the targets of the redirections of the command
+    /// and checks them, nothing is opened
+    pub fn expand_redirects(&mut self, command: &SimpleCommand) -> Result<Vec<Redirection>> {
+        let mut redirections = Vec::with_capacity(command.redirects.len());
+        for redirect in &command.redirects {
+            let target = self.expand_words(std::slice::from_ref(&redirect.target))?.remove(0);
+            redirections.push(Redirection::parse(&redirect.operator, Some(&target))?);
+        }
+        Ok(redirections)
+    }
+
+    /// Replaces the words with braces by the words they expand to, see `expand_braces`
+    fn expand_braces(&self, words: &[Word]) -> Result<Vec<Word>> {
+        let limit = self.max_words();
+        let mut result = Vec::with_capacity(words.len());
+        for word in words {
+            let expanded = expand_braces(&word.raw, result.len(), limit)?;
+            if expanded.len() == 1 && expanded[0] == word.raw {
+                result.push(word.clone());
+            } else {
+                result.extend(expanded.iter().map(|raw| Word::new(raw)));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Substitutes arithmetic expressions and variables in the words
+    /// except the `$` which are quoted, see `lexer::quoted_dollars`.
+    /// Every word stays one word, the words of lists are joined with spaces.
+    pub fn expand_words(&mut self, words: &[Word]) -> Result<Vec<String>> {
+        words
+            .iter()
+            .map(|word| {
+                let text = self.expand_word(&word.text, &word.quoted)?;
+                self.expand_tilde(&word.raw, text)
+            })
+            .collect()
+    }
+
+    /// Expands the word like `expand_words` but a list variable outside double quotes
+    /// gives a word for every word of the list, an empty one gives none
+    fn expand_fields(&mut self, word: &Word) -> Result<Vec<String>> {
+        let mut fields = self.substitute(&word.text, &word.quoted, &word.joined)?;
+        if let Some(first) = fields.first_mut() {
+            *first = self.expand_tilde(&word.raw, std::mem::take(first))?;
+        }
+        Ok(fields)
+    }
+
+    /// Replaces `~` or `~user` at the beginning of the word with the home directory,
+    /// the text is the word after the other expansions and still begins with it.
+    /// A quoted `~` is a plain character, an unknown user is an error.
+    fn expand_tilde(&self, raw: &str, text: String) -> Result<String> {
+        match tilde_user(raw) {
+            Some(user) => {
+                let home = self.user_home(user)?;
+                Ok(format!("{}{}", home.to_string_lossy(), &text[(1 + user.len())..]))
+            }
+            None => Ok(text),
+        }
+    }
+
+    /// Gets the pattern of the word with the home directory instead of `~`,
+    /// the name of the directory is matched literally
+    fn home_pattern(&self, word: &Word) -> Result<Option<String>> {
+        match (&word.pattern, tilde_user(&word.raw)) {
+            (Some(pattern), Some(user)) => {
+                let home = escape_pattern(&self.user_home(user)?.to_string_lossy());
+                Ok(Some(format!("{}{}", home, &pattern[(1 + user.len())..])))
+            }
+            (pattern, _) => Ok(pattern.clone()),
+        }
+    }
+
+    /// Gets the home directory of the user, of the current one if the name is empty
+    fn user_home(&self, user: &str) -> Result<PathBuf> {
+        if user.is_empty() {
+            return Ok(self.home.clone());
+        }
+        get_named_home_dir(user).map_err(|_| Error::UnknownUser(String::from(user)))
+    }
+
+    /// Replaces every `$name`, `${name}` and `$(( expression ))` anywhere in the word with
+    /// its value and `` `command` `` with its output except the `$` and `` ` `` at the offsets.
+    /// A `$` not followed by a name is a plain character.
+    /// Modifiers may follow the name like `$name:t` or `${name:s/old/new/}`.
+    /// The values are inserted as they are, they aren't expanded again.
+    fn expand_word(&mut self, word: &str, quoted: &[usize]) -> Result<String> {
+        // every list is joined back with the spaces it is split at
+        Ok(self.substitute(word, quoted, &[])?.join(" "))
+    }
+
+    /// Does the substitutions of `expand_word` splitting the word at the words of list
+    /// variables and of the output of commands unless their `$` or `` ` `` is at one of
+    /// the `joined` offsets, there the lines of the output are joined with spaces
+    fn substitute(
+        &mut self,
+        word: &str,
+        quoted: &[usize],
+        joined: &[usize],
+    ) -> Result<Vec<String>> {
+        let mut fields = Vec::new();
+        let mut result = String::with_capacity(word.len());
+        // an empty list alone makes no word at all
+        let mut vanished = false;
+        let mut index = 0;
+        while let Some(found) = word[index..].find(['$', '`']) {
+            let begin = index + found;
+            result.push_str(&word[index..begin]);
+            let after = &word[(begin + 1)..];
+            index = begin + 1;
+            if quoted.contains(&begin) {
+                result.push_str(&word[begin..index]);
+            } else if word[begin..].starts_with('`') {
+                let end = after.find('`').ok_or(Error::UnmatchedQuote('`'))?;
+                let output = self.command_output(&after[..end])?;
+                let value = if joined.contains(&begin) {
+                    Value::word(output.trim_end_matches('\n').replace('\n', " "))
+                } else {
+                    Value::list(output.split_whitespace().map(String::from).collect())
+                };
+                vanished |= push_value(&mut fields, &mut result, value, true);
+                index += end + 1;
+            } else if let Some(inner) = after.strip_prefix("((") {
+                let end = closing_parentheses(inner).ok_or(Error::SyntaxError)?;
+                result.push_str(&self.arithmetic(&inner[..end])?);
+                index += 2 + end + 2;
+            } else if let Some(inner) = after.strip_prefix('{') {
+                let end = inner.find('}').ok_or(Error::UnmatchedQuote('{'))?;
+                if end == 0 {
+                    return Err(Error::SyntaxError.about("${}"));
+                }
+                let inner = &inner[..end];
+                let (name, text) = inner.split_at(inner.find(':').unwrap_or(end));
+                let (modifiers, length) = parse_modifiers(text);
+                if length < text.len() {
+                    return Err(Error::SyntaxError.about(&text[length..]));
+                }
+                let value = self.variable_value(name);
+                let value = self.modify_value(value, &modifiers)?;
+                let split = !joined.contains(&begin);
+                vanished |= push_value(&mut fields, &mut result, value, split);
+                index += 1 + end + 1;
+            } else {
+                let length = after
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                if length == 0 {
+                    result.push('$');
+                } else {
+                    let value = self.variable_value(&after[..length]);
+                    let (modifiers, modifiers_length) = parse_modifiers(&after[length..]);
+                    let value = self.modify_value(value, &modifiers)?;
+                    let split = !joined.contains(&begin);
+                    vanished |= push_value(&mut fields, &mut result, value, split);
+                    index += length + modifiers_length;
+                }
+            }
+        }
+        result.push_str(&word[index..]);
+        if !(vanished && fields.is_empty() && result.is_empty()) {
+            fields.push(result);
+        }
+        Ok(fields)
+    }
+
+    /// Applies the modifiers like `$file:r` or `${path:gs/:/ /}` to the words of the value
+    fn modify_value(&mut self, value: Value, modifiers: &[Modification]) -> Result<Value> {
+        if modifiers.is_empty() {
+            return Ok(value);
+        }
+        let words = modify(value.words(), modifiers, &mut self.history.last_substitution)?;
+        if value.is_list() {
+            Ok(Value::list(words))
+        } else {
+            Ok(Value::word(words.join(" ")))
+        }
+    }
+
+    /// Looks for the variable in shell variables, special variables and the environment
+    fn variable_value(&mut self, name: &str) -> Value {
+        if name == "loginsh" {
+            return Value::word(String::from(if self.is_login { "1" } else { "0" }));
+        }
+        if let Some(value) = self.variables.value(name) {
+            return value.clone();
+        }
+        Value::word(match name {
+            "RANDOM" => format!("{}", self.random.next_u64() % 32768),
+            "status" => format!("{}", self.status),
+            "coredumped" => String::from(if self.core_dumped { "1" } else { "0" }),
+            _ => var(name).unwrap_or_default(),
+        })
+    }
+}
+
+/// Appends the value to the word being expanded. The first word of a split list goes
+/// to the end of it, every next one ends it and begins a new one.
+/// Tells whether the value was an empty list which has vanished.
+fn push_value(fields: &mut Vec<String>, result: &mut String, value: Value, split: bool) -> bool {
+    if !(split && value.is_list()) {
+        result.push_str(value.text());
+        return false;
+    }
+    let mut words = value.words().into_iter();
+    match words.next() {
+        Some(first) => result.push_str(&first),
+        None => return true,
+    }
+    for word in words {
+        fields.push(std::mem::replace(result, word));
+    }
+    false
+}
+
+/// Gets the user name of `~user` at the beginning of the word as it is written,
+/// an empty one for `~` alone, None if the word doesn't begin with an unquoted `~`
+fn tilde_user(raw: &str) -> Option<&str> {
+    let rest = raw.strip_prefix('~')?;
+    let user = &rest[..rest.find('/').unwrap_or(rest.len())];
+    let plain = user.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if plain {
+        Some(user)
+    } else {
+        None
+    }
+}
+
+/// Finds `))` which closes `$((`, parentheses inside the expression are skipped
+fn closing_parentheses(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => return if text[index..].starts_with("))") { Some(index) } else { None },
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn find_closing_parentheses() {
+        assert_eq!(closing_parentheses("1 + 2)) rest"), Some(5));
+        assert_eq!(closing_parentheses("(1 + 2) * 3))"), Some(11));
+        assert_eq!(closing_parentheses("1 + 2)"), None);
+        assert_eq!(closing_parentheses("1 + 2"), None);
+    }
+
+    #[test]
+    fn variables_inside_words() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("dir"), String::from("/opt/$x"));
+        let expand = |shell: &mut Shell, word: &str, quoted: &[usize]| {
+            shell.expand_word(word, quoted).unwrap()
+        };
+        assert_eq!(expand(&mut shell, "--prefix=$dir/x", &[]), "--prefix=/opt/$x/x");
+        assert_eq!(expand(&mut shell, "${dir}s and $dir_x.", &[]), "/opt/$xs and .");
+        assert_eq!(expand(&mut shell, "$dir costs $-5 $", &[0]), "$dir costs $-5 $");
+        assert_eq!(expand(&mut shell, "n=$((1 + 2))$", &[]), "n=3$");
+        assert!(shell.expand_word("${dir", &[]).is_err());
+    }
+
+    #[test]
+    fn lines_are_expanded_without_running() {
+        let dir = TestDir::new("expand");
+        for name in &["a.rs", "b.rs", "c.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let mut shell = Shell::new().unwrap();
+        shell.cwd = dir.path().to_path_buf();
+        shell.variables.insert(String::from("flags"), String::from("-l"));
+        shell.add_history("touch 'new file'");
+        let words = shell.expand_line("LANG=C rm $flags *.rs !!:1 '$x*'").unwrap();
+        shell.home = dir.path().to_path_buf();
+        let home = shell.expand_line("echo ~/*.txt ~ '~' {1..3} x{$flags,'{a,b}'}").unwrap();
+        shell.variables.insert(String::from("maxwords"), String::from("3"));
+        assert!(shell.expand_line("echo *.rs").is_ok());
+        assert!(shell.expand_line("echo x *.rs").is_err());
+        assert!(shell.expand_line("echo *.rs x").is_err());
+        assert_eq!(words, vec!["rm", "-l", "a.rs", "b.rs", "new file", "$x*"]);
+        let txt = dir.join("c.txt").display().to_string();
+        let home_dir = dir.path().display().to_string();
+        let expected = vec!["echo", &txt, &home_dir, "~", "1", "2", "3", "x-l", "x{a,b}"];
+        assert_eq!(home, expected);
+        assert!(shell.expand_line("echo ~no-such-user").is_err());
+        assert!(shell.expand_line("ls | wc").is_err());
+        assert!(shell.expand_line("ls ; ls").is_err());
+        assert_eq!(shell.expand_line("if (1) repeat 2 ls").unwrap(), vec!["ls"]);
+        shell.variables.insert(String::from("maxwords"), String::from("5"));
+        assert!(shell.expand_line("echo {1..4}").is_ok());
+        assert!(shell.expand_line("echo {1..5}").is_err());
+        assert!(shell.expand_line("").unwrap().is_empty());
+        shell.variables.insert(String::from("tool"), String::from("echo"));
+        assert_eq!(shell.expand_line("$tool:t hi").unwrap(), vec!["echo", "hi"]);
+        assert!(shell.expand_line("ls *.none").is_err());
+        shell.variables.remove("maxwords");
+        let words = vec![String::from("-l"), String::from("a b")];
+        shell.variables.insert_value(String::from("flags"), Value::list(words));
+        shell.variables.insert_value(String::from("none"), Value::list(Vec::new()));
+        let words = shell.expand_line("ls $flags x$flags:t\"$flags\" $none '' \"$none\"").unwrap();
+        assert_eq!(words, vec!["ls", "-l", "a b", "x-l", "a b-l a b", "", ""]);
+        assert_eq!(shell.expand_line("$flags").unwrap(), vec!["-l", "a b"]);
+    }
+
+    #[test]
+    fn modifiers_of_variables() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("file"), String::from("/src/main.rs"));
+        shell.variables.insert(String::from("list"), String::from("a.c b.c"));
+        let mut expand = |word: &str| shell.expand_word(word, &[]);
+        assert_eq!(expand("$file:h:t:$file:t:r").unwrap(), "src:main");
+        assert_eq!(expand("${file:e}s $list:gs/c/o/").unwrap(), "rss a.o b.o");
+        assert_eq!(expand("${list:s/.c/&pp/}:8080").unwrap(), "a.cpp b.c:8080");
+        assert!(expand("$list:s/x/y/").is_err());
+        assert!(expand("${file:q}").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use native::error::*;
+use native::write_all;
+
+use super::Shell;
+
+/// Seconds to wait for the whole download unless `fetchtimeout` is set
+const DEFAULT_TIMEOUT: u64 = 30;
+/// Bytes of the response allowed unless `fetchlimit` is set
+const DEFAULT_LIMIT: usize = 16 * 1024 * 1024;
+/// Redirections followed before giving up
+const MAX_REDIRECTS: usize = 5;
+
+/// Parts of an `http://` URL
+#[derive(Debug, PartialEq)]
+struct Url<'a> {
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+/// Splits the URL, only plain HTTP is supported since there is no TLS implementation
+fn parse_url(url: &str) -> Result<Url<'_>> {
+    let rest = url.strip_prefix("http://").ok_or(Error::UnsupportedUrl)?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| Error::UnsupportedUrl)?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(Error::UnsupportedUrl);
+    }
+    Ok(Url { host, port, path })
+}
+
+/// A response split into the status code, the Location header and the body
+#[derive(Debug, PartialEq)]
+struct Response<'a> {
+    status: u16,
+    location: Option<&'a str>,
+    body: &'a [u8],
+}
+
+/// Parses an HTTP/1.x response read till the end of the connection
+fn parse_response(data: &[u8]) -> Result<Response<'_>> {
+    let end = data
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or(Error::BadResponse)?;
+    let head = std::str::from_utf8(&data[..end]).map_err(|_| Error::BadResponse)?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .filter(|line| line.starts_with("HTTP/1."))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or(Error::BadResponse)?;
+    let location = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("location"))
+        .map(|(_, value)| value.trim());
+    Ok(Response {
+        status,
+        location,
+        body: &data[(end + 4)..],
+    })
+}
+
+/// Converts errors of the standard library, they carry errno unless it's a timeout
+fn from_io(error: io::Error) -> Error {
+    match error.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Error::TimedOut,
+        _ => match error.raw_os_error() {
+            Some(code) => Error::Errno(Errno::from_code(code)),
+            None => Error::BadResponse,
+        },
+    }
+}
+
+/// Sends a GET request and reads the whole response, failing after the deadline or the limit
+fn request(url: &Url, deadline: Instant, limit: usize) -> Result<Vec<u8>> {
+    let left = || {
+        deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| *left > Duration::from_millis(0))
+            .ok_or(Error::TimedOut)
+    };
+    let address = (url.host, url.port)
+        .to_socket_addrs()
+        .map_err(|_| Error::NotFound)?
+        .next()
+        .ok_or(Error::NotFound)?;
+    let mut stream = TcpStream::connect_timeout(&address, left()?).map_err(from_io)?;
+    stream.set_write_timeout(Some(left()?)).map_err(from_io)?;
+    // HTTP/1.0 makes the server close the connection and not use chunked encoding
+    let text = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: rsh\r\nConnection: close\r\n\r\n",
+        url.path, url.host
+    );
+    stream.write_all(text.as_bytes()).map_err(from_io)?;
+    let mut data = Vec::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        stream.set_read_timeout(Some(left()?)).map_err(from_io)?;
+        let count = stream.read(&mut buf).map_err(from_io)?;
+        if count == 0 {
+            return Ok(data);
+        }
+        if data.len() + count > limit {
+            return Err(Error::ResponseTooLarge(limit));
+        }
+        data.extend_from_slice(&buf[..count]);
+    }
+}
+
+impl Shell {
+    /// Implements `fetch URL`: downloads the URL over HTTP following redirections
+    /// and prints the body once it has been read completely.
+    /// The time and the size are limited with `fetchtimeout` seconds and `fetchlimit` bytes.
+    pub fn fetch_builtin(&self, arguments: &[&str]) -> Result<()> {
+        let url = match arguments {
+            [url] => *url,
+            [] => return Err(Error::MissingArgument("fetch")),
+            _ => return Err(Error::SyntaxError),
+        };
+        let timeout = self.variables.get("fetchtimeout").and_then(|value| value.parse().ok());
+        let deadline = Instant::now() + Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT));
+        let limit = self.variables.get("fetchlimit").and_then(|value| value.parse().ok());
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        let mut url = String::from(url);
+        for _ in 0..=MAX_REDIRECTS {
+            let data = request(&parse_url(&url)?, deadline, limit)?;
+            let response = parse_response(&data)?;
+            match (response.status, response.location) {
+                (200..=299, _) => return write_all(1, response.body),
+                (300..=399, Some(location)) if location.starts_with('/') => {
+                    let parsed = parse_url(&url)?;
+                    url = format!("http://{}:{}{}", parsed.host, parsed.port, location);
+                }
+                (300..=399, Some(location)) => url = String::from(location),
+                (status, _) => return Err(Error::HttpStatus(status)),
+            }
+        }
+        Err(Error::TooManyRedirects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let url = parse_url("http://example.com:8080/a/b?c").unwrap();
+        assert_eq!(url, Url { host: "example.com", port: 8080, path: "/a/b?c" });
+        assert_eq!(parse_url("http://example.com").unwrap().path, "/");
+        assert!(parse_url("https://example.com/").is_err());
+        assert!(parse_url("ftp://example.com/").is_err());
+        assert!(parse_url("http://:80/").is_err());
+    }
+
+    #[test]
+    fn responses() {
+        let data = b"HTTP/1.1 301 Moved\r\nLOCATION: /new\r\n\r\nbody";
+        let response = parse_response(data).unwrap();
+        assert_eq!(response.status, 301);
+        assert_eq!(response.location, Some("/new"));
+        assert_eq!(response.body, b"body");
+        assert!(parse_response(b"SSH-2.0\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn one_url_is_required() {
+        let shell = Shell::new().unwrap();
+        assert!(matches!(shell.fetch_builtin(&[]), Err(Error::MissingArgument("fetch"))));
+        let extra = shell.fetch_builtin(&["http://a", "http://b"]);
+        assert!(matches!(extra, Err(Error::SyntaxError)));
+    }
+}
@@ -0,0 +1,139 @@
+use native::error::*;
+use native::read_byte;
+
+use super::Shell;
+
+/// Separators used when `ifs` is unset
+const DEFAULT_SEPARATORS: &str = " \t\n";
+
+/// Splits the text into fields at the separators like IFS of sh: runs of whitespace
+/// separators count as one and are ignored at the ends, any other separator ends a field,
+/// so two of them in a row give an empty field
+pub fn split_fields(text: &str, separators: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    // a field has begun, even an empty one, since the last separator
+    let mut started = false;
+    for c in text.chars() {
+        if !separators.contains(c) {
+            field.push(c);
+            started = true;
+        } else if c.is_whitespace() {
+            if started {
+                fields.push(std::mem::take(&mut field));
+                started = false;
+            }
+        } else {
+            fields.push(std::mem::take(&mut field));
+            started = false;
+        }
+    }
+    if started {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Turns `\n` and `\t` of the variable value into the characters
+fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n").replace("\\t", "\t")
+}
+
+impl Shell {
+    /// Gets the characters which split input of `read` into words,
+    /// `ifs` may use `\n` and `\t` for newline and tab, whitespace is the default
+    pub fn field_separators(&self) -> String {
+        match self.variables.get("ifs") {
+            Some(value) => unescape(value),
+            None => String::from(DEFAULT_SEPARATORS),
+        }
+    }
+
+    /// Implements `read name ...`: reads a line from stdin and assigns its fields to the
+    /// variables in order, the last one gets the rest of the fields joined with spaces.
+    /// `read -0 name ...` reads a record ended by a null byte for every variable instead,
+    /// records are not split, so file names from `find -print0` are kept intact.
+    /// The status is 1 at the end of input.
+    pub fn read_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        let (delimiter, names) = match arguments.split_first() {
+            Some((&"-0", names)) => (b'\0', names),
+            _ => (b'\n', arguments),
+        };
+        if names.is_empty() {
+            return Err(Error::MissingArgument("read"));
+        }
+        if delimiter == b'\0' {
+            for name in names {
+                match read_record(delimiter)? {
+                    Some(record) => {
+                        self.variables.insert(String::from(*name), record);
+                    }
+                    None => {
+                        self.status = 1;
+                        return Ok(());
+                    }
+                }
+            }
+            return Ok(());
+        }
+        let line = match read_record(delimiter)? {
+            Some(line) => line,
+            None => {
+                self.status = 1;
+                return Ok(());
+            }
+        };
+        let mut fields = split_fields(&line, &self.field_separators()).into_iter();
+        for (index, name) in names.iter().enumerate() {
+            let value = if index + 1 == names.len() {
+                fields.by_ref().collect::<Vec<String>>().join(" ")
+            } else {
+                fields.next().unwrap_or_default()
+            };
+            self.variables.insert(String::from(*name), value);
+        }
+        Ok(())
+    }
+}
+
+/// Reads stdin till the delimiter, which is dropped, or the end of input.
+/// Returns None if the input has ended before anything is read.
+fn read_record(delimiter: u8) -> Result<Option<String>> {
+    // byte by byte, so the rest of the input is left to the commands which follow
+    let mut record = Vec::new();
+    let mut ended = true;
+    while let Some(byte) = read_byte(0)? {
+        ended = false;
+        if byte == delimiter {
+            break;
+        }
+        record.push(byte);
+    }
+    if ended {
+        return Ok(None);
+    }
+    String::from_utf8(record).map(Some).map_err(|_| Error::InvalidUnicode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_at_whitespace() {
+        assert_eq!(split_fields("  a \t b\nc  ", DEFAULT_SEPARATORS), vec!["a", "b", "c"]);
+        assert!(split_fields("   ", DEFAULT_SEPARATORS).is_empty());
+    }
+
+    #[test]
+    fn split_at_newlines_only() {
+        let fields = split_fields("my file.txt\nother file\n", "\n");
+        assert_eq!(fields, vec!["my file.txt", "other file"]);
+    }
+
+    #[test]
+    fn split_at_other_separators() {
+        assert_eq!(split_fields("a::b: c", ": "), vec!["a", "", "b", "c"]);
+        assert_eq!(split_fields(":a", ":"), vec!["", "a"]);
+    }
+}
@@ -0,0 +1,65 @@
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+use libc::{ENXIO, O_NONBLOCK};
+
+use native::error::*;
+use native::file_stat::is_fifo;
+use native::{make_fifo, open_file, set_blocking};
+
+use super::Shell;
+
+/// Permissions of pipes made by `mkfifo` without `-m`, the umask is applied by the system
+const FIFO_MODE: u32 = 0o666;
+
+/// Opens the target of an output redirection. A named pipe is opened without waiting first,
+/// then waits are turned back on for writes. If the pipe has no reader yet, a forked child
+/// waits for one as usual, while the shell itself gets ENXIO instead of blocking forever.
+pub fn open_output(path: &Path, flags: i32, mode: u32, wait_reader: bool) -> Result<RawFd> {
+    if is_fifo(path) {
+        match open_file(path, flags | O_NONBLOCK, Some(mode)) {
+            Ok(fd) => {
+                set_blocking(fd)?;
+                return Ok(fd);
+            }
+            Err(ref reason) if reason.is_errno(ENXIO) && wait_reader => {}
+            Err(reason) => return Err(reason),
+        }
+    }
+    open_file(path, flags, Some(mode))
+}
+
+impl Shell {
+    /// Implements `mkfifo [-m mode] name ...`: creates named pipes,
+    /// the mode is octal like in chmod(1)
+    pub fn mkfifo_builtin(&self, arguments: &[&str]) -> Result<()> {
+        let (mode, names) = match arguments {
+            ["-m", mode, names @ ..] => {
+                let mode = u32::from_str_radix(mode, 8).map_err(|_| Error::SyntaxError)?;
+                (mode, names)
+            }
+            names => (FIFO_MODE, names),
+        };
+        if names.is_empty() {
+            return Err(Error::MissingArgument("mkfifo"));
+        }
+        for name in names {
+            make_fifo(&self.cwd.join(name), mode).map_err(|reason| reason.about(name))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_mode_are_checked() {
+        let shell = Shell::new().unwrap();
+        let missing = shell.mkfifo_builtin(&["-m", "600"]);
+        assert!(matches!(missing, Err(Error::MissingArgument("mkfifo"))));
+        let mode = shell.mkfifo_builtin(&["-m", "rw", "fifo"]);
+        assert!(matches!(mode, Err(Error::SyntaxError)));
+    }
+}
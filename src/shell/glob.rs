@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use native::error::*;
+use native::file_stat::get_link_mode;
+use native::{collate, read_dir_names};
+
+use super::Shell;
+
+/// Commands whose arguments aren't globbed: `*` is multiplication in expressions
+/// and key notation like `^?` or `^[` is no pattern
+const LITERAL_COMMANDS: &[&str] = &["@", "bindkey", "calc"];
+
+impl Shell {
+    /// Replaces the arguments which are filename patterns with the names of the matching files
+    /// sorted like `ls-F` does it, see `lexer::glob_patterns`. A pattern matching nothing
+    /// is an error unless `nonomatch` is set, then it is passed as is.
+    /// Nothing is globbed with `noglob` set or in the arguments of `@`, `bindkey` and `calc`.
+    /// Fails as soon as the command and its arguments are more words than `maxwords`.
+    pub fn expand_globs(
+        &self,
+        command: &str,
+        words: Vec<String>,
+        patterns: &[Option<String>],
+    ) -> Result<Vec<String>> {
+        if self.variables.contains_key("noglob") || LITERAL_COMMANDS.contains(&command) {
+            return Ok(words);
+        }
+        let limit = self.max_words();
+        // the command itself is a word too
+        let room = limit - 1;
+        let mut result = Vec::with_capacity(words.len());
+        for (word, pattern) in words.into_iter().zip(patterns) {
+            if result.len() == room {
+                return Err(Error::TooManyWords(limit));
+            }
+            let pattern = match pattern {
+                Some(pattern) => pattern,
+                None => {
+                    result.push(word);
+                    continue;
+                }
+            };
+            let names = glob(pattern, &self.cwd, room - result.len())
+                .ok_or(Error::TooManyWords(limit))?;
+            if !names.is_empty() {
+                result.extend(names);
+            } else if self.variables.contains_key("nonomatch") {
+                result.push(word);
+            } else {
+                return Err(Error::NoMatch.about(command));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Finds the files matching the pattern, relative ones in the directory.
+/// Hidden files match only if the pattern of the name starts with a dot,
+/// a trailing slash makes the pattern match only directories.
+/// None if the last part of the pattern matches more than `limit` names.
+fn glob(pattern: &str, cwd: &Path, limit: usize) -> Option<Vec<String>> {
+    let mut found = vec![String::from(if pattern.starts_with('/') { "/" } else { "" })];
+    let parts: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+    for (index, part) in parts.iter().enumerate() {
+        let last = index + 1 == parts.len();
+        let mut next = Vec::new();
+        for prefix in &found {
+            if !has_special(part) {
+                next.push(format!("{}{}/", prefix, unescape(part)));
+                continue;
+            }
+            let dir = cwd.join(if prefix.is_empty() { "." } else { prefix.as_str() });
+            let part: Vec<char> = part.chars().collect();
+            for name in read_dir_names(&dir).unwrap_or_default() {
+                let hidden = name.starts_with('.') && !part.starts_with(&['.']);
+                if !hidden && matches(&part, &name.chars().collect::<Vec<char>>()) {
+                    if last && next.len() == limit {
+                        return None;
+                    }
+                    next.push(format!("{}{}/", prefix, name));
+                }
+            }
+        }
+        found = next;
+    }
+    let dirs_only = pattern.ends_with('/');
+    let mut names: Vec<String> = found
+        .into_iter()
+        .filter(|name| name.len() > 1)
+        .map(|mut name| {
+            if !dirs_only {
+                name.pop();
+            }
+            name
+        })
+        // literal parts after the patterns may name nothing
+        .filter(|name| get_link_mode(&cwd.join(name)).is_ok())
+        .collect();
+    names.sort_by(|left, right| collate(left, right));
+    Some(names)
+}
+
+/// Checks whether the part of a pattern has an unescaped `*`, `?` or `[`
+fn has_special(part: &str) -> bool {
+    let mut chars = part.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Removes the backslashes escaping characters of the pattern
+fn unescape(part: &str) -> String {
+    let mut result = String::with_capacity(part.len());
+    let mut chars = part.chars();
+    while let Some(c) = chars.next() {
+        result.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    result
+}
+
+/// Matches the name against the pattern: `*` is any string, `?` is any character,
+/// `[a-z]` is one of the characters, `[!a]` or `[^a]` is any other one
+/// and a backslash makes the next character plain
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['*', rest @ ..] => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+        ['?', rest @ ..] => !name.is_empty() && matches(rest, &name[1..]),
+        ['[', ..] if name.is_empty() => false,
+        ['[', inner @ ..] => match match_bracket(inner, name[0]) {
+            Some((found, length)) => found && matches(&inner[length..], &name[1..]),
+            // an unclosed bracket is a plain character
+            None => name[0] == '[' && matches(inner, &name[1..]),
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            name.first() == Some(c) && matches(rest, &name[1..])
+        }
+    }
+}
+
+/// Checks whether the character is in the bracket expression after `[`,
+/// returns that and the length of the expression with `]`, None if it isn't closed
+fn match_bracket(inner: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = matches!(inner.first(), Some('!') | Some('^'));
+    let mut index = usize::from(negated);
+    let mut found = false;
+    // `]` right after the opening bracket is a member
+    let mut first = true;
+    while index < inner.len() {
+        let mut low = inner[index];
+        if low == ']' && !first {
+            return Some((found != negated, index + 1));
+        }
+        first = false;
+        if low == '\\' {
+            index += 1;
+            low = *inner.get(index)?;
+        }
+        let mut high = low;
+        if inner.get(index + 1) == Some(&'-') && inner.get(index + 2).is_some_and(|c| *c != ']') {
+            index += 2;
+            high = inner[index];
+            if high == '\\' {
+                index += 1;
+                high = *inner.get(index)?;
+            }
+        }
+        found |= low <= c && c <= high;
+        index += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    fn matches_str(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        matches(&pattern, &name)
+    }
+
+    #[test]
+    fn patterns_match_names() {
+        assert!(matches_str("*.rs", "main.rs"));
+        assert!(!matches_str("*.rs", "main.rc"));
+        assert!(matches_str("a?c", "abc"));
+        assert!(matches_str("[a-c]x", "bx"));
+        assert!(!matches_str("[!a-c]x", "bx"));
+        assert!(matches_str("[]]", "]"));
+        assert!(matches_str("\\*", "*"));
+        assert!(!matches_str("\\*", "a"));
+        assert!(matches_str("[ab", "[ab"));
+        assert!(matches_str("*é*", "café"));
+    }
+
+    #[test]
+    fn glob_walks_directories() {
+        let dir = TestDir::new("glob");
+        let root = dir.path();
+        for name in &["a/one.rs", "b/two.rs", "b/three.txt", "c", ".hidden/four.rs"] {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        assert_eq!(glob("*/*.rs", root, 10).unwrap(), vec!["a/one.rs", "b/two.rs"]);
+        assert_eq!(glob("*/", root, 10).unwrap(), vec!["a/", "b/"]);
+        assert_eq!(glob(".*/*", root, 10).unwrap(), vec![".hidden/four.rs"]);
+        assert_eq!(glob("b/t*", root, 10).unwrap(), vec!["b/three.txt", "b/two.rs"]);
+        assert_eq!(glob("?/missing", root, 10).unwrap(), Vec::<String>::new());
+        let absolute = format!("{}/[c-z]", root.display());
+        let expected = vec![format!("{}/c", root.display())];
+        assert_eq!(glob(&absolute, Path::new("/"), 10).unwrap(), expected);
+        assert_eq!(glob("*/*", root, 3).unwrap().len(), 3);
+        assert_eq!(glob("*/*", root, 2), None);
+        assert_eq!(glob("b/*", root, 2).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,148 @@
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use libc::{EINTR, O_RDWR, SIGINT};
+
+use native::error::*;
+use native::signal::send_signal;
+use native::term::RawMode;
+use native::{close_file, open_file, read_byte, write_to_file};
+
+use super::Shell;
+
+/// Output which stops for longer than this starts counting again
+const QUIET_GAP: Duration = Duration::from_secs(1);
+/// Clears the line with the question after the answer
+const ERASE_LINE: &str = "\r\x1b[K";
+/// Ctrl-C, raw mode reads it as a key
+const INTERRUPT_KEY: u8 = 3;
+
+/// Limits of continuous output of a foreground command to the terminal:
+/// `outputlimit` megabytes and `outputtime` seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guard {
+    bytes: Option<usize>,
+    duration: Option<Duration>,
+}
+
+/// Counts the output passing through the guard
+pub struct Watch {
+    guard: Guard,
+    bytes: usize,
+    started: Instant,
+    last: Option<Instant>,
+}
+
+impl Watch {
+    pub fn new(guard: Guard) -> Self {
+        Watch {
+            guard,
+            bytes: 0,
+            started: Instant::now(),
+            last: None,
+        }
+    }
+
+    /// Counts the chunk of output, returns true if the output has gone on for too long
+    pub fn exceeded(&mut self, count: usize) -> bool {
+        let now = Instant::now();
+        if self.last.is_none_or(|last| now - last > QUIET_GAP) {
+            self.restart(now);
+        }
+        self.last = Some(now);
+        self.bytes += count;
+        self.guard.bytes.is_some_and(|limit| self.bytes > limit)
+            || self.guard.duration.is_some_and(|limit| now - self.started >= limit)
+    }
+
+    /// Starts counting anew after the user has let the output go on
+    pub fn restart(&mut self, now: Instant) {
+        self.bytes = 0;
+        self.started = now;
+    }
+}
+
+/// Pauses the output and asks the user on the terminal whether it should go on.
+/// If the user refuses, the foreground job is interrupted like by Ctrl-C.
+pub fn ask_to_continue() -> Result<bool> {
+    let tty = open_file(Path::new("/dev/tty"), O_RDWR, None)?;
+    let answer = ask_on(tty);
+    close_file(tty)?;
+    if let Ok(false) = answer {
+        // the copier belongs to the job, so its process group is the one of the job
+        send_signal(0, SIGINT)?;
+    }
+    answer
+}
+
+/// Prints the question and reads keys till `c` or `q`, Ctrl-C means `q` too
+fn ask_on(tty: RawFd) -> Result<bool> {
+    ask_key(tty, &msg!("-- Long output, press q to kill, c to continue --"), b'c', b'q')
+}
+
+/// Prints the question on the terminal and reads keys in raw mode till the one answering yes
+/// or no in either case, Ctrl-C and the end of input mean no. The question is erased then.
+pub fn ask_key(tty: RawFd, question: &str, yes: u8, no: u8) -> Result<bool> {
+    write_to_file(tty, question)?;
+    let answer = {
+        let _raw = RawMode::enable(tty)?;
+        loop {
+            match read_byte(tty) {
+                Ok(Some(key)) if key.to_ascii_lowercase() == yes => break true,
+                Ok(Some(key)) if key.to_ascii_lowercase() == no || key == INTERRUPT_KEY => {
+                    break false
+                }
+                Ok(None) => break false,
+                Ok(Some(_)) => {}
+                Err(ref reason) if reason.is_errno(EINTR) => {}
+                Err(reason) => return Err(reason),
+            }
+        }
+    };
+    write_to_file(tty, ERASE_LINE)?;
+    Ok(answer)
+}
+
+impl Shell {
+    /// Reads the limits of the output guard, None if neither `outputlimit` megabytes
+    /// nor `outputtime` seconds is set
+    pub fn output_guard(&self) -> Result<Option<Guard>> {
+        let number = |name: &str| -> Result<Option<u64>> {
+            match self.variables.get(name) {
+                Some(value) => match value.parse() {
+                    Ok(number) if number > 0 => Ok(Some(number)),
+                    _ => Err(Error::SyntaxError),
+                },
+                None => Ok(None),
+            }
+        };
+        let bytes = number("outputlimit")?.map(|megabytes| megabytes as usize * 1024 * 1024);
+        let duration = number("outputtime")?.map(Duration::from_secs);
+        if bytes.is_none() && duration.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Guard { bytes, duration }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_counts_continuous_output() {
+        let guard = Guard {
+            bytes: Some(10),
+            duration: None,
+        };
+        let mut watch = Watch::new(guard);
+        assert!(!watch.exceeded(6));
+        assert!(watch.exceeded(6));
+        watch.restart(Instant::now());
+        assert!(!watch.exceeded(6));
+        // a pause in the output starts counting again
+        watch.last = Some(Instant::now() - QUIET_GAP * 2);
+        assert!(!watch.exceeded(6));
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use native::error::*;
+use native::file_stat::is_executable_file;
+use native::write_to_file;
+
+use super::debug::Debug;
+use super::Shell;
+
+/// Counters of command lookups for a directory of PATH
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DirStats {
+    /// Lookups answered by the hash table with a command of the directory
+    pub hits: u64,
+    /// Lookups which had to search PATH and found the command in the directory
+    pub misses: u64,
+    /// Remembered commands of the directory which were gone when they were run again
+    pub failures: u64,
+}
+
+/// Remembers where commands were found in PATH, so directories aren't read for every command
+#[derive(Debug, Default)]
+pub struct CommandHash {
+    table: HashMap<String, PathBuf>,
+    stats: HashMap<PathBuf, DirStats>,
+}
+
+impl CommandHash {
+    /// Forgets the remembered commands, the statistics are kept
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Counters of the directory the command is in
+    fn stats_of(&mut self, command: &Path) -> &mut DirStats {
+        let dir = command.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.stats.entry(dir).or_default()
+    }
+}
+
+/// Formats the statistics of the directories in the order of PATH
+fn format_stats(path: &[PathBuf], stats: &HashMap<PathBuf, DirStats>) -> String {
+    let mut text = format!("{:>8} {:>8} {:>8}  {}\n", "hits", "misses", "failures", "directory");
+    for dir in path {
+        let dir_stats = stats.get(dir).cloned().unwrap_or_default();
+        text.push_str(&format!(
+            "{:>8} {:>8} {:>8}  {}\n",
+            dir_stats.hits,
+            dir_stats.misses,
+            dir_stats.failures,
+            dir.display()
+        ));
+    }
+    text
+}
+
+impl Shell {
+    /// Finds the program like `find_path`, but remembers where commands were found in PATH.
+    /// A remembered command which isn't executable any more is looked for again.
+    pub fn hash_lookup(&mut self, name: &str) -> Option<PathBuf> {
+        if name.contains('/') {
+            return self.find_path(name);
+        }
+        if let Some(path) = self.hash.table.get(name).cloned() {
+            if is_executable_file(&path) {
+                self.hash.stats_of(&path).hits += 1;
+                return Some(path);
+            }
+            self.debug(Debug::Exec, || format!("{} is gone, rehashing {}", path.display(), name));
+            self.hash.stats_of(&path).failures += 1;
+            self.hash.table.remove(name);
+        }
+        let path = self.find_path(name)?;
+        self.hash.stats_of(&path).misses += 1;
+        if !path.parent().is_some_and(|dir| self.is_unhashed(dir)) {
+            self.hash.table.insert(String::from(name), path.clone());
+        }
+        Some(path)
+    }
+
+    /// Checks whether the directory of PATH is in the `nohash` list or under one of its entries.
+    /// Slow or remote directories like NFS mounts aren't read as a whole: commands are probed
+    /// in them one by one, aren't remembered and aren't completed.
+    pub fn is_unhashed(&self, dir: &Path) -> bool {
+        self.variables.get("nohash").is_some_and(|list| {
+            list.split_whitespace().any(|entry| dir.starts_with(self.expand_home(entry)))
+        })
+    }
+
+    /// Implements `hashstat`: prints lookup statistics of every PATH directory
+    pub fn hashstat_builtin(&self) -> Result<()> {
+        write_to_file(1, &format_stats(&self.path, &self.hash.stats))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn stats_follow_path_order() {
+        let path = vec![PathBuf::from("/usr/local/bin"), PathBuf::from("/bin")];
+        let mut hash = CommandHash::default();
+        hash.stats_of(Path::new("/bin/ls")).hits += 2;
+        hash.stats_of(Path::new("/bin/ls")).misses += 1;
+        let expected = "    hits   misses failures  directory\n       \
+                        0        0        0  /usr/local/bin\n       \
+                        2        1        0  /bin\n";
+        assert_eq!(format_stats(&path, &hash.stats), expected);
+    }
+
+    #[test]
+    fn unhashed_directories_are_probed() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = TestDir::new("nohash");
+        let dir = root.join("bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("tool");
+        std::fs::write(&tool, "").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.path = vec![dir.clone()];
+        shell.variables.insert(String::from("nohash"), root.path().display().to_string());
+        assert!(shell.is_unhashed(&dir));
+        assert_eq!(shell.hash_lookup("tool"), Some(tool));
+        assert!(shell.hash.table.is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use libc::{O_RDONLY, S_IRUSR, S_IWUSR};
+
+use native::error::*;
+use native::file_stat::get_file_size;
+use native::{close_file, open_file, read_at, read_file, write_file_atomically, write_to_file};
+
+#[cfg(feature = "crypt")]
+use super::crypt::HistoryKey;
+use super::lexer::{raw_words, split_words};
+use super::Shell;
+
+/// Default number of commands kept in memory if `history` is not set
+const DEFAULT_HISTORY_SIZE: usize = 100;
+/// Size of chunks read from the end of the history file
+const CHUNK_SIZE: usize = 4096;
+/// Starts every encrypted history file, the number is the version of the format
+pub const CRYPT_MAGIC: &[u8] = b"rsh-crypt-1\n";
+
+/// Commands entered during the session and loaded from the history file
+#[derive(Debug, Default)]
+pub struct History {
+    pub entries: Vec<String>,
+    /// Words of every entry with their quotes for word designators like `!$`, see `words`
+    words: Vec<Vec<String>>,
+    /// Number of leading entries which are already in the history file
+    saved: usize,
+    /// Inverted index from a command to the arguments used with it, the latest first
+    arguments: HashMap<String, Vec<String>>,
+    /// Old and new text of the last `:s` modifier which `:&` and `^old^new` repeat
+    pub last_substitution: Option<(String, String)>,
+    /// Keys of the encrypted history file, known after the passphrase has been entered
+    #[cfg(feature = "crypt")]
+    pub key: Option<HistoryKey>,
+}
+
+impl History {
+    /// Adds the command and forgets the oldest ones so that at most `limit` entries are kept
+    pub fn push(&mut self, line: &str, limit: usize) {
+        self.entries.push(String::from(line));
+        self.words.push(raw_words(line));
+        self.index(line);
+        if self.entries.len() > limit {
+            let extra = self.entries.len() - limit;
+            self.entries.drain(..extra);
+            self.words.drain(..extra);
+            self.saved = self.saved.saturating_sub(extra);
+        }
+    }
+
+    /// Words of the entry split once when it was added, quotes are kept
+    pub fn words(&self, index: usize) -> &[String] {
+        &self.words[index]
+    }
+
+    /// Arguments previously used with the command, the most recent one first
+    pub fn arguments(&self, command: &str) -> &[String] {
+        self.arguments.get(command).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds the arguments of the command line to the index moving repeated ones to the front
+    fn index(&mut self, line: &str) {
+        let words = match split_words(line) {
+            Ok(words) => words,
+            Err(_) => return,
+        };
+        let mut words = words.into_iter();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return,
+        };
+        let known = self.arguments.entry(command).or_default();
+        for word in words {
+            known.retain(|argument| *argument != word);
+            known.insert(0, word);
+        }
+    }
+}
+
+impl Shell {
+    /// Remembers the interactive command, blank lines are ignored and nothing is remembered
+    /// while the shell is incognito
+    pub fn add_history(&mut self, line: &str) {
+        if !line.trim().is_empty() && !self.incognito {
+            let limit = self.history_size();
+            self.history.push(line, limit);
+        }
+    }
+
+    /// Loads the last `savehist` commands from the history file reading it from the end
+    pub fn load_history(&mut self) -> Result<()> {
+        let count = match self.save_history_size() {
+            Some(count) => count,
+            None => return Ok(()),
+        };
+        let path = self.history_file();
+        let text = self.read_history_file(&path, count)?;
+        self.history.entries = text.lines().map(String::from).collect();
+        self.history.words = text.lines().map(raw_words).collect();
+        self.history.saved = self.history.entries.len();
+        for line in text.lines() {
+            self.history.index(line);
+        }
+        Ok(())
+    }
+
+    /// Merges the commands of this session with the history file if `savehist` is set.
+    /// The file is rewritten atomically and keeps only the last `savehist` commands.
+    pub fn save_history(&mut self) -> Result<()> {
+        let count = match self.save_history_size() {
+            Some(count) => count,
+            None => return Ok(()),
+        };
+        if self.history.saved == self.history.entries.len() {
+            return Ok(());
+        }
+        let path = self.history_file();
+        let mut text = self.read_history_file(&path, count)?;
+        for entry in &self.history.entries[self.history.saved..] {
+            text.push_str(entry);
+            text.push('\n');
+        }
+        self.write_history_file(&path, &last_lines(&text, count))?;
+        self.history.saved = self.history.entries.len();
+        Ok(())
+    }
+
+    /// Implements `history`: prints the commands, the last N ones if a number is given.
+    /// `history --compact` removes duplicates from the history file and truncates it.
+    pub fn history_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        match arguments.first() {
+            Some(&"--compact") => self.compact_history(),
+            Some(count) => {
+                let count = count.parse().map_err(|_| Error::SyntaxError)?;
+                self.print_history(count)
+            }
+            None => self.print_history(self.history.entries.len()),
+        }
+    }
+
+    /// Implements `incognito on|off`: pauses history, statistics and `sessionlog`
+    /// till it is turned off, the prompt shows it meanwhile. Prints the state without arguments.
+    pub fn incognito_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        match arguments {
+            ["on"] => self.incognito = true,
+            ["off"] => self.incognito = false,
+            [] => {
+                write_to_file(1, if self.incognito { "on\n" } else { "off\n" })?;
+            }
+            _ => return Err(Error::SyntaxError),
+        }
+        Ok(())
+    }
+
+    /// Prints the last `count` commands with their numbers. Entries are whole lines as they
+    /// were typed with their quotes, so unlike the values of `set` they aren't quoted again.
+    fn print_history(&self, count: usize) -> Result<()> {
+        let entries = &self.history.entries;
+        let first = entries.len() - min(count, entries.len());
+        let mut text = String::new();
+        for (index, entry) in entries.iter().enumerate().skip(first) {
+            text.push_str(&format!("{:>6}  {}\n", index + 1, entry));
+        }
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+
+    /// Saves the session and deduplicates the file keeping the latest occurrences
+    fn compact_history(&mut self) -> Result<()> {
+        self.save_history()?;
+        let path = self.history_file();
+        let keep = self.save_history_size().unwrap_or_else(|| self.history_size());
+        let text = if self.history_encrypted(&path) {
+            self.read_encrypted_history(&path)?
+        } else {
+            let fd = open_file(&path, O_RDONLY, None)?;
+            let text = read_file(fd);
+            close_file(fd)?;
+            text?
+        };
+        let mut compacted = String::new();
+        for line in compact(text.lines(), keep) {
+            compacted.push_str(line);
+            compacted.push('\n');
+        }
+        self.write_history_file(&path, &compacted)
+    }
+
+    /// Reads the last `count` commands of the history file, a missing file is empty
+    fn read_history_file(&mut self, path: &Path, count: usize) -> Result<String> {
+        if self.history_encrypted(path) {
+            Ok(last_lines(&self.read_encrypted_history(path)?, count))
+        } else if path.exists() {
+            read_tail(path, count)
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    /// Replaces the history file with the text, encrypted if `histcrypt` is set
+    fn write_history_file(&mut self, path: &Path, text: &str) -> Result<()> {
+        if self.history_encrypted(path) {
+            self.write_encrypted_history(path, text)
+        } else {
+            write_file_atomically(path, text.as_bytes(), S_IRUSR | S_IWUSR)
+        }
+    }
+
+    /// The history file is encrypted with a passphrase if `histcrypt` is set.
+    /// A file which is already encrypted stays so, it's never read as plain text.
+    fn history_encrypted(&self, path: &Path) -> bool {
+        self.variables.contains_key("histcrypt") || starts_with_magic(path)
+    }
+
+    /// Path of the history file, `histfile` variable or ~/.history
+    fn history_file(&self) -> PathBuf {
+        match self.variables.get("histfile") {
+            Some(path) => PathBuf::from(path),
+            None => self.home.join(".history"),
+        }
+    }
+
+    /// Number of commands kept in memory, changed with `history` variable
+    fn history_size(&self) -> usize {
+        self.variables
+            .get("history")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_SIZE)
+    }
+
+    /// Number of commands kept in the history file, history is saved only if `savehist` is set
+    fn save_history_size(&self) -> Option<usize> {
+        self.variables
+            .get("savehist")
+            .map(|value| value.parse().unwrap_or_else(|_| self.history_size()))
+    }
+}
+
+/// Without the `crypt` feature `histcrypt` is refused, so history isn't saved in plain text
+#[cfg(not(feature = "crypt"))]
+impl Shell {
+    fn read_encrypted_history(&mut self, _: &Path) -> Result<String> {
+        Err(Error::NotBuiltIn("crypt"))
+    }
+
+    fn write_encrypted_history(&mut self, _: &Path, _: &str) -> Result<()> {
+        Err(Error::NotBuiltIn("crypt"))
+    }
+}
+
+/// Checks whether the file begins like an encrypted history file
+fn starts_with_magic(path: &Path) -> bool {
+    let fd = match open_file(path, O_RDONLY, None) {
+        Ok(fd) => fd,
+        Err(_) => return false,
+    };
+    let head = read_at(fd, 0, CRYPT_MAGIC.len());
+    close_file(fd).ok();
+    head.is_ok_and(|head| head == CRYPT_MAGIC)
+}
+
+/// Reads the last `count` lines of the file in chunks starting from its end
+fn read_tail(path: &Path, count: usize) -> Result<String> {
+    let size = get_file_size(path)?;
+    let fd = open_file(path, O_RDONLY, None)?;
+    let mut offset = size;
+    let mut data: Vec<u8> = Vec::new();
+    let mut newlines = 0;
+    // one more line break is needed to be sure that the first line is complete
+    while offset > 0 && newlines <= count {
+        let len = min(CHUNK_SIZE as u64, offset) as usize;
+        offset -= len as u64;
+        let mut chunk = match read_at(fd, offset, len) {
+            Ok(chunk) => chunk,
+            Err(reason) => {
+                close_file(fd).ok();
+                return Err(reason);
+            }
+        };
+        newlines += chunk.iter().filter(|c| **c == b'\n').count();
+        chunk.extend_from_slice(&data);
+        data = chunk;
+    }
+    close_file(fd)?;
+    let text = String::from_utf8_lossy(&data);
+    Ok(last_lines(&text, count))
+}
+
+/// Takes the last `count` lines of the text
+fn last_lines(text: &str, count: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let first = lines.len() - min(count, lines.len());
+    let mut result = lines[first..].join("\n");
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result
+}
+
+/// Removes repeated commands keeping the latest occurrence and at most `keep` lines
+fn compact<'a, I>(lines: I, keep: usize) -> Vec<&'a str>
+where
+    I: DoubleEndedIterator<Item = &'a str>,
+{
+    let mut seen = HashSet::new();
+    let mut result: Vec<&str> = lines
+        .rev()
+        .filter(|line| !line.trim().is_empty() && seen.insert(*line))
+        .take(keep)
+        .collect();
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_limits_entries() {
+        let mut history = History::default();
+        for line in &["a", "b", "c"] {
+            history.push(line, 2);
+        }
+        assert_eq!(history.entries, vec!["b", "c"]);
+        assert_eq!(history.words(1), &["c"]);
+    }
+
+    #[test]
+    fn arguments_latest_first() {
+        let mut history = History::default();
+        for line in &["ssh alpha", "ssh beta", "ls -l", "ssh alpha", "ssh 'un closed"] {
+            history.push(line, 10);
+        }
+        assert_eq!(history.arguments("ssh"), &["alpha", "beta"]);
+        assert_eq!(history.arguments("ls"), &["-l"]);
+        assert!(history.arguments("cd").is_empty());
+    }
+
+    #[test]
+    fn last_lines_of_text() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), "b\nc\n");
+        assert_eq!(last_lines("a\nb\n", 5), "a\nb\n");
+        assert_eq!(last_lines("", 5), "");
+    }
+
+    #[test]
+    fn compact_keeps_latest() {
+        let lines = vec!["ls", "cd /", "ls", "", "pwd", "cd /"];
+        assert_eq!(compact(lines.into_iter(), 10), vec!["ls", "pwd", "cd /"]);
+        let lines = vec!["a", "b", "c"];
+        assert_eq!(compact(lines.into_iter(), 2), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn incognito_pauses_history() {
+        let mut shell = Shell::new().unwrap();
+        shell.add_history("ls");
+        shell.incognito_builtin(&["on"]).unwrap();
+        shell.add_history("cat secret");
+        assert!(shell.prompt_text().starts_with("(incognito) "));
+        shell.incognito_builtin(&["off"]).unwrap();
+        shell.add_history("pwd");
+        assert_eq!(shell.history.entries, vec!["ls", "pwd"]);
+        assert!(shell.incognito_builtin(&["maybe"]).is_err());
+        assert!(matches!(shell.history_builtin(&["many"]), Err(Error::SyntaxError)));
+    }
+}
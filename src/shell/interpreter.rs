@@ -0,0 +1,163 @@
+use native::error::*;
+
+use super::ast::{Command, Connector, List, Pipeline};
+use super::debug::Debug;
+use super::pipeline::{pipeline_text, Stage};
+use super::{Flow, Shell};
+
+impl Shell {
+    /// Runs the commands of the list one after another, a command after `&&` or `||`
+    /// runs only if the status of the previous one allows it. Stops if a command exits.
+    pub fn run_list(&mut self, list: &List) -> Result<Flow> {
+        for (connector, command) in &list.items {
+            let run = match connector {
+                Connector::Always => true,
+                Connector::Success => self.status == 0,
+                Connector::Failure => self.status != 0,
+            };
+            if run && self.run_command(command)? == Flow::Exit {
+                return Ok(Flow::Exit);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn run_command(&mut self, command: &Command) -> Result<Flow> {
+        match command {
+            Command::Pipeline(pipeline) => self.run_commands(pipeline),
+            Command::Conditional(conditional) => {
+                if self.arithmetic(&conditional.condition)? == "0" {
+                    return Ok(Flow::Continue);
+                }
+                self.run_command(&conditional.body)
+            }
+            Command::Loop(repeat) => {
+                let count = self.expand_words(std::slice::from_ref(&repeat.count))?.remove(0);
+                let count: usize = count.parse().map_err(|_| Error::SyntaxError.about(&count))?;
+                for _ in 0..count {
+                    if self.run_command(&repeat.body)? == Flow::Exit {
+                        return Ok(Flow::Exit);
+                    }
+                }
+                Ok(Flow::Continue)
+            }
+        }
+    }
+
+    /// Expands the commands of the pipeline just before it runs, so the previous commands
+    /// of the line may have changed variables. A command alone runs in the shell if it's
+    /// a builtin, others and groups are run by `run_pipeline`.
+    fn run_commands(&mut self, pipeline: &Pipeline) -> Result<Flow> {
+        if let Some(command) = pipeline.assignments_only() {
+            let assignments = self.expand_words(&command.assignments)?;
+            // the redirections only create or check the files like for a command
+            let redirections = self.expand_redirects(command)?;
+            self.with_redirections(&redirections, |shell| {
+                assignments.iter().try_for_each(|assignment| shell.assign_variable(assignment))
+            })?;
+            self.status = 0;
+            return Ok(Flow::Continue);
+        }
+        let mut stages = Vec::with_capacity(pipeline.commands.len());
+        for command in &pipeline.commands {
+            stages.push(self.expand_command(command)?);
+        }
+        self.debug(Debug::Parser, || format!("expanded {:?}", stages));
+        self.trace_command(&pipeline_text(&stages))?;
+        if pipeline.background || stages.len() > 1 || stages[0].group.is_some() {
+            return self.run_pipeline(&stages, pipeline.background);
+        }
+        self.run_simple(stages.remove(0))
+    }
+
+    /// Runs a builtin in the shell, its redirections are undone after it,
+    /// others are programs
+    fn run_simple(&mut self, stage: Stage) -> Result<Flow> {
+        if self.builtins.contains_key(stage.words[0].as_str()) {
+            let arguments: Vec<&str> = stage.words[1..].iter().map(String::as_str).collect();
+            return self.with_redirections(&stage.redirections, |shell| {
+                shell.run_builtin(&stage.words[0], &arguments)
+            });
+        }
+        // external commands replace it when they finish
+        self.status = 0;
+        if let Some(status) = self.try_spawn(&stage) {
+            self.status = status?;
+            return Ok(Flow::Continue);
+        }
+        self.run_pipeline(&[stage], false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+    use super::super::ast::parse_line;
+
+    fn run(shell: &mut Shell, line: &str) -> Result<Flow> {
+        shell.run_list(&parse_line(line)?)
+    }
+
+    #[test]
+    fn lists_run_in_order() {
+        let mut shell = Shell::new().unwrap();
+        run(&mut shell, "set x = 1 ; set y = $x && [ a = b ] && set z = 1 || set w = $y").unwrap();
+        assert_eq!(shell.variables["y"], "1");
+        assert!(!shell.variables.contains_key("z"));
+        assert_eq!(shell.variables["w"], "1");
+        run(&mut shell, "if ( $x == 1 ) repeat 3 @ n += 1").unwrap();
+        run(&mut shell, "if ( $x > 1 ) set n = 0").unwrap();
+        assert_eq!(shell.variables["n"], "3");
+        assert_eq!(run(&mut shell, "exit 2 ; set n = 0").unwrap(), Flow::Exit);
+        assert_eq!(shell.status, 2);
+        assert_eq!(shell.variables["n"], "3");
+        assert!(run(&mut shell, "repeat many true").is_err());
+        run(&mut shell, "dir=$x/bin ; greeting='a b' ; LANG=C true").unwrap();
+        assert_eq!(shell.variables["dir"], "1/bin");
+        assert_eq!(shell.variables["greeting"], "a b");
+        assert!(!shell.variables.contains_key("LANG"));
+        assert!(run(&mut shell, "loginsh=1").is_err());
+        let dir = TestDir::new("assign");
+        let file = dir.join("file");
+        let line = format!("FOO=1 > {} ; BAR=2 < {}", file.display(), file.display());
+        assert_eq!(run(&mut shell, &line).unwrap(), Flow::Continue);
+        assert_eq!((&shell.variables["FOO"][..], &shell.variables["BAR"][..]), ("1", "2"));
+        assert!(file.exists());
+        assert!(run(&mut shell, &format!("BAZ=3 < {}", dir.join("none").display())).is_err());
+        assert!(!shell.variables.contains_key("BAZ"));
+        run(&mut shell, "set m = $((2**3))$((1 + 2))").unwrap();
+        assert_eq!(shell.variables["m"], "83");
+    }
+
+    #[test]
+    fn subshells_keep_the_shell() {
+        let mut shell = Shell::new().unwrap();
+        let cwd = shell.cwd.clone();
+        run(&mut shell, "( cd / ; set x = 1 ; exit 3 )").unwrap();
+        assert_eq!(shell.status, 3);
+        assert_eq!((&shell.cwd, shell.variables.contains_key("x")), (&cwd, false));
+        run(&mut shell, "set y = 1 | true && ( true )").unwrap();
+        assert_eq!((shell.status, shell.variables.contains_key("y")), (0, false));
+        let dir = TestDir::new("subshell");
+        let file = dir.join("out");
+        run(&mut shell, &format!("(cd / ; pwd) > {} | true", file.display())).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "/\n");
+    }
+
+    #[test]
+    fn commands_are_substituted() {
+        let mut shell = Shell::new().unwrap();
+        let line = "set x = (`printf 'a b\\nc'`) ; set y = \"`printf 'a  b\\nc\\n'`\"";
+        run(&mut shell, line).unwrap();
+        let x = shell.variables.value("x").unwrap();
+        assert!(x.is_list());
+        assert_eq!(x.words(), ["a", "b", "c"]);
+        assert_eq!(shell.variables["y"], "a  b c");
+        run(&mut shell, "set z = '`pwd`'x`true` ; set n = `@ n = 2 ; echo $n`$n").unwrap();
+        assert_eq!(shell.variables["z"], "`pwd`x");
+        assert_eq!(shell.variables["n"], "2");
+        assert!(run(&mut shell, "set w = `ls |`").is_err());
+    }
+}
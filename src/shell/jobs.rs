@@ -0,0 +1,593 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use libc::{c_int, pid_t, ESRCH, SIGCONT, SIGTTIN};
+
+use native::error::*;
+use native::procfs::{core_location, process_cwd, process_tty, CoreLocation};
+use native::signal::{children_changed, death_description, send_signal, stop_self};
+use native::term::{get_terminal_group, set_terminal_group, TerminalModes};
+use native::{get_process_group, get_process_id, poll_children, set_process_group, wait_child,
+             write_to_file, ChildStatus, ExitCode};
+
+use super::debug::Debug;
+use super::dirs::abbreviate_home;
+use super::pipeline::is_pipe;
+use super::Shell;
+
+/// State of a background job as reported by `jobs` and notifications
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped(c_int),
+    /// All processes have exited, the code is the one of the last process
+    Done(ExitCode),
+    /// The last process was killed by the signal, the flag tells whether a core was dumped
+    Killed(c_int, bool),
+}
+
+impl JobState {
+    fn describe(self) -> String {
+        match self {
+            JobState::Running => msg!("Running"),
+            JobState::Stopped(_) => msg!("Stopped"),
+            JobState::Done(0) => msg!("Done"),
+            JobState::Done(code) => msg!("Exit {}", code),
+            JobState::Killed(signal, core_dumped) => death_description(signal, core_dumped),
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        match self {
+            JobState::Done(_) | JobState::Killed(..) => true,
+            JobState::Running | JobState::Stopped(_) => false,
+        }
+    }
+}
+
+/// Processes started with `&`, a pipeline makes one job
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    /// Process group of the job, it is the pid of its first process
+    pub group: pid_t,
+    /// Processes which haven't exited yet
+    pub processes: Vec<pid_t>,
+    pub command: String,
+    pub state: JobState,
+    /// Report state changes as soon as they happen, set with `notify %n`
+    pub notify: bool,
+    /// Socket of the holder of the pseudo-terminal of a job started with `bg --detach`
+    pub socket: Option<PathBuf>,
+    /// The last process of the pipeline, its exit code is the one of the job
+    last: pid_t,
+    /// The state of the last process once it has exited
+    result: Option<JobState>,
+    /// The state has changed since it was reported
+    changed: bool,
+    started: Instant,
+}
+
+impl Job {
+    /// Formats the job like `[1]  Done    make all`
+    fn describe(&self) -> String {
+        format!("[{}]  {:<7} {}\n", self.id, self.state.describe(), self.command)
+    }
+
+    /// Sends SIGCONT to every process of the job, the ones which have just exited are skipped
+    fn resume(&mut self) -> Result<()> {
+        for pid in &self.processes {
+            match send_signal(*pid, SIGCONT) {
+                Err(ref reason) if reason.is_errno(ESRCH) => {}
+                result => result?,
+            }
+        }
+        self.state = JobState::Running;
+        Ok(())
+    }
+}
+
+/// Table of background jobs of the shell
+#[derive(Debug, Default)]
+pub struct Jobs {
+    list: Vec<Job>,
+    /// The user has been warned about unfinished jobs on the previous attempt to exit
+    pub exit_warned: bool,
+    /// Commands get their own process groups and the terminal, it is set for interactive shells
+    pub control: bool,
+    /// Process group the shell was started in, it gets the terminal back when the shell exits
+    original_group: pid_t,
+    /// Terminal settings of the shell, they are restored if a job leaves the terminal broken
+    modes: Option<TerminalModes>,
+}
+
+impl Jobs {
+    /// Adds the job and returns its number, the smallest one above all used numbers
+    pub fn add(&mut self, group: pid_t, processes: Vec<pid_t>, command: String) -> usize {
+        let id = self.next_id();
+        let last = processes.last().cloned().unwrap_or(group);
+        self.list.push(Job {
+            id,
+            group,
+            processes,
+            command,
+            state: JobState::Running,
+            notify: false,
+            socket: None,
+            last,
+            result: None,
+            changed: false,
+            started: Instant::now(),
+        });
+        id
+    }
+
+    /// Applies the state change of the process to its job, returns the job if it has finished
+    pub fn update(&mut self, pid: pid_t, status: ChildStatus) -> Option<&Job> {
+        let job = self.list.iter_mut().find(|job| job.processes.contains(&pid))?;
+        match status {
+            ChildStatus::Stopped(signal) => {
+                job.state = JobState::Stopped(signal);
+                job.changed = true;
+            }
+            ChildStatus::Continued => job.state = JobState::Running,
+            ChildStatus::Exited(_) | ChildStatus::Signaled(..) => {
+                job.processes.retain(|process| *process != pid);
+                if pid == job.last {
+                    job.result = Some(match status {
+                        ChildStatus::Signaled(signal, core_dumped) => {
+                            JobState::Killed(signal, core_dumped)
+                        }
+                        status => JobState::Done(status.exit_code()),
+                    });
+                }
+                if job.processes.is_empty() {
+                    job.state = job.result.unwrap_or(JobState::Done(0));
+                    job.changed = true;
+                    return Some(job);
+                }
+            }
+        }
+        None
+    }
+
+    /// Takes the descriptions of changed jobs and forgets the finished ones.
+    /// If `all` is false, only jobs with `notify` set are reported.
+    pub fn take_changed(&mut self, all: bool) -> String {
+        let mut text = String::new();
+        for job in self.list.iter_mut() {
+            if job.changed && (all || job.notify) {
+                text.push_str(&job.describe());
+                job.changed = false;
+            }
+        }
+        self.list.retain(|job| job.changed || !job.state.is_finished());
+        text
+    }
+
+    /// Checks whether some jobs are still running or stopped
+    pub fn has_unfinished(&self) -> bool {
+        self.list.iter().any(|job| !job.state.is_finished())
+    }
+
+    /// Number which the next job gets
+    pub fn next_id(&self) -> usize {
+        self.list.iter().map(|job| job.id).max().unwrap_or(0) + 1
+    }
+
+    /// Takes the job out of the table
+    fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.list.iter().position(|job| job.id == id)?;
+        Some(self.list.remove(index))
+    }
+
+    /// Finds the job by `%n` or `n`, `%%`, `%+` and no specification mean the latest job
+    pub fn find(&mut self, spec: Option<&str>) -> Option<&mut Job> {
+        match spec {
+            None | Some("%%") | Some("%+") => self.list.last_mut(),
+            Some(spec) => {
+                let id = spec.trim_start_matches('%').parse().ok()?;
+                self.get(id)
+            }
+        }
+    }
+
+    pub fn get(&mut self, id: usize) -> Option<&mut Job> {
+        self.list.iter_mut().find(|job| job.id == id)
+    }
+}
+
+impl Shell {
+    /// Enables job control: the shell becomes the leader of its own process group
+    /// and the foreground group of the terminal.
+    /// If started in the background, it stops with SIGTTIN until it is moved to the foreground.
+    pub fn take_terminal(&mut self) -> Result<()> {
+        loop {
+            let group = get_process_group();
+            if get_terminal_group(0)? == group {
+                break;
+            }
+            send_signal(-group, SIGTTIN)?;
+        }
+        self.jobs.original_group = get_process_group();
+        let pid = get_process_id();
+        // a session leader already leads its group and can't move
+        if self.jobs.original_group != pid {
+            set_process_group(0, pid)?;
+        }
+        set_terminal_group(0, pid)?;
+        self.debug(Debug::Jobs, || format!("took the terminal for group {}", pid));
+        self.jobs.control = true;
+        self.jobs.modes = Some(TerminalModes::get(0)?);
+        Ok(())
+    }
+
+    /// Returns the shell to its original process group and gives the terminal back to it
+    pub fn release_terminal(&mut self) -> Result<()> {
+        if !self.jobs.control {
+            return Ok(());
+        }
+        self.jobs.control = false;
+        let group = self.jobs.original_group;
+        // the original group may be gone already, then the terminal stays with the shell
+        if group == get_process_group() || set_process_group(0, group).is_ok() {
+            set_terminal_group(0, group)?;
+        }
+        Ok(())
+    }
+
+    /// Remembers the started background job, prints its number and pid and returns the number
+    pub fn start_job(&mut self, processes: Vec<pid_t>, command: String) -> Result<usize> {
+        let group = processes[0];
+        let last = processes[processes.len() - 1];
+        let id = self.jobs.add(group, processes, command);
+        write_to_file(1, &format!("[{}] {}\n", id, last))?;
+        Ok(id)
+    }
+
+    /// Collects state changes of children without blocking
+    pub fn update_jobs(&mut self) -> Result<()> {
+        while let Some((pid, status)) = poll_children()? {
+            self.debug(Debug::Jobs, || format!("child {} changed to {:?}", pid, status));
+            let finished = self
+                .jobs
+                .update(pid, status)
+                .map(|job| (job.started.elapsed(), job.describe()));
+            if let Some((elapsed, description)) = finished {
+                self.alert_long_job(elapsed, &description)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits a desktop notification if the job has run at least `longjob` seconds and
+    /// the terminal is unfocused. `longjobstyle` is `osc9` for the OSC 9 sequence,
+    /// which is the default, or `bel` for the bell.
+    fn alert_long_job(&self, elapsed: Duration, description: &str) -> Result<()> {
+        let threshold = match self.variables.get("longjob").and_then(|value| value.parse().ok()) {
+            Some(seconds) => Duration::from_secs(seconds),
+            None => return Ok(()),
+        };
+        if elapsed < threshold || !self.editor.unfocused {
+            return Ok(());
+        }
+        let text = match self.variables.get("longjobstyle").map(String::as_str) {
+            Some("bel") => String::from("\x07"),
+            _ => format!("\x1b]9;{}\x07", description.trim()),
+        };
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+
+    /// Prints changes of jobs before the prompt.
+    /// If `all` is false, the read was interrupted by a signal and only jobs which
+    /// should be reported immediately (`notify` is set) are printed.
+    /// Returns whether something was printed.
+    pub fn report_jobs(&mut self, all: bool) -> Result<bool> {
+        if !children_changed() && !all {
+            return Ok(false);
+        }
+        self.update_jobs()?;
+        let text = self.jobs.take_changed(all || self.variables.contains_key("notify"));
+        if text.is_empty() {
+            return Ok(false);
+        }
+        let text = if all { text } else { format!("\n{}", text) };
+        write_to_file(1, &text)?;
+        Ok(true)
+    }
+
+    /// Restores the saved terminal settings if something has changed them.
+    /// It is called before the prompt and after a foreground job is killed or stopped.
+    /// Settings without echo or line input, e.g. kept after a program which exited
+    /// in raw mode, are repaired, so the session stays usable.
+    pub fn reset_tty(&mut self) -> Result<()> {
+        let mut saved = match self.jobs.modes {
+            Some(modes) => modes,
+            None => return Ok(()),
+        };
+        if saved.make_sane() {
+            self.debug(Debug::Jobs, || format!("repaired terminal settings {:?}", saved));
+            self.jobs.modes = Some(saved);
+        }
+        let current = TerminalModes::get(0)?;
+        if current != saved {
+            self.debug(Debug::Jobs, || format!("restoring {:?} from {:?}", saved, current));
+            saved.set(0)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the processes of a foreground job in order, the status is the one of the last.
+    /// If one of them is stopped with Ctrl-Z, the remaining ones become a stopped job.
+    /// With job control the group owns the terminal meanwhile. Terminal settings left by
+    /// a job which exits are kept, like the ones of `stty`, otherwise the saved ones are restored.
+    pub fn wait_foreground(
+        &mut self,
+        group: pid_t,
+        processes: Vec<pid_t>,
+        command: String,
+    ) -> Result<ChildStatus> {
+        if self.jobs.control {
+            set_terminal_group(0, group)?;
+        }
+        let result = self.wait_processes(group, processes, command);
+        if self.jobs.control {
+            set_terminal_group(0, get_process_group())?;
+            match result {
+                Ok(ChildStatus::Exited(_)) => self.jobs.modes = Some(TerminalModes::get(0)?),
+                _ => self.reset_tty()?,
+            }
+        }
+        result
+    }
+
+    /// Returns the status of the last process or the one which has stopped
+    fn wait_processes(
+        &mut self,
+        group: pid_t,
+        mut remaining: Vec<pid_t>,
+        command: String,
+    ) -> Result<ChildStatus> {
+        let mut last = ChildStatus::Exited(0);
+        // processes are started in the order of the commands, a resumed job may have lost some
+        let mut names = command_names(&command);
+        if names.len() != remaining.len() {
+            names.clear();
+        }
+        let mut names = names.into_iter();
+        self.core_dumped = false;
+        while let Some(pid) = remaining.first().cloned() {
+            let status = wait_child(pid)?;
+            self.debug(Debug::Jobs, || format!("child {} changed to {:?}", pid, status));
+            match status {
+                ChildStatus::Stopped(signal) => {
+                    self.status = status.exit_code();
+                    let id = self.jobs.add(group, remaining, command);
+                    let job = self.jobs.get(id).ok_or(Error::NoSuchJob)?;
+                    job.state = JobState::Stopped(signal);
+                    write_to_file(1, &format!("\n{}", job.describe()))?;
+                    return Ok(status);
+                }
+                ChildStatus::Continued => {}
+                status => {
+                    self.status = status.exit_code();
+                    self.report_death(pid, names.next(), status)?;
+                    remaining.remove(0);
+                    last = status;
+                }
+            }
+        }
+        Ok(last)
+    }
+
+    /// Describes the death of a foreground process by a signal. With `exitinfo` set the signal
+    /// is named, `set exitinfo = verbose` reports deaths by SIGINT and SIGPIPE too.
+    pub fn death_message(&self, status: ChildStatus) -> Option<String> {
+        match self.variables.get("exitinfo") {
+            None => status.death_message(),
+            Some(_) if matches!(status, ChildStatus::Exited(_)) => None,
+            Some(value) => status.explanation(value == "verbose"),
+        }
+    }
+
+    /// Prints how the foreground process has died if a signal has killed it and where its core
+    /// has gone if it has dumped one, `name` is the program it was running
+    pub fn report_death(
+        &mut self,
+        pid: pid_t,
+        name: Option<&str>,
+        status: ChildStatus,
+    ) -> Result<()> {
+        if let Some(message) = self.death_message(status) {
+            write_to_file(2, &format!("{}\n", message))?;
+        }
+        if let ChildStatus::Signaled(_, true) = status {
+            self.core_dumped = true;
+            let name = name.map(|name| name.rsplit('/').next().unwrap_or(name));
+            let hint = match core_location(pid, name) {
+                Some(CoreLocation::File(path)) => {
+                    msg!("The core file is {}", self.cwd.join(path).display())
+                }
+                Some(CoreLocation::Program(program)) => msg!("The core is passed to {}", program),
+                None => return Ok(()),
+            };
+            write_to_file(2, &format!("{}\n", hint))?;
+        }
+        Ok(())
+    }
+
+    /// Tells what the exit code of the foreground command means if `exitinfo` is set.
+    /// Every failure is reported if it's `verbose`, otherwise only well-known codes are.
+    pub fn explain_exit(&self, status: ChildStatus) -> Result<()> {
+        let verbose = match self.variables.get("exitinfo") {
+            Some(value) => value == "verbose",
+            None => return Ok(()),
+        };
+        if let ChildStatus::Exited(_) = status {
+            if let Some(message) = status.explanation(verbose) {
+                write_to_file(2, &format!("{}\n", message))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Implements `fg [%n]`: continues the job and waits for it, a detached job is reattached
+    pub fn fg_builtin(&mut self, spec: Option<&str>) -> Result<()> {
+        self.update_jobs()?;
+        let job = self.jobs.find(spec).ok_or(Error::NoSuchJob)?;
+        if job.socket.is_some() {
+            return self.reattach_builtin(spec);
+        }
+        let id = job.id;
+        let mut job = self.jobs.remove(id).ok_or(Error::NoSuchJob)?;
+        write_to_file(1, &format!("{}\n", job.command))?;
+        if self.jobs.control {
+            // the job may read the terminal as soon as it continues
+            set_terminal_group(0, job.group)?;
+        }
+        job.resume()?;
+        let status = self.wait_foreground(job.group, job.processes, job.command)?;
+        self.explain_exit(status)
+    }
+
+    /// Implements `bg [%n]`: continues the stopped job in the background
+    pub fn bg_builtin(&mut self, spec: Option<&str>) -> Result<()> {
+        self.update_jobs()?;
+        let job = self.jobs.find(spec).ok_or(Error::NoSuchJob)?;
+        job.resume()?;
+        write_to_file(1, &format!("[{}]    {} &\n", job.id, job.command))?;
+        Ok(())
+    }
+
+    /// Implements `suspend`: stops the shell until its parent continues it.
+    /// Only an interactive shell owning the terminal is stopped, a script or a shell
+    /// in the background would stay stopped with nobody to continue it.
+    pub fn suspend_builtin(&self) -> Result<()> {
+        if self.is_login {
+            return Err(Error::SuspendLoginShell);
+        }
+        let owner = get_terminal_group(0).is_ok_and(|group| group == get_process_group());
+        if !self.jobs.control || !owner {
+            return Err(Error::SuspendWithoutTerminal);
+        }
+        stop_self()
+    }
+
+    /// Refuses the first attempt to leave the interactive shell while there are unfinished jobs,
+    /// an immediately repeated attempt succeeds. Returns whether the shell may exit.
+    pub fn may_exit(&mut self) -> Result<bool> {
+        self.update_jobs()?;
+        if self.jobs.exit_warned || !self.jobs.has_unfinished() {
+            return Ok(true);
+        }
+        self.jobs.exit_warned = true;
+        write_to_file(2, &format!("{}\n", msg!("There are stopped jobs.")))?;
+        Ok(false)
+    }
+
+    /// Implements `jobs [-v]`: lists all jobs and forgets the finished ones.
+    /// `-v` adds a line for every process with its pid, terminal and current directory.
+    pub fn jobs_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        let verbose = match arguments {
+            [] => false,
+            ["-v"] => true,
+            _ => return Err(Error::SyntaxError),
+        };
+        self.update_jobs()?;
+        let mut text = String::new();
+        for job in &mut self.jobs.list {
+            text.push_str(&job.describe());
+            if verbose {
+                for pid in &job.processes {
+                    text.push_str(&describe_process(*pid, &self.home));
+                }
+            }
+            job.changed = false;
+        }
+        self.jobs.take_changed(true);
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+
+    /// Implements `notify [%n]`: the job's state changes are reported immediately
+    pub fn notify_builtin(&mut self, spec: Option<&str>) -> Result<()> {
+        match self.jobs.find(spec) {
+            Some(job) => {
+                job.notify = true;
+                Ok(())
+            }
+            None => Err(Error::NoSuchJob),
+        }
+    }
+}
+
+/// Names the programs run by the pipeline like `ls` and `sort` for `ls -l | sort -r`
+fn command_names(command: &str) -> Vec<&str> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let mut names: Vec<&str> = words.first().copied().into_iter().collect();
+    for pair in words.windows(2) {
+        if is_pipe(pair[0]) {
+            names.push(pair[1]);
+        }
+    }
+    names
+}
+
+/// Formats the process of a job like `    4242  pts/3    ~/src`,
+/// `-` is shown for no terminal and `?` for what the system doesn't tell
+fn describe_process(pid: pid_t, home: &Path) -> String {
+    let tty = process_tty(pid).unwrap_or_else(|| String::from("-"));
+    let dir = process_cwd(pid).map_or_else(|| String::from("?"), |dir| abbreviate_home(&dir, home));
+    format!("    {:>6}  {:<8} {}\n", pid, tty, dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_finishes_with_last_process() {
+        let mut jobs = Jobs::default();
+        let id = jobs.add(10, vec![10, 11], String::from("false | true"));
+        assert_eq!(id, 1);
+        assert!(jobs.update(11, ChildStatus::Exited(3)).is_none());
+        assert_eq!(jobs.take_changed(true), "");
+        assert_eq!(jobs.update(10, ChildStatus::Exited(0)).unwrap().id, 1);
+        assert_eq!(jobs.take_changed(true), "[1]  Exit 3  false | true\n");
+        assert!(jobs.find(None).is_none());
+    }
+
+    #[test]
+    fn stopped_job_is_kept() {
+        let mut jobs = Jobs::default();
+        jobs.add(10, vec![10], String::from("vi"));
+        jobs.add(20, vec![20], String::from("make all"));
+        jobs.update(10, ChildStatus::Stopped(19));
+        jobs.update(20, ChildStatus::Exited(0));
+        assert_eq!(jobs.take_changed(false), "");
+        jobs.find(Some("%2")).unwrap().notify = true;
+        assert_eq!(jobs.take_changed(false), "[2]  Done    make all\n");
+        assert_eq!(jobs.take_changed(true), "[1]  Stopped vi\n");
+        assert_eq!(jobs.find(None).unwrap().id, 1);
+        assert!(jobs.has_unfinished());
+    }
+
+    #[test]
+    fn missing_jobs_are_errors() {
+        let mut shell = Shell::new().unwrap();
+        assert!(matches!(shell.fg_builtin(Some("%9")), Err(Error::NoSuchJob)));
+        assert!(matches!(shell.fg_builtin(None), Err(Error::NoSuchJob)));
+        assert!(matches!(shell.notify_builtin(Some("%9")), Err(Error::NoSuchJob)));
+        assert!(matches!(shell.jobs_builtin(&["-x"]), Err(Error::SyntaxError)));
+    }
+
+    #[test]
+    fn only_interactive_shell_is_suspended() {
+        let mut shell = Shell::new().unwrap();
+        shell.is_login = true;
+        assert!(matches!(shell.suspend_builtin(), Err(Error::SuspendLoginShell)));
+        shell.is_login = false;
+        assert!(matches!(shell.suspend_builtin(), Err(Error::SuspendWithoutTerminal)));
+    }
+}
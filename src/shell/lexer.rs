@@ -0,0 +1,500 @@
+use native::error::*;
+
+/// Problems found while splitting a line into words
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnmatchedQuote(char),
+}
+
+impl LexError {
+    /// Converts the problem to the error reported by the shell
+    pub fn to_error(&self) -> Error {
+        match *self {
+            LexError::UnmatchedQuote(quote) => Error::UnmatchedQuote(quote),
+        }
+    }
+}
+
+/// The result of splitting a line which is produced even for malformed input
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedLine {
+    pub words: Vec<String>,
+    pub errors: Vec<LexError>,
+}
+
+/// A word of the line as it is written and without quotes and backslashes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// The byte offset of the word in the line
+    pub start: usize,
+    pub raw: String,
+    pub text: String,
+}
+
+/// Splits the line into words removing quotes and backslashes.
+/// Never panics: problems are collected in `errors` and the words are still returned,
+/// an unmatched quote is treated as if it was closed at the end of the line.
+pub fn parse_line_lossless(line: &str) -> ParsedLine {
+    let mut errors = Vec::new();
+    let words = tokens(line, &mut errors).0.into_iter().map(|token| token.text).collect();
+    ParsedLine { words, errors }
+}
+
+/// Splits the line into words keeping quotes and backslashes, so they can be joined back
+pub fn raw_words(line: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    tokens(line, &mut errors).0.into_iter().map(|token| token.raw).collect()
+}
+
+/// Splits the line into tokens failing on the first problem
+pub fn tokenize(line: &str) -> Result<Vec<Token>> {
+    let mut errors = Vec::new();
+    let (tokens, _) = tokens(line, &mut errors);
+    match errors.first() {
+        Some(reason) => Err(reason.to_error()),
+        None => Ok(tokens),
+    }
+}
+
+/// Finds the `$` and `` ` `` characters of every word which are in single quotes or escaped
+/// with a backslash, they aren't substituted. Offsets are in bytes of the unquoted word.
+pub fn quoted_dollars(line: &str) -> Vec<Vec<usize>> {
+    let mut errors = Vec::new();
+    tokens(line, &mut errors)
+        .0
+        .iter()
+        .map(|token| word_quoted_dollars(&token.raw))
+        .collect()
+}
+
+/// Finds the quoted `$` of the word as it is written, see `quoted_dollars`
+pub fn word_quoted_dollars(word: &str) -> Vec<usize> {
+    find_dollars(word, '\'', true)
+}
+
+/// Finds the `$` and `` ` `` of the word which are in double quotes, a list variable or
+/// the output of a command substituted there stays one word. Offsets are in bytes of the
+/// unquoted word.
+pub fn word_double_quoted_dollars(word: &str) -> Vec<usize> {
+    find_dollars(word, '"', false)
+}
+
+/// Characters which start a substitution
+const SUBSTITUTIONS: &str = "$`";
+
+/// Finds the `$` and `` ` `` in the quotes and the escaped ones if asked to
+fn find_dollars(word: &str, quote: char, escaped_too: bool) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut length = 0;
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                for inner in chars.by_ref().take_while(|inner| *inner != c) {
+                    if SUBSTITUTIONS.contains(inner) && c == quote {
+                        offsets.push(length);
+                    }
+                    length += inner.len_utf8();
+                }
+            }
+            '`' => {
+                length += 1;
+                for inner in chars.by_ref() {
+                    length += inner.len_utf8();
+                    if inner == '`' {
+                        break;
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().unwrap_or('\\');
+                if SUBSTITUTIONS.contains(escaped) && escaped_too {
+                    offsets.push(length);
+                }
+                length += escaped.len_utf8();
+            }
+            c => length += c.len_utf8(),
+        }
+    }
+    offsets
+}
+
+/// Characters which make a filename pattern unless they are quoted
+const GLOB_SPECIAL: &str = "*?[]\\";
+
+/// Finds the words of the line which are filename patterns: they have an unquoted `*`, `?`
+/// or `[...]`. Quoted special characters of a pattern are escaped with a backslash.
+/// Words with a variable or a command substitution aren't patterns, values aren't globbed.
+pub fn glob_patterns(line: &str) -> Vec<Option<String>> {
+    let mut errors = Vec::new();
+    tokens(line, &mut errors)
+        .0
+        .iter()
+        .map(|token| glob_pattern(&token.raw))
+        .collect()
+}
+
+/// Gets the filename pattern of the word as it is written, see `glob_patterns`
+pub fn glob_pattern(word: &str) -> Option<String> {
+    if word.contains('$') || word.contains('`') {
+        return None;
+    }
+    let mut pattern = String::with_capacity(word.len());
+    let mut special = false;
+    let mut bracket = false;
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                for inner in chars.by_ref().take_while(|inner| *inner != c) {
+                    push_literal(&mut pattern, inner);
+                }
+            }
+            '\\' => push_literal(&mut pattern, chars.next().unwrap_or('\\')),
+            c => {
+                // a lone bracket is a plain character
+                special |= c == '*' || c == '?' || (c == ']' && bracket);
+                bracket |= c == '[';
+                pattern.push(c);
+            }
+        }
+    }
+    if special {
+        Some(pattern)
+    } else {
+        None
+    }
+}
+
+/// Escapes the special characters of the text, so a pattern matches it literally
+pub fn escape_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_literal(&mut pattern, c);
+    }
+    pattern
+}
+
+fn push_literal(pattern: &mut String, c: char) {
+    if GLOB_SPECIAL.contains(c) {
+        pattern.push('\\');
+    }
+    pattern.push(c);
+}
+
+/// Characters which separate words like whitespace does and make operators
+/// unless they are quoted
+const METACHARACTERS: &str = ";&|<>()";
+
+/// Finds the length of the operator at the beginning of the text: `>`, `>>`, `>&` and `>>&`
+/// are one operator, so are `&&`, `||` and `|&`
+fn operator_length(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    match bytes[0] {
+        b'>' => {
+            let append = bytes.get(1) == Some(&b'>');
+            let both = bytes.get(1 + append as usize) == Some(&b'&');
+            1 + append as usize + both as usize
+        }
+        b'&' | b'|' if bytes.get(1) == Some(&bytes[0]) => 2,
+        b'|' if bytes.get(1) == Some(&b'&') => 2,
+        _ => 1,
+    }
+}
+
+/// Finds the length of `$((expression))` or `${name}` at the beginning of the text, they are
+/// a part of the word even with spaces or operators inside. A plain `$` is one byte long,
+/// an unfinished substitution goes on till the end of the line.
+fn substitution_length(text: &str) -> usize {
+    if text.starts_with("${") {
+        return text.find('}').map_or(text.len(), |end| end + 1);
+    }
+    if !text.starts_with("$((") {
+        return 1;
+    }
+    let mut depth = 0;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// Splits the line on whitespace which is not quoted or escaped keeping the quotes in words.
+/// Unquoted operators like `;`, `&&` or `>>` are separate words even without spaces around,
+/// a descriptor number like in `2>` is a part of the redirection.
+/// A `#` which begins a word outside quotes starts a comment till the end of the line.
+/// Returns the tokens and the length of the line before the comment.
+fn tokens(line: &str, errors: &mut Vec<LexError>) -> (Vec<Token>, usize) {
+    let mut tokens = Vec::new();
+    let mut raw = String::new();
+    let mut text = String::new();
+    let mut start: Option<usize> = None;
+    let mut quote: Option<char> = None;
+    let mut end = line.len();
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) => {
+                raw.push(c);
+                if c != open || open == '`' {
+                    text.push(c);
+                }
+                if c == open {
+                    quote = None;
+                }
+            }
+            (None, '\\') => {
+                start.get_or_insert(index);
+                raw.push(c);
+                match chars.next() {
+                    Some((_, escaped)) => {
+                        raw.push(escaped);
+                        text.push(escaped);
+                    }
+                    None => text.push(c),
+                }
+            }
+            (None, '#') if start.is_none() => {
+                end = index;
+                break;
+            }
+            (None, '\'') | (None, '"') | (None, '`') => {
+                start.get_or_insert(index);
+                quote = Some(c);
+                raw.push(c);
+                // the command of backquotes is kept as it is written for the substitution
+                if c == '`' {
+                    text.push(c);
+                }
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    tokens.push(token(start, &mut raw, &mut text));
+                }
+            }
+            (None, c) if METACHARACTERS.contains(c) => {
+                let number = (c == '>' || c == '<') && raw.bytes().all(|b| b.is_ascii_digit());
+                if !number {
+                    if let Some(start) = start.take() {
+                        tokens.push(token(start, &mut raw, &mut text));
+                    }
+                }
+                let length = operator_length(&line[index..]);
+                raw.push_str(&line[index..(index + length)]);
+                text.push_str(&line[index..(index + length)]);
+                tokens.push(token(start.take().unwrap_or(index), &mut raw, &mut text));
+                // operators are ASCII, every byte of the rest of the operator is a character
+                for _ in 1..length {
+                    chars.next();
+                }
+            }
+            (None, '$') => {
+                start.get_or_insert(index);
+                let length = substitution_length(&line[index..]);
+                raw.push_str(&line[index..(index + length)]);
+                text.push_str(&line[index..(index + length)]);
+                for _ in line[(index + 1)..(index + length)].chars() {
+                    chars.next();
+                }
+            }
+            (None, c) => {
+                start.get_or_insert(index);
+                raw.push(c);
+                text.push(c);
+            }
+        }
+    }
+    if let Some(open) = quote {
+        errors.push(LexError::UnmatchedQuote(open));
+    }
+    if let Some(start) = start {
+        tokens.push(token(start, &mut raw, &mut text));
+    }
+    (tokens, end)
+}
+
+/// Takes the word read so far, the strings are reused for the next one
+fn token(start: usize, raw: &mut String, text: &mut String) -> Token {
+    Token {
+        start,
+        raw: raw.split_off(0),
+        text: text.split_off(0),
+    }
+}
+
+/// Why the command goes on in the next line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Continuation {
+    /// The line ends with a backslash which escapes the line break
+    Backslash,
+    /// The quote is still open, the line break is a part of the word
+    Quote(char),
+}
+
+/// Checks whether the command is unfinished at the end of the line
+pub fn continuation(line: &str) -> Option<Continuation> {
+    let mut errors = Vec::new();
+    let (_, end) = tokens(line, &mut errors);
+    if let Some(LexError::UnmatchedQuote(quote)) = errors.first() {
+        return Some(Continuation::Quote(*quote));
+    }
+    // backslashes escape each other in pairs, an odd one is left for the line break,
+    // a comment can't be continued
+    let backslashes = line[..end].chars().rev().take_while(|c| *c == '\\').count();
+    if backslashes % 2 == 1 {
+        Some(Continuation::Backslash)
+    } else {
+        None
+    }
+}
+
+/// Adds the next line to the unfinished command: an escaped line break disappears
+/// with its backslash, a quoted one is kept
+pub fn continue_line(line: &mut String, next: &str, continuation: Continuation) {
+    match continuation {
+        Continuation::Backslash => {
+            line.pop();
+        }
+        Continuation::Quote(_) => line.push('\n'),
+    }
+    line.push_str(next);
+}
+
+/// Splits the line into words failing on the first problem
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let parsed = parse_line_lossless(line);
+    match parsed.errors.first() {
+        Some(reason) => Err(reason.to_error()),
+        None => Ok(parsed.words),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_plain_words() {
+        let words = split_words("  ls\t-l   /tmp ").unwrap();
+        assert_eq!(words, vec!["ls", "-l", "/tmp"]);
+    }
+
+    #[test]
+    fn split_quoted_words() {
+        let words = split_words(r#"echo "a b"'c d' e\ f """#).unwrap();
+        assert_eq!(words, vec!["echo", "a bc d", "e f", ""]);
+    }
+
+    #[test]
+    fn unmatched_quote_is_reported() {
+        let parsed = parse_line_lossless("echo \"hello world");
+        assert_eq!(parsed.words, vec!["echo", "hello world"]);
+        assert_eq!(parsed.errors, vec![LexError::UnmatchedQuote('"')]);
+        assert!(split_words("echo 'oops").is_err());
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(split_words("# set x = 1").unwrap(), Vec::<String>::new());
+        assert_eq!(split_words("echo a # it's a comment").unwrap(), vec!["echo", "a"]);
+        assert_eq!(split_words("echo a#b '#' \\#").unwrap(), vec!["echo", "a#b", "#", "#"]);
+        assert_eq!(continuation("echo a # ends with \\"), None);
+        assert_eq!(continuation("echo a # \"unmatched"), None);
+    }
+
+    #[test]
+    fn operators_without_spaces() {
+        assert_eq!(split_words("echo a;echo b").unwrap(), vec!["echo", "a", ";", "echo", "b"]);
+        let words = split_words("ls|&wc>>out&&cat<in 2>&1||(x)").unwrap();
+        let expected = vec![
+            "ls", "|&", "wc", ">>", "out", "&&", "cat", "<", "in", "2>&", "1", "||", "(", "x", ")",
+        ];
+        assert_eq!(words, expected);
+        let words = split_words("echo 'a;b' a\\|b \"(\"x x2>y >>&z").unwrap();
+        assert_eq!(words, vec!["echo", "a;b", "a|b", "(x", "x2", ">", "y", ">>&", "z"]);
+        let starts: Vec<usize> = tokenize("ls;(a) 12<b").unwrap().iter().map(|t| t.start).collect();
+        assert_eq!(starts, vec![0, 2, 3, 4, 5, 7, 10]);
+        let tokens = tokenize(r#"a"b c"\ d>'e'"#).unwrap();
+        let words: Vec<(&str, &str)> =
+            tokens.iter().map(|t| (t.raw.as_str(), t.text.as_str())).collect();
+        assert_eq!(words, vec![(r#"a"b c"\ d"#, "ab c d"), (">", ">"), ("'e'", "e")]);
+        assert!(tokenize("echo 'oops").is_err());
+        assert_eq!(split_words("ls;# comment").unwrap(), vec!["ls", ";"]);
+    }
+
+    #[test]
+    fn substitutions_are_words() {
+        let words = split_words("echo $((1+2))x ${a;b} $(( (1 | 2) > 0 ));ls $(a)").unwrap();
+        let expected = vec![
+            "echo", "$((1+2))x", "${a;b}", "$(( (1 | 2) > 0 ))", ";", "ls", "$", "(", "a", ")",
+        ];
+        assert_eq!(words, expected);
+        assert_eq!(split_words("echo $((1 + 2").unwrap(), vec!["echo", "$((1 + 2"]);
+        assert_eq!(split_words("echo ${é").unwrap(), vec!["echo", "${é"]);
+    }
+
+    #[test]
+    fn backquotes_are_kept() {
+        let words = split_words("echo a`echo 'b c';ls`d \\` '`'").unwrap();
+        assert_eq!(words, vec!["echo", "a`echo 'b c';ls`d", "`", "`"]);
+        assert_eq!(continuation("echo `ls"), Some(Continuation::Quote('`')));
+        assert_eq!(word_quoted_dollars("'`'\\``$a`"), vec![0, 1]);
+        assert_eq!(word_double_quoted_dollars("`\"`\"`\""), vec![3]);
+    }
+
+    #[test]
+    fn continued_lines() {
+        assert_eq!(continuation("echo a \\"), Some(Continuation::Backslash));
+        assert_eq!(continuation("echo a \\\\"), None);
+        assert_eq!(continuation("echo \"hello"), Some(Continuation::Quote('"')));
+        assert_eq!(continuation("echo 'a\\"), Some(Continuation::Quote('\'')));
+        assert_eq!(continuation("echo 'a\\'"), None);
+        let mut line = String::from("echo a \\");
+        continue_line(&mut line, "b", Continuation::Backslash);
+        assert_eq!(line, "echo a b");
+        let mut line = String::from("echo \"a");
+        continue_line(&mut line, "b\"", Continuation::Quote('"'));
+        assert_eq!(split_words(&line).unwrap(), vec!["echo", "a\nb"]);
+    }
+
+    #[test]
+    fn patterns_are_unquoted() {
+        let patterns = glob_patterns(r#"ls *.rs 'a*'b "x"?[ab] \* [ $x* a[b"]"]"#);
+        let expected = vec![
+            None,
+            Some(String::from("*.rs")),
+            None,
+            Some(String::from("x?[ab]")),
+            None,
+            None,
+            None,
+            Some(String::from("a[b\\]]")),
+        ];
+        assert_eq!(patterns, expected);
+    }
+
+    #[test]
+    fn single_quoted_dollars() {
+        let quoted = quoted_dollars(r#"echo '$a' "$b" \$c x'é$'$d"#);
+        assert_eq!(quoted, vec![vec![], vec![0], vec![], vec![0], vec![3]]);
+        assert_eq!(word_double_quoted_dollars(r#"'$a'"$b"\$c"é$"$d"#), vec![2, 8]);
+    }
+
+    #[test]
+    fn garbage_does_not_panic() {
+        let bytes: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+        let line = String::from_utf8_lossy(&bytes);
+        parse_line_lossless(&line);
+        parse_line_lossless("\\");
+        parse_line_lossless("'\"\\");
+    }
+}
@@ -0,0 +1,59 @@
+use super::Shell;
+
+/// Default maximum number of words a single command can expand to
+const DEFAULT_MAX_WORDS: usize = 100_000;
+/// Default maximum length of an input line in bytes
+const DEFAULT_MAX_LINE: usize = 1 << 20;
+/// Default number of seconds an rc file may run
+const DEFAULT_RC_TIMEOUT: usize = 60;
+
+impl Shell {
+    /// Gets the maximum number of words in a command, it can be changed with `maxwords` variable
+    pub fn max_words(&self) -> usize {
+        self.limit("maxwords", DEFAULT_MAX_WORDS)
+    }
+
+    /// Gets the maximum length of an input line, it can be changed with `maxline` variable
+    pub fn max_line(&self) -> usize {
+        self.limit("maxline", DEFAULT_MAX_LINE)
+    }
+
+    /// Gets how many seconds an rc file may run before its command is killed,
+    /// it can be changed with `rctimeout` variable for the files read after the one setting it
+    pub fn rc_timeout(&self) -> u32 {
+        self.limit("rctimeout", DEFAULT_RC_TIMEOUT).min(u32::MAX as usize) as u32
+    }
+
+    /// Reads the limit from the variable falling back to the default if it is unset or invalid
+    fn limit(&self, name: &str, default: usize) -> usize {
+        self.variables
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_from_variable() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(shell.max_words(), DEFAULT_MAX_WORDS);
+        shell.variables.insert(String::from("maxwords"), String::from("10"));
+        assert_eq!(shell.max_words(), 10);
+        shell.variables.insert(String::from("rctimeout"), String::from("99999999999"));
+        assert_eq!(shell.rc_timeout(), u32::MAX);
+    }
+
+    #[test]
+    fn limit_invalid_value() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("maxline"), String::from("lots"));
+        assert_eq!(shell.max_line(), DEFAULT_MAX_LINE);
+        shell.variables.insert(String::from("maxline"), String::from("0"));
+        assert_eq!(shell.max_line(), DEFAULT_MAX_LINE);
+    }
+}
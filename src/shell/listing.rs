@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use libc::{S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFSOCK, S_IXGRP, S_IXOTH,
+           S_IXUSR};
+
+use native::error::*;
+use native::file_stat::{get_file_mode, get_link_mode, FileMode};
+use native::{collate, read_dir_names, write_to_file};
+
+use super::Shell;
+
+impl Shell {
+    /// Implements `ls-F [name ...]` of tcsh: lists the files and the contents of the directories,
+    /// the current one by default, in columns without running ls. Hidden files are skipped.
+    /// Names get a suffix telling the type: `/` a directory, `@` a symlink, `*` an executable,
+    /// `|` a FIFO, `=` a socket, `#` a block and `%` a character device.
+    pub fn ls_f_builtin(&self, names: &[&str]) -> Result<()> {
+        let names = if names.is_empty() { vec!["."] } else { names.to_vec() };
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for name in &names {
+            let path = self.cwd.join(name);
+            // like ls, a symlink to a directory given by name is listed as the directory
+            let mode = get_file_mode(&path).map_err(|reason| reason.about(name))?;
+            if mode & S_IFMT == S_IFDIR {
+                dirs.push(*name);
+            } else {
+                files.push(mark(name, &path));
+            }
+        }
+        let width = self.window.map_or(80, |size| usize::from(size.columns));
+        let mut text = columns(sorted(files), width);
+        for name in dirs {
+            let path = self.cwd.join(name);
+            let entries = read_dir_names(&path).map_err(|reason| reason.about(name))?;
+            let entries = entries
+                .iter()
+                .filter(|entry| !entry.starts_with('.'))
+                .map(|entry| mark(entry, &path.join(entry)))
+                .collect();
+            if names.len() > 1 {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&format!("{}:\n", name));
+            }
+            text.push_str(&columns(sorted(entries), width));
+        }
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+}
+
+/// Appends the suffix telling the type of the file to its name
+fn mark(name: &str, path: &Path) -> String {
+    let suffix = get_link_mode(path).map_or("", type_suffix);
+    format!("{}{}", name, suffix)
+}
+
+/// Tells the type of the file like `ls -F`
+fn type_suffix(mode: FileMode) -> &'static str {
+    match mode & S_IFMT {
+        S_IFDIR => "/",
+        S_IFLNK => "@",
+        S_IFIFO => "|",
+        S_IFSOCK => "=",
+        S_IFBLK => "#",
+        S_IFCHR => "%",
+        _ if mode & (S_IXUSR | S_IXGRP | S_IXOTH) != 0 => "*",
+        _ => "",
+    }
+}
+
+fn sorted(mut names: Vec<String>) -> Vec<String> {
+    names.sort_by(|left, right| collate(left, right));
+    names
+}
+
+/// Arranges the names in columns fitting in the width, they go down first like in ls
+fn columns(names: Vec<String>, width: usize) -> String {
+    let column = names.iter().map(|name| name.chars().count()).max().unwrap_or(0) + 2;
+    let per_line = (width / column).max(1);
+    let rows = names.len().div_ceil(per_line);
+    let mut text = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for name in names.iter().skip(row).step_by(rows) {
+            line.push_str(name);
+            line.push_str(&" ".repeat(column - name.chars().count()));
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use libc::S_IFREG;
+
+    use super::*;
+
+    fn names(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn columns_go_down_first() {
+        assert_eq!(columns(names("a b c d e"), 9), "a  c  e\nb  d\n");
+        assert_eq!(columns(names("alpha beta"), 3), "alpha\nbeta\n");
+        assert_eq!(columns(Vec::new(), 80), "");
+    }
+
+    #[test]
+    fn suffixes_of_types() {
+        assert_eq!(type_suffix(S_IFDIR | 0o755), "/");
+        assert_eq!(type_suffix(S_IFLNK | 0o777), "@");
+        assert_eq!(type_suffix(S_IFREG | 0o755), "*");
+        assert_eq!(type_suffix(S_IFREG | 0o644), "");
+        assert_eq!(type_suffix(S_IFIFO | 0o644), "|");
+    }
+}
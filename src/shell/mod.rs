@@ -1,27 +1,157 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
-use std::env::{args, var, vars};
+use std::env::{args, set_var, var, vars};
 use std::ffi::OsString;
 use std::iter::once;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
 
-use libc::{O_CREAT, O_WRONLY, O_RDONLY, S_IRUSR};
+use libc::{O_APPEND, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY, S_IRGRP, S_IROTH, S_IRUSR,
+           S_IWGRP, S_IWOTH, S_IWUSR};
 
 use native::*;
 use native::users::*;
+#[cfg(feature = "net")]
+use native::net::connect_tcp;
 use native::error::*;
 use native::file_stat::*;
+use native::signal::{catch_interrupt, catch_terminal_stop, deadline_passed, drain_wakeups,
+                     interrupted, open_wakeup_pipe, start_deadline, stop_deadline,
+                     watch_children, watch_window_size, window_changed};
+use native::term::{get_window_size, is_terminal, WindowSize};
+use native::watch::FileWatch;
+
+mod arith;
+pub mod ast;
+mod batch;
+pub mod bench;
+mod braces;
+mod builtins;
+mod checksum;
+mod complete;
+mod condition;
+#[cfg(feature = "crypt")]
+mod crypt;
+mod debug;
+mod detach;
+mod dirs;
+mod dryrun;
+mod editor;
+mod events;
+mod expand;
+#[cfg(feature = "fetch")]
+mod fetch;
+mod fifo;
+mod fields;
+mod glob;
+mod guard;
+mod hash;
+pub mod history;
+mod interpreter;
+pub mod jobs;
+pub mod lexer;
+mod limits;
+mod listing;
+mod modifiers;
+mod motd;
+mod multios;
+pub mod options;
+mod pipeline;
+mod prompt;
+pub mod quoting;
+pub mod random;
+mod redirect;
+mod reload;
+mod spawn;
+mod stats;
+mod subhelper;
+mod subshell;
+mod temp;
+mod theme;
+mod trace;
+mod variables;
+
+use self::ast::parse_line;
+use self::builtins::Builtin;
+use self::debug::Debug;
+use self::dirs::abbreviate_home;
+use self::editor::Editor;
+use self::fifo::open_output;
+use self::hash::CommandHash;
+use self::history::History;
+use self::jobs::Jobs;
+use self::lexer::{continuation, continue_line};
+use self::multios::TerminalOutput;
+use self::options::Options;
+use self::random::Random;
+use self::redirect::{Kind, Redirection};
+use self::stats::SessionStats;
+use self::subhelper::SubstitutionHelper;
+use self::temp::TempDir;
+use self::reload::RcChanges;
+use self::theme::Theme;
+use self::variables::{Value, Variables};
+
+/// System-wide rc files, each is read before the one of the same kind in the home directory
+const SYSTEM_CSHRC: &str = "/etc/csh.cshrc";
+const SYSTEM_LOGIN: &str = "/etc/csh.login";
+const SYSTEM_LOGOUT: &str = "/etc/csh.logout";
+
+/// Permissions of files created by redirections unless `redirmode` is set,
+/// umask is applied by the system
+const CREATE_MODE: u32 = S_IRUSR | S_IWUSR | S_IRGRP | S_IWGRP | S_IROTH | S_IWOTH;
+
+/// Prompt of the lines which continue an unfinished command unless `prompt2` is set
+const DEFAULT_PROMPT2: &str = "? ";
+
+/// Tells the reader of commands whether it should go on after a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Continue,
+    /// `exit` was called, the exit code is saved in the status of the shell
+    Exit,
+}
 
 /// The structure represents the state of a shell. First of all, it stores variables.
 pub struct Shell {
-    pub variables: HashMap<String, String>,
+    pub variables: Variables,
     pub is_login: bool,
     pub argv: Vec<String>,
     pub user: UserId,
     pub status: ExitCode,
+    /// A process of the last external command has dumped core, `$coredumped` shows it
+    pub core_dumped: bool,
     pub home: PathBuf,
     pub path: Vec<PathBuf>,
     pub prompt: String,
     pub cwd: PathBuf,
+    pub dir_stack: Vec<PathBuf>,
+    pub history: History,
+    pub jobs: Jobs,
+    /// Where commands were found in PATH, see `hash_lookup`
+    pub hash: CommandHash,
+    /// Durations, failures and directories of commands typed in this session
+    session_stats: SessionStats,
+    /// Set by `incognito on`: history, statistics and `sessionlog` don't record anything
+    incognito: bool,
+    editor: Editor,
+    /// Prompt theme selected with `prompt theme`, it replaces `prompt` when set
+    theme: Option<&'static Theme>,
+    /// What the rc files have changed, `reload` undoes it
+    rc_changes: RcChanges,
+    /// Notices edits of the rc files while `rcwatch` is set
+    rc_watch: Option<FileWatch>,
+    /// Builtins by their names, see `builtins::registry`
+    builtins: HashMap<&'static str, &'static dyn Builtin>,
+    /// Size of the terminal, it is updated after SIGWINCH
+    pub window: Option<WindowSize>,
+    /// Created by `mktemp` and removed on exit
+    temp_dir: Option<TempDir>,
+    /// Runs `` `...` `` without forking while `subhelper` is set
+    helper: Option<SubstitutionHelper>,
+    pub options: Options,
+    pub random: Random,
 }
 
 impl Shell {
@@ -35,147 +165,349 @@ impl Shell {
             .split(':')
             .map(PathBuf::from)
             .collect();
-        let argv = args().collect();
+        let argv: Vec<String> = args().collect();
+        let options = Options::parse(&argv)?;
+        let mut variables = Variables::default();
+        if options.echo {
+            variables.insert(String::from("echo"), String::new());
+        }
+        if options.verbose {
+            variables.insert(String::from("verbose"), String::new());
+        }
+        if options.noexec {
+            variables.insert(String::from("noexec"), String::new());
+        }
+        variables.insert_value(String::from("argv"), Value::list(options.arguments.clone()));
+        if is_terminal(0) && is_terminal(1) {
+            variables.insert(String::from("edit"), String::new());
+        }
         Ok(Shell {
-            variables: HashMap::new(),
-            is_login: Self::is_login(&argv),
+            variables,
+            is_login: Self::is_login(&argv, &options),
             argv,
             user,
             status: 0,
+            core_dumped: false,
             path,
             home: get_home_dir(user)?,
             cwd: get_current_dir()?,
             prompt: get_prompt(user),
+            dir_stack: Vec::new(),
+            history: History::default(),
+            jobs: Jobs::default(),
+            hash: CommandHash::default(),
+            session_stats: SessionStats::default(),
+            incognito: false,
+            editor: Editor::default(),
+            theme: None,
+            rc_changes: RcChanges::default(),
+            rc_watch: None,
+            builtins: builtins::registry(),
+            window: None,
+            temp_dir: None,
+            helper: None,
+            random: Random::with_seed_from_time(options.deterministic),
+            options,
         })
     }
 
     /// The function opens a file on the provided path if any and tries to interpret this file.
     /// All changes in shell variables are saved!
     /// It is recommended to call this function in a clone of the current shell.
-    pub fn interpret(&mut self, path: &PathBuf) -> Result<()> {
-        let fdi = open_file(path, O_RDONLY, None)?;
-        let header = read_line(fdi)?;
-        if header.starts_with("#!") {
-            fork_process(|| {
-                let name = match path.to_str() {
-                    Some(value) => String::from(value),
-                    None => return Error::InvalidUnicode,
-                };
-                let environment: Vec<String> = vars()
-                    .map(|(key, value)| format!("{}={}", key, value))
-                    .collect();
-                execute(path, vec![name], environment)
-            })?;
-        } else {
-            let content = read_file(fdi)?;
-            for line in content.lines() {
-                self.parse(line)?;
+    /// If `noexec` is set, errors are reported with line numbers and checking goes on.
+    pub fn interpret(&mut self, path: &Path) -> Result<Flow> {
+        let fdi = open_file(path, O_RDONLY, None).map_err(|reason| reason.about(path.display()))?;
+        let result = self.interpret_lines(path, &mut Reader::new(fdi));
+        close_file(fdi)?;
+        result
+    }
+
+    /// Reads and runs the lines of the script opened by `interpret`
+    fn interpret_lines(&mut self, path: &Path, reader: &mut Reader) -> Result<Flow> {
+        let header = match reader.read_line(self.max_line())? {
+            Some(header) => header,
+            None => return Ok(Flow::Continue),
+        };
+        let mut number = 0;
+        let mut line = Some(header);
+        if let Some((interpreter, argument)) = line.as_deref().and_then(parse_shebang) {
+            if !is_own_interpreter(interpreter) {
+                self.status = self.run_interpreter(path, interpreter, argument)?;
+                return Ok(Flow::Continue);
             }
+            // the script is meant for rsh, so the header is just skipped
+            number += 1;
+            line = reader.read_line(self.max_line())?;
         }
-        Ok(())
+        let mut failed = false;
+        while let Some(mut current) = line {
+            number += 1;
+            while let Some(continued) = continuation(&current) {
+                match reader.read_line(self.max_line())? {
+                    Some(next) => continue_line(&mut current, &next, continued),
+                    None => break,
+                }
+                number += 1;
+            }
+            let result = self.parse(&current);
+            if deadline_passed() {
+                // the rest of the rc file is skipped even with `noexec`
+                let reason = Error::RcTimedOut(self.rc_timeout());
+                return Err(reason.at(abbreviate_home(path, &self.home), number));
+            }
+            match result {
+                Ok(Flow::Exit) => return Ok(Flow::Exit),
+                Ok(Flow::Continue) => {}
+                Err(reason) => {
+                    let reason = reason.at(abbreviate_home(path, &self.home), number);
+                    if !self.variables.contains_key("noexec") {
+                        return Err(reason);
+                    }
+                    write_to_file(2, &format!("{}\n", reason))?;
+                    failed = true;
+                }
+            }
+            line = reader.read_line(self.max_line())?;
+        }
+        if failed {
+            return Err(Error::SyntaxError);
+        }
+        Ok(Flow::Continue)
     }
 
-    /// Parses the command and executes it.
-    /// Returns true if reading should be stopped.
-    fn parse(&mut self, line: &str) -> Result<bool> {
-        let mut arguments = line.split_whitespace();
-        let mut environment: Vec<String> = vars()
-            .map(|(key, value)| format!("{}={}", key, value))
+    /// Runs the script by the interpreter of its `#!` line like execve(2) does it:
+    /// the interpreter gets its optional argument and the path of the script
+    fn run_interpreter(
+        &self,
+        path: &Path,
+        interpreter: &str,
+        argument: Option<&str>,
+    ) -> Result<ExitCode> {
+        let script = path.to_str().ok_or(Error::InvalidUnicode)?;
+        let arguments: Vec<String> = once(interpreter)
+            .chain(argument)
+            .chain(once(script))
+            .map(String::from)
             .collect();
-        let mut argument;
-        loop {
-            argument = match arguments.next() {
-                Some(value) => value,
-                None => return Err(Error::NotFound),
-            };
-            if argument.contains('=') {
-                environment.push(String::from(argument));
-            } else {
-                break;
-            }
+        self.debug(Debug::Exec, || format!("{} runs {}", interpreter, script));
+        fork_process(|| {
+            let environment: Vec<String> = vars()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            execute(Path::new(interpreter), arguments, environment).about(interpreter)
+        })
+    }
+
+    /// Parses the command and executes it unless `noexec` is set.
+    /// Returns whether reading of commands should go on.
+    fn parse(&mut self, line: &str) -> Result<Flow> {
+        self.trace_input(line)?;
+        if let Some(command) = self.dry_run_command(line) {
+            self.dry_run(command)?;
+            return Ok(Flow::Continue);
         }
-        match argument {
-            "exit" => Ok(true),
-            "pwd" => {
-                let cwd = self.cwd.clone();
-                let cwd = cwd.to_str().ok_or(Error::InvalidUnicode)?;
-                write_to_file(1, &format!("{}\n", cwd))?;
-                Ok(false)
+        let list = parse_line(line)?;
+        self.debug(Debug::Parser, || format!("commands {:?}", list));
+        if self.variables.contains_key("noexec") {
+            return Ok(Flow::Continue);
+        }
+        self.run_list(&list)
+    }
+
+    /// Runs the program found by the parent in the forked child,
+    /// returns only if it can't be executed
+    fn execute_command(
+        &self,
+        name: &str,
+        path: Option<&Path>,
+        arguments: &[String],
+        redirections: &[Redirection],
+        environment: Vec<String>,
+        terminal: TerminalOutput,
+    ) -> Error {
+        let path = match path {
+            None => {
+                self.debug(Debug::Exec, || format!("{} not found", name));
+                return Error::CommandNotFound(String::from(name));
             }
-            _ => {
-                self.status = fork_process(|| {
-                    let path = match self.find_path(argument) {
-                        None => return Error::NotFound,
-                        Some(value) => value,
-                    };
-                    let arguments = match self.parse_shell(arguments) {
-                        Err(reason) => return reason,
-                        Ok(value) => value,
-                    };
-                    let slices = arguments.into_iter();
-                    let arguments = once(argument.to_owned()).chain(slices).collect();
-                    execute(&path, arguments, environment)
-                })?;
-                Ok(false)
+            Some(value) => value,
+        };
+        self.debug(Debug::Exec, || format!("{} resolved to {}", name, path.display()));
+        if let Err(reason) = self.apply_redirections(redirections, terminal) {
+            return reason;
+        }
+        let arguments: Vec<String> = once(name.to_owned()).chain(arguments.to_vec()).collect();
+        let reason = execute(path, arguments.clone(), environment.clone());
+        if reason.is_too_long() {
+            return self.run_in_batches(path, arguments, environment, reason).about(name);
+        }
+        reason.about(name)
+    }
+
+    /// Applies the redirections recorded by the parent in the forked child, the descriptors
+    /// of the shell itself are never touched. A target like `@/run/app.sock` is a Unix socket
+    /// which is connected to, so is `/dev/tcp/host/port` with the `net` feature.
+    /// If `multios` is set, `> a > b` writes to both files and `< a < b` reads one file
+    /// after another. Whatever still goes to the terminal afterwards is watched
+    /// as the terminal output says.
+    fn apply_redirections(
+        &self,
+        redirections: &[Redirection],
+        terminal: TerminalOutput,
+    ) -> Result<()> {
+        let enabled = self.variables.contains_key("multios");
+        let mut multios = Vec::new();
+        for redirection in redirections {
+            let old_fd = redirection.fd;
+            let (target, append, both) = match &redirection.kind {
+                Kind::Input(target) => {
+                    let new_fd = open_file(Path::new(target), O_RDONLY, None)
+                        .map_err(|reason| reason.about(target))?;
+                    multios::redirect(&mut multios, enabled, old_fd, new_fd, true)?;
+                    continue;
+                }
+                Kind::Duplicate(new_fd) => {
+                    replace_fdi(old_fd, *new_fd)?;
+                    continue;
+                }
+                Kind::Output {
+                    target,
+                    append,
+                    both,
+                } => (target.as_str(), *append, *both),
+            };
+            // the redirection is done by the forked child, so it may wait for a reader
+            let new_fd = self.open_target(target, append, true)?;
+            if both {
+                multios::redirect(&mut multios, enabled, 2, duplicate_fd(new_fd)?, false)?;
             }
+            multios::redirect(&mut multios, enabled, old_fd, new_fd, false)?;
+        }
+        multios::watch_terminal(&mut multios, terminal)?;
+        multios::join(multios)?;
+        Ok(())
+    }
+
+    /// Opens the target of an output redirection: a socket, a file or a named pipe, which may
+    /// wait for a reader
+    fn open_target(&self, target: &str, append: bool, wait_reader: bool) -> Result<RawFd> {
+        if let Some(socket) = target.strip_prefix('@') {
+            return connect_socket(Path::new(socket)).map_err(|reason| reason.about(socket));
+        }
+        if let Some(connected) = connect_tcp_target(target) {
+            return connected;
+        }
+        if !append {
+            self.back_up(Path::new(target))?;
         }
+        let flags = O_CREAT | O_WRONLY | if append { O_APPEND } else { O_TRUNC };
+        open_output(Path::new(target), flags, self.create_mode()?, wait_reader)
+            .map_err(|reason| reason.about(target))
     }
 
-    fn parse_shell<'a, I>(&self, mut arguments: I) -> Result<Vec<String>>
+    /// Runs the builtin with the redirections applied to the descriptors of the shell itself,
+    /// they are saved before and restored afterwards even if the builtin fails, so the error
+    /// is reported by the shell where it reports others.
+    /// A descriptor gets only its last target as if `multios` wasn't set.
+    fn with_redirections<T, F>(&mut self, redirections: &[Redirection], run: F) -> Result<T>
     where
-        I: Iterator<Item = &'a str>,
+        F: FnOnce(&mut Shell) -> Result<T>,
     {
-        let mut result: Vec<String> = Vec::new();
-        'outer: loop {
-            let mut arg = match arguments.next() {
-                None => break,
-                Some(value) => String::from(value),
-            };
-            arg = if let Some(begin) = arg.find("$") {
-                let end = arg[(begin + 1)..]
-                    .rfind(|c: char| !c.is_alphanumeric())
-                    .map(|end| end + begin + 1)
-                    .unwrap_or(arg.len());
-                let var_name = &arg[(begin + 1)..end];
-                let value = self.variables
-                    .get(var_name)
-                    .map(String::to_owned)
-                    .unwrap_or(var(var_name).unwrap_or(String::new()));
-                value
-            } else {
-                arg
+        if redirections.is_empty() {
+            return run(self);
+        }
+        let mut saved = Vec::new();
+        let result = self.redirect_shell(redirections, &mut saved).and_then(|_| run(self));
+        for (fd, copy) in saved.into_iter().rev() {
+            match copy {
+                Some(copy) => {
+                    replace_fdi(fd, copy)?;
+                    close_file(copy)?;
+                }
+                // the descriptor wasn't open before
+                None => close_file(fd)?,
+            }
+        }
+        result
+    }
+
+    /// Redirects the descriptors of the shell saving every one of them the first time,
+    /// a named pipe is opened only if it has a reader already
+    fn redirect_shell(
+        &self,
+        redirections: &[Redirection],
+        saved: &mut Vec<(RawFd, Option<RawFd>)>,
+    ) -> Result<()> {
+        for redirection in redirections {
+            let fd = redirection.fd;
+            let both = matches!(redirection.kind, Kind::Output { both: true, .. });
+            for fd in once(fd).chain(Some(2).filter(|_| both)) {
+                if saved.iter().all(|(saved, _)| *saved != fd) {
+                    saved.push((fd, save_fd(fd).ok()));
+                }
+            }
+            let new_fd = match &redirection.kind {
+                Kind::Input(target) => open_file(Path::new(target), O_RDONLY, None)
+                    .map_err(|reason| reason.about(target))?,
+                Kind::Duplicate(other) => {
+                    replace_fdi(fd, *other)?;
+                    continue;
+                }
+                Kind::Output { target, append, .. } => self.open_target(target, *append, false)?,
             };
-            if let Some(index) = arg.find(">") {
-                let old_fd = if arg.starts_with(">") {
-                    1
-                } else {
-                    (&arg[..index]).parse().map_err(|_| Error::NotFound)?
-                };
-                let new_fd = if (&arg[index..]).starts_with(">&") {
-                    if arg.ends_with(">&") {
-                        arguments.next().ok_or(Error::NotFound).and_then(
-                            |value: &str| {
-                                value.parse().map_err(|_| Error::NotFound)
-                            },
-                        )?
-                    } else {
-                        (&arg[(index + 2)..]).parse().map_err(|_| Error::NotFound)?
-                    }
-                } else {
-                    let path = if arg.len() == 1 {
-                        arguments.next().ok_or(Error::NotFound)?
-                    } else {
-                        &arg[index..]
-                    };
-                    let path = PathBuf::from(path);
-                    open_file(&path, O_CREAT | O_WRONLY, Some(S_IRUSR))?
-                };
-                replace_fdi(old_fd, new_fd)?;
-            } else {
-                result.push(arg);
+            if both {
+                replace_fdi(2, new_fd)?;
+            }
+            if new_fd != fd {
+                replace_fdi(fd, new_fd)?;
+                close_file(new_fd)?;
             }
         }
-        Ok(result)
+        Ok(())
+    }
+
+    /// Renames the regular file which `>` is about to truncate if `backup` is set,
+    /// its value is the suffix added to the name, `~` if it's empty
+    fn back_up(&self, path: &Path) -> Result<()> {
+        let suffix = match self.variables.get("backup") {
+            Some(suffix) if suffix.is_empty() => "~",
+            Some(suffix) => suffix.as_str(),
+            None => return Ok(()),
+        };
+        if !path.is_file() {
+            return Ok(());
+        }
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        rename_file(path, Path::new(&backup))
+    }
+
+    /// Opens the file of `sessionlog` for appending, the output of foreground commands
+    /// which goes to the terminal is copied there.
+    /// Returns None if the variable isn't set or the shell is incognito.
+    fn open_session_log(&self) -> Result<Option<RawFd>> {
+        let name = match self.variables.get("sessionlog") {
+            Some(name) if !name.is_empty() && !self.incognito => name,
+            _ => return Ok(None),
+        };
+        let path = self.cwd.join(self.expand_home(name));
+        let flags = O_CREAT | O_WRONLY | O_APPEND;
+        open_file(&path, flags, Some(self.create_mode()?))
+            .map(Some)
+            .map_err(|reason| reason.about(name))
+    }
+
+    /// Gets permissions of files created by redirections: `redirmode` in octal like `0644`
+    /// or `CREATE_MODE`. Like open(2) does it, the umask is cleared from them and existing
+    /// files keep their permissions.
+    fn create_mode(&self) -> Result<u32> {
+        match self.variables.get("redirmode") {
+            Some(mode) => match u32::from_str_radix(mode, 8) {
+                Ok(mode) if mode <= 0o7777 => Ok(mode),
+                _ => Err(Error::SyntaxError),
+            },
+            None => Ok(CREATE_MODE),
+        }
     }
 
     /// Iterates over the PATH variable contents looking for the program
@@ -190,12 +522,17 @@ impl Shell {
         } else {
             let name = OsString::from(name);
             for path in &self.path {
-                if let Ok(dir) = path.read_dir() {
-                    for entry in dir {
-                        if let Ok(entry) = entry {
-                            if entry.file_name() == name {
-                                return Some(entry.path());
-                            }
+                if self.is_unhashed(path) {
+                    let candidate = path.join(&name);
+                    if is_executable_file(&candidate) {
+                        return Some(candidate);
+                    }
+                } else if let Ok(dir) = path.read_dir() {
+                    for entry in dir.flatten() {
+                        // directories and files without execute permission
+                        // don't hide programs in later directories
+                        if entry.file_name() == name && is_executable_file(&entry.path()) {
+                            return Some(entry.path());
                         }
                     }
                 }
@@ -204,68 +541,371 @@ impl Shell {
         }
     }
 
-    /// Checks whether we're the login shell or not
-    fn is_login(args: &Vec<String>) -> bool {
-        match args.len() {
+    /// Checks whether we're the login shell or not: the name we were started with begins
+    /// with '-' like login(1) does it, or `-l` or `--login` was given anywhere
+    fn is_login(args: &[String], options: &Options) -> bool {
+        let name = match args.first() {
             // first argument MUST be present
-            0 => write_exit(7, "Something went REALLY wrong"),
-            1 => args[0].starts_with('-'), // we had no arguments and started as -<something>,
-            2 => args[1].eq(&"-l".to_string()), // we had only one argument - "-l",
-            _ => false,
-        }
+            None => write_exit(7, "Something went REALLY wrong"),
+            Some(name) => Path::new(name).file_name().and_then(|name| name.to_str()),
+        };
+        name.is_some_and(|name| name.starts_with('-')) || options.login
     }
 
     /// Checks whether the provided rc file should be interpreted or not. If so, it interprets it.
-    pub fn interpret_rc(&mut self, rc_name: &str) -> Result<()> {
-        let mut rc_file = self.home.clone();
-        rc_file.push(rc_name);
-        return if check_file(&rc_file)? {
-            self.interpret(&rc_file)
-        } else {
-            Ok(())
-        };
+    pub fn interpret_rc(&mut self, rc_name: &str) -> Result<Flow> {
+        let rc_file = self.home.join(rc_name);
+        self.interpret_checked(&rc_file)
+    }
+
+    /// Interprets the file if it exists and passes `check_file`.
+    /// A command still running `rc_timeout` seconds after the file was opened is killed
+    /// and the rest of the file is skipped, so a hung rc file doesn't block the shell.
+    fn interpret_checked(&mut self, path: &Path) -> Result<Flow> {
+        if !(path.exists() && check_file(path)?) {
+            return Ok(Flow::Continue);
+        }
+        start_deadline(self.rc_timeout())?;
+        let result = self.interpret(path);
+        stop_deadline();
+        result
     }
 
     /// Starts interactive shell which prints prompt and waits for user's input.
+    /// The history file is loaded before the first prompt and updated at the end,
+    /// a history which can't be loaded, e.g. for a wrong passphrase, is reported and skipped.
+    /// The end of input finishes the shell unless `ignoreeof` is set and input is a terminal.
     pub fn interact(&mut self) -> Result<()> {
-        loop {
-            write_to_file(1, &self.prompt)?;
-            let input = read_line(0)?;
-            if self.parse(&input)? {
-                break;
+        if let Err(reason) = self.load_history() {
+            write_to_file(2, &reason.diagnostic())?;
+        }
+        open_wakeup_pipe()?;
+        watch_children()?;
+        catch_terminal_stop()?;
+        catch_interrupt()?;
+        watch_window_size()?;
+        if is_terminal(0) {
+            self.take_terminal()?;
+        }
+        if self.is_login {
+            self.greet()?;
+        }
+        let mut stdin = Reader::new(0);
+        stdin.wake_on_signals = true;
+        'commands: loop {
+            // signals which have come while a command ran are handled right here
+            interrupted();
+            drain_wakeups();
+            self.report_jobs(true)?;
+            self.reset_tty()?;
+            match self.check_rc_files() {
+                Ok(Flow::Exit) => break,
+                Ok(Flow::Continue) => {}
+                Err(reason) => {
+                    write_to_file(2, &reason.diagnostic())?;
+                }
+            }
+            self.update_window_size();
+            if !self.is_editing() {
+                self.write_prompt()?;
+            }
+            stdin.timeout = self.autologout();
+            let input = loop {
+                match self.read_command(&mut stdin) {
+                    Err(Error::TimedOut) => {
+                        write_to_file(1, &format!("\n{}\n", msg!("auto-logout")))?;
+                        break 'commands;
+                    }
+                    // SIGCHLD wakes the read up to report jobs if `notify` is set,
+                    // SIGWINCH does to pass the new size to the editor
+                    Err(Error::Interrupted) => {
+                        self.update_window_size();
+                        // the terminal driver has dropped the typed line on Ctrl-C
+                        if interrupted() {
+                            stdin.discard_line();
+                            write_to_file(1, "\n")?;
+                            self.report_jobs(true)?;
+                            self.write_prompt()?;
+                            continue;
+                        }
+                        if !self.report_jobs(false)? {
+                            continue;
+                        }
+                        if self.is_editing() {
+                            self.editor.invalidate();
+                        } else {
+                            self.write_prompt()?;
+                        }
+                    }
+                    result => break result?,
+                }
+            };
+            let input = match input {
+                Some(input) => input,
+                None if self.variables.contains_key("ignoreeof") && is_terminal(0) => {
+                    let command = if self.is_login { "logout" } else { "exit" };
+                    // the editor has already moved to the next line
+                    let newline = if self.is_editing() { "" } else { "\n" };
+                    let text = msg!("Use \"{}\" to leave the shell.", command);
+                    write_to_file(2, &format!("{}{}\n", newline, text))?;
+                    continue;
+                }
+                None => {
+                    if is_terminal(0) && !self.is_editing() {
+                        write_to_file(1, "\n")?;
+                    }
+                    if self.may_exit()? {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let input = self.read_continuation(&mut stdin, input)?;
+            let input = match self.expand_history(&input) {
+                Ok(None) => input,
+                Ok(Some((expanded, print))) => {
+                    // the expanded command is shown before it runs like csh does
+                    write_to_file(1, &format!("{}\n", expanded))?;
+                    if print {
+                        self.add_history(&expanded);
+                        continue;
+                    }
+                    expanded
+                }
+                Err(reason) => {
+                    write_to_file(2, &reason.diagnostic())?;
+                    self.status = 1;
+                    continue;
+                }
+            };
+            self.add_history(&input);
+            let started = Instant::now();
+            let dir = self.cwd.clone();
+            // a failed command is reported and the next one is read,
+            // only failures of the terminal itself end the session
+            let result = self.parse(&input);
+            let failed = result.is_err() || self.status != 0;
+            if !self.incognito {
+                self.session_stats.record(&input, &dir, started.elapsed(), failed);
+            }
+            match result {
+                Ok(Flow::Exit) => {
+                    if self.may_exit()? {
+                        break;
+                    }
+                    continue;
+                }
+                Ok(Flow::Continue) => {}
+                Err(reason) => {
+                    write_to_file(2, &reason.diagnostic())?;
+                    self.status = 1;
+                }
             }
+            // only an immediately repeated exit ignores unfinished jobs
+            self.jobs.exit_warned = false;
+        }
+        self.release_terminal()?;
+        self.save_history()
+    }
+
+    /// Gets the size of the terminal again if the window has been resized
+    /// and exports it to children as COLUMNS and LINES
+    fn update_window_size(&mut self) {
+        if !window_changed() {
+            return;
+        }
+        self.window = if is_terminal(1) { get_window_size(1).ok() } else { None };
+        // a pseudo terminal may have no size at all
+        if let Some(size) = self.window.filter(|size| size.columns > 0) {
+            set_var("COLUMNS", size.columns.to_string());
+            set_var("LINES", size.rows.to_string());
+        }
+    }
+
+    /// Gets how long a login shell on a terminal waits for a command before it exits,
+    /// `autologout` is the number of minutes
+    fn autologout(&self) -> Option<Duration> {
+        if !self.is_login || !is_terminal(0) {
+            return None;
+        }
+        let minutes: f64 = self.variables.get("autologout")?.parse().ok()?;
+        if minutes > 0.0 {
+            Some(Duration::from_secs_f64(minutes * 60.0))
+        } else {
+            None
         }
-        Ok(())
     }
 
-    /// Reads initial scripts
-    pub fn on_start(&mut self) -> Result<()> {
+    /// Checks whether the line editor is used: `edit` is set and input is a terminal
+    fn is_editing(&self) -> bool {
+        self.variables.contains_key("edit") && is_terminal(0)
+    }
+
+    /// Reads the next interactive command with the line editor or as is
+    fn read_command(&mut self, stdin: &mut Reader) -> Result<Option<String>> {
+        let prompt = self.prompt_text();
+        self.read_line(stdin, &prompt)
+    }
+
+    /// Reads lines while the command is unfinished: after a trailing backslash
+    /// or in an open quote. They are prompted for with `prompt2`.
+    /// Ctrl-C drops the whole command, the end of input leaves it unfinished.
+    fn read_continuation(&mut self, stdin: &mut Reader, mut input: String) -> Result<String> {
+        while let Some(continuation) = continuation(&input) {
+            let prompt = match self.variables.get("prompt2") {
+                Some(prompt) => prompt.clone(),
+                None => String::from(DEFAULT_PROMPT2),
+            };
+            if !self.is_editing() {
+                write_to_file(1, &prompt)?;
+            }
+            self.editor.abort_ends = true;
+            let result = self.read_line(stdin, &prompt);
+            self.editor.abort_ends = false;
+            match result {
+                Ok(Some(next)) => continue_line(&mut input, &next, continuation),
+                Ok(None) => break,
+                // the editor has already printed ^C
+                Err(Error::Aborted) => return Ok(String::new()),
+                Err(Error::Interrupted) if interrupted() => {
+                    stdin.discard_line();
+                    write_to_file(1, "\n")?;
+                    return Ok(String::new());
+                }
+                // other signals only wake the read up
+                Err(Error::Interrupted) => {}
+                Err(reason) => return Err(reason),
+            }
+        }
+        Ok(input)
+    }
+
+    /// Reads a line from stdin or with the editor which shows the prompt itself
+    fn read_line(&mut self, stdin: &mut Reader, prompt: &str) -> Result<Option<String>> {
+        if !self.is_editing() {
+            return stdin.read_line(self.max_line());
+        }
+        // the editor is taken out because the shell completes words for it
+        let mut editor = mem::take(&mut self.editor);
+        editor.track_focus = self.variables.contains_key("longjob");
+        editor.filec = self.variables.contains_key("filec");
+        editor.timeout = self.autologout();
+        editor.columns = self.window.map_or(0, |size| usize::from(size.columns));
+        let result = editor.read_line(prompt, &self.history.entries, self);
+        self.editor = editor;
+        result
+    }
+
+    /// Checks whether rc files should be read: `-f` disables them and
+    /// a command string is run without them unless a login shell is requested.
+    pub fn reads_rc(&self) -> bool {
+        !self.options.fast && (self.options.command.is_none() || self.is_login)
+    }
+
+    /// Gets the user rc file: the one given with `-r`, else `$RSHRC`, else ~/.cshrc
+    fn user_rc(&self) -> PathBuf {
+        let path = self
+            .options
+            .rc
+            .clone()
+            .or_else(|| var("RSHRC").ok().filter(|path| !path.is_empty()));
+        match path {
+            Some(path) => self.cwd.join(path),
+            None => self.home.join(".cshrc"),
+        }
+    }
+
+    /// Reads initial scripts, stops if one of them calls `exit`.
+    /// Every shell reads /etc/csh.cshrc, then a login shell reads /etc/csh.login,
+    /// then every shell reads the user rc file (see `user_rc`)
+    /// and a login shell reads ~/.login at last.
+    pub fn on_start(&mut self) -> Result<Flow> {
+        if self.interpret_checked(Path::new(SYSTEM_CSHRC))? == Flow::Exit {
+            return Ok(Flow::Exit);
+        }
+        if self.is_login && self.interpret_checked(Path::new(SYSTEM_LOGIN))? == Flow::Exit {
+            return Ok(Flow::Exit);
+        }
+        if self.interpret_checked(&self.user_rc())? == Flow::Exit {
+            return Ok(Flow::Exit);
+        }
         if self.is_login {
-            self.interpret(&PathBuf::from("/etc/.login"))?;
-            self.interpret_rc(".cshrc")?;
-            self.interpret_rc(".login")?;
+            self.interpret_rc(".login")
         } else {
-            self.interpret_rc(".cshrc")?;
+            Ok(Flow::Continue)
         }
-        Ok(())
     }
 
-    /// Iterates over arguments given to the shell
+    /// Reads /etc/csh.logout and ~/.logout when a login shell exits
+    pub fn on_logout(&mut self) -> Result<Flow> {
+        if self.interpret_checked(Path::new(SYSTEM_LOGOUT))? == Flow::Exit {
+            return Ok(Flow::Exit);
+        }
+        self.interpret_rc(".logout")
+    }
+
+    /// Runs the command string given with `-c` or the script, `-` stands for the standard
+    /// input. Their arguments are in `$argv`.
     pub fn handle_arguments(&mut self) -> Result<()> {
-        let args: Vec<String> = self.argv.iter().skip(1).cloned().collect();
-        for arg in args {
-            if arg == "-" {
-                self.interact()?;
-            } else if arg.starts_with("-") {
-                continue;
-            } else {
-                self.interpret(&PathBuf::from(arg))?;
+        if let Some(command) = self.options.command.clone() {
+            for line in command.lines() {
+                if self.parse(line)? == Flow::Exit {
+                    break;
+                }
             }
+            return Ok(());
         }
-        Ok(())
+        match self.options.script.clone() {
+            Some(ref script) if script == "-" => self.interact(),
+            Some(script) => self.interpret(&PathBuf::from(script)).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits the `#!` line into the interpreter and its optional argument,
+/// everything after the interpreter is a single argument like in Linux
+fn parse_shebang(header: &str) -> Option<(&str, Option<&str>)> {
+    let line = header.strip_prefix("#!")?.trim();
+    match line.split_once(|c: char| c.is_ascii_whitespace()) {
+        Some((interpreter, argument)) => Some((interpreter, Some(argument.trim()))),
+        None if line.is_empty() => None,
+        None => Some((line, None)),
+    }
+}
+
+/// Checks whether the interpreter of a script is this very shell, then it runs the script
+/// itself. Another program named rsh isn't, it may be a different version.
+fn is_own_interpreter(interpreter: &str) -> bool {
+    match (Path::new(interpreter).canonicalize(), std::env::current_exe()) {
+        (Ok(interpreter), Ok(own)) => interpreter == own,
+        _ => false,
     }
 }
 
+/// Splits a `/dev/tcp/host/port` target into the host and the port
+#[cfg(feature = "net")]
+fn tcp_address(target: &str) -> Option<(&str, &str)> {
+    target
+        .strip_prefix("/dev/tcp/")?
+        .split_once('/')
+        .filter(|(host, port)| !host.is_empty() && !port.is_empty() && !port.contains('/'))
+}
+
+/// Connects to the address if the target is a `/dev/tcp/host/port` one.
+/// Returns None for other targets, so they are opened as files.
+#[cfg(feature = "net")]
+fn connect_tcp_target(target: &str) -> Option<Result<RawFd>> {
+    let (host, port) = tcp_address(target)?;
+    Some(connect_tcp(host, port).map_err(|reason| reason.about(target)))
+}
+
+/// Without the `net` feature `/dev/tcp` paths are opened like other files
+#[cfg(not(feature = "net"))]
+fn connect_tcp_target(_: &str) -> Option<Result<RawFd>> {
+    None
+}
+
 /// Gets text for prompt from the system
 fn get_prompt(user: UserId) -> String {
     let hostname = get_hostname().unwrap_or(String::from("hostname"));
@@ -274,60 +914,154 @@ fn get_prompt(user: UserId) -> String {
 }
 
 /// Checks whether the file is readable and either is owned by the current user
-/// or the current user's real group ID matches the file's group ID
-fn check_file(path: &PathBuf) -> Result<bool> {
-    let file_uid: UserId = get_file_uid(&path)?;
-    let file_gid: GroupId = get_file_gid(&path)?;
+/// or the current user's real group ID matches the file's group ID.
+/// Files owned by root which everyone may read, like the system-wide rc files, are trusted too.
+fn check_file(path: &Path) -> Result<bool> {
+    let file_uid: UserId = get_file_uid(path)?;
+    let file_gid: GroupId = get_file_gid(path)?;
     let user_uid: UserId = get_uid();
     let user_gid: GroupId = get_gid();
-    let mode = get_file_mode(&path)?;
+    let mode = get_file_mode(path)?;
     let can_user_read = mode & 0o400 != 0;
     let can_group_read = mode & 0o040 != 0;
-    Ok(
-        (user_uid == file_uid && can_user_read) || (user_gid == file_gid && can_group_read),
-    )
+    let can_others_read = mode & 0o004 != 0;
+    Ok((user_uid == file_uid && can_user_read)
+        || (user_gid == file_gid && can_group_read)
+        || (file_uid == 0 && can_others_read))
 }
 
 #[cfg(test)]
 mod tests {
+    use native::test_dir::TestDir;
+
     use super::*;
 
+    #[cfg(feature = "net")]
+    #[test]
+    fn tcp_targets() {
+        assert_eq!(tcp_address("/dev/tcp/localhost/80"), Some(("localhost", "80")));
+        assert_eq!(tcp_address("/dev/tcp/localhost/"), None);
+        assert_eq!(tcp_address("/dev/tcp/a/b/c"), None);
+        assert_eq!(tcp_address("/dev/null"), None);
+    }
+
+    #[test]
+    fn shebang_lines() {
+        assert_eq!(parse_shebang("#!/bin/sh"), Some(("/bin/sh", None)));
+        let env = parse_shebang("#! /usr/bin/env python3 -u");
+        assert_eq!(env, Some(("/usr/bin/env", Some("python3 -u"))));
+        assert_eq!(parse_shebang("#!"), None);
+        assert_eq!(parse_shebang("# comment"), None);
+        let own = std::env::current_exe().unwrap().display().to_string();
+        assert!(is_own_interpreter(&own));
+        assert!(!is_own_interpreter("/usr/local/bin/rsh"));
+        assert!(!is_own_interpreter("/bin/sh"));
+    }
+
+    #[test]
+    fn script_errors_are_located() {
+        let dir = TestDir::new("located");
+        let path = dir.join("script.rsh");
+        std::fs::write(&path, "set x = 1\nset y = (a\n").unwrap();
+        let mut shell = Shell::new().unwrap();
+        let reason = shell.interpret(&path).unwrap_err();
+        let file = path.display().to_string();
+        assert_eq!(reason.location(), Some((file.as_str(), 2)));
+        assert_eq!(shell.variables["x"], "1");
+    }
+
+    #[test]
+    fn find_path_skips_non_executables_and_directories() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = TestDir::new("path");
+        let dirs = [root.join("first"), root.join("second")];
+        for dir in &dirs {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("tool"), "").unwrap();
+        }
+        let executable = dirs[1].join("tool");
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let directory = root.join("directory");
+        std::fs::create_dir_all(directory.join("tool")).unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.path = vec![directory, dirs[0].clone(), dirs[1].clone()];
+        assert_eq!(shell.find_path("tool"), Some(executable));
+    }
+
+    #[test]
+    fn create_mode_from_variable() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(shell.create_mode().unwrap(), 0o666);
+        shell.variables.insert(String::from("redirmode"), String::from("0640"));
+        assert_eq!(shell.create_mode().unwrap(), 0o640);
+        shell.variables.insert(String::from("redirmode"), String::from("rw"));
+        assert!(shell.create_mode().is_err());
+    }
+
+    #[test]
+    fn builtin_redirections_are_undone() {
+        let dir = TestDir::new("builtin");
+        let path = dir.join("output");
+        let target = path.display().to_string();
+        // descriptors far from those opened by other tests
+        let redirections = [
+            Redirection::parse("200>", Some(&target)).unwrap(),
+            Redirection::parse("201>&", Some("200")).unwrap(),
+        ];
+        let mut shell = Shell::new().unwrap();
+        let result = shell.with_redirections(&redirections, |_| {
+            write_to_file(200, "a")?;
+            write_to_file(201, "b")
+        });
+        let written = std::fs::read_to_string(&path).unwrap();
+        result.unwrap();
+        assert_eq!(written, "ab");
+        assert!(write_to_file(200, "c").is_err());
+        assert!(write_to_file(201, "c").is_err());
+        let missing = [Redirection::parse("200<", Some("/nonexistent")).unwrap()];
+        assert!(shell.with_redirections(&missing, |_| Ok(())).is_err());
+        assert!(write_to_file(200, "c").is_err());
+    }
+
+    #[test]
+    fn parse_exit_stops_reading() {
+        let mut shell = Shell::new().unwrap();
+        assert_eq!(shell.parse("set x = 1").unwrap(), Flow::Continue);
+        assert_eq!(shell.parse("exit 3").unwrap(), Flow::Exit);
+        assert_eq!(shell.status, 3);
+    }
+
+    fn is_login(args: &[&str]) -> bool {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Shell::is_login(&args, &Options::parse(&args).unwrap())
+    }
+
     #[test]
     fn is_login_regular() {
-        let args: Vec<String> = vec!["rsh", "hello.rsh"]
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-        assert_eq!(Shell::is_login(&args), false);
+        assert!(!is_login(&["rsh", "hello.rsh"]));
+        assert!(!is_login(&["/bin/rsh"]));
     }
 
     #[test]
     fn is_login_minus_and_arg() {
-        let args = vec!["-rsh", "hello.rsh"]
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-        assert_eq!(Shell::is_login(&args), false);
+        assert!(is_login(&["-rsh", "hello.rsh"]));
     }
 
     #[test]
     fn is_login_minus_no_args() {
-        let args = vec!["-rsh"].iter().map(|s| s.to_string()).collect();
-        assert_eq!(Shell::is_login(&args), true);
+        assert!(is_login(&["-rsh"]));
+        assert!(is_login(&["/bin/-rsh"]));
     }
 
     #[test]
     fn is_login_argument_login() {
-        let args = vec!["rsh", "-l"].iter().map(|s| s.to_string()).collect();
-        assert_eq!(Shell::is_login(&args), true);
+        assert!(is_login(&["rsh", "-l"]));
+        assert!(is_login(&["rsh", "--login"]));
     }
 
     #[test]
     fn is_login_argument_login_and_another() {
-        let args = vec!["rsh", "-l", "hello.rsh"]
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-        assert_eq!(Shell::is_login(&args), false);
+        assert!(is_login(&["rsh", "-l", "hello.rsh"]));
+        assert!(is_login(&["rsh", "-fl", "-c", "pwd"]));
     }
 }
@@ -0,0 +1,174 @@
+use native::error::*;
+
+/// Changes words of a history event or a variable, written after a colon like `:h` or `:s/l/r/`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Modifier {
+    /// `h`: removes the last path component
+    Head,
+    /// `t`: keeps the last path component
+    Tail,
+    /// `r`: removes the extension
+    Root,
+    /// `e`: keeps the extension
+    Extension,
+    /// `s/old/new/`: replaces the first occurrence, `&` in the new text stands for the old one.
+    /// An empty old text means the one of the previous substitution.
+    Substitute(String, String),
+    /// `&`: repeats the previous substitution
+    Repeat,
+    /// `p`: prints the command instead of running it, only for history events
+    Print,
+}
+
+/// A modifier and whether it is applied to every word, `g` before it
+pub type Modification = (Modifier, bool);
+
+/// Reads the modifiers at the start of the text like `:h:gs/a/b/`, returns them and the length
+/// of their text. A colon not followed by a modifier ends them, so `$host:8080` keeps `:8080`.
+/// The closing delimiter of the last substitution may be omitted.
+pub fn parse_modifiers(text: &str) -> (Vec<Modification>, usize) {
+    let mut modifiers = Vec::new();
+    let mut length = 0;
+    while let Some(rest) = text[length..].strip_prefix(':') {
+        let (global, rest) = match rest.strip_prefix('g') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let mut chars = rest.chars();
+        let modifier = match chars.next() {
+            Some('h') => Modifier::Head,
+            Some('t') => Modifier::Tail,
+            Some('r') => Modifier::Root,
+            Some('e') => Modifier::Extension,
+            Some('&') => Modifier::Repeat,
+            Some('p') => Modifier::Print,
+            Some('s') => match chars.next() {
+                Some(delimiter) if !delimiter.is_whitespace() => {
+                    let (old, rest) = until_delimiter(chars.as_str(), delimiter);
+                    let (new, rest) = until_delimiter(rest, delimiter);
+                    chars = rest.chars();
+                    Modifier::Substitute(old, new)
+                }
+                _ => break,
+            },
+            _ => break,
+        };
+        modifiers.push((modifier, global));
+        length = text.len() - chars.as_str().len();
+    }
+    (modifiers, length)
+}
+
+/// Splits the text at the delimiter which isn't escaped with a backslash, the delimiter is
+/// skipped. Without one the whole text is taken.
+fn until_delimiter(text: &str, delimiter: char) -> (String, &str) {
+    let mut result = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c == delimiter {
+            return (result, &text[(index + c.len_utf8())..]);
+        }
+        if c == '\\' {
+            if let Some((_, escaped)) = chars.clone().next().filter(|(_, next)| *next == delimiter)
+            {
+                chars.next();
+                result.push(escaped);
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    (result, "")
+}
+
+/// Applies the modifiers to the words in order. Without `g` a modifier changes only the first
+/// word it applies to. `last` is the previous substitution, it is updated by `:s`.
+/// A substitution which finds nothing is an error.
+pub fn modify(
+    mut words: Vec<String>,
+    modifiers: &[Modification],
+    last: &mut Option<(String, String)>,
+) -> Result<Vec<String>> {
+    for (modifier, global) in modifiers {
+        let (old, new) = match modifier {
+            Modifier::Print => continue,
+            Modifier::Substitute(old, new) => {
+                let old = if old.is_empty() {
+                    last.as_ref().map(|(old, _)| old.clone()).ok_or(Error::ModifierFailed)?
+                } else {
+                    old.clone()
+                };
+                *last = Some((old.clone(), new.replace('&', &old)));
+                last.clone().unwrap_or_default()
+            }
+            Modifier::Repeat => last.clone().ok_or(Error::ModifierFailed)?,
+            _ => (String::new(), String::new()),
+        };
+        let mut changed = false;
+        for word in words.iter_mut() {
+            let result = match modifier {
+                Modifier::Head => word.rfind('/').map(|index| String::from(&word[..index])),
+                Modifier::Tail => word.rfind('/').map(|index| String::from(&word[(index + 1)..])),
+                Modifier::Root => extension_start(word).map(|index| String::from(&word[..index])),
+                Modifier::Extension => {
+                    extension_start(word).map(|index| String::from(&word[(index + 1)..]))
+                }
+                _ => word.find(old.as_str()).map(|_| word.replacen(old.as_str(), &new, 1)),
+            };
+            if let Some(result) = result {
+                *word = result;
+                changed = true;
+                if !global {
+                    break;
+                }
+            }
+        }
+        let substitution = matches!(modifier, Modifier::Substitute(..) | Modifier::Repeat);
+        if substitution && !changed {
+            return Err(Error::ModifierFailed);
+        }
+    }
+    Ok(words)
+}
+
+/// Finds the dot starting the extension of the last path component
+fn extension_start(word: &str) -> Option<usize> {
+    let name_start = word.rfind('/').map_or(0, |index| index + 1);
+    word[name_start..].rfind('.').map(|index| name_start + index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(words: &str, modifiers: &str) -> Result<String> {
+        let words = words.split_whitespace().map(String::from).collect();
+        let (modifiers, length) = parse_modifiers(modifiers);
+        assert!(length > 0);
+        Ok(modify(words, &modifiers, &mut None)?.join(" "))
+    }
+
+    #[test]
+    fn path_modifiers() {
+        assert_eq!(apply("/usr/lib/libc.so.6", ":h").unwrap(), "/usr/lib");
+        assert_eq!(apply("/usr/lib/libc.so.6", ":t:r").unwrap(), "libc.so");
+        assert_eq!(apply("a/b.c d/e.f", ":e").unwrap(), "c d/e.f");
+        assert_eq!(apply("a/b.c d/e.f", ":ge").unwrap(), "c f");
+        assert_eq!(apply("x a/b", ":h").unwrap(), "x a");
+    }
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(apply("cat foo foo.c", ":s/foo/bar/").unwrap(), "cat bar foo.c");
+        assert_eq!(apply("cat foo foo.c", ":gs/foo/&2").unwrap(), "cat foo2 foo2.c");
+        assert_eq!(apply("a/b", ":s|/|\\|x|").unwrap(), "a|xb");
+        assert!(apply("cat foo", ":s/bar/baz/").is_err());
+        assert_eq!(parse_modifiers(":8080"), (vec![], 0));
+        let (modifiers, length) = parse_modifiers(":s/a/b/:& rest");
+        assert_eq!(length, 9);
+        let mut last = None;
+        let words = vec![String::from("aa")];
+        assert_eq!(modify(words, &modifiers, &mut last).unwrap(), vec!["bb"]);
+        assert_eq!(last, Some((String::from("a"), String::from("b"))));
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use libc::O_RDONLY;
+
+use native::error::*;
+use native::{close_file, open_file, read_file, write_to_file};
+
+use super::Shell;
+
+/// The message of the day shown by login shells
+const MOTD: &str = "/etc/motd";
+/// A file in the home directory which makes logins quiet like login(1) does
+const HUSH_LOGIN: &str = ".hushlogin";
+
+impl Shell {
+    /// Prints what a login shell shows before its first prompt, see `greeting`
+    pub fn greet(&self) -> Result<()> {
+        write_to_file(1, &self.greeting(Path::new(MOTD)))?;
+        Ok(())
+    }
+
+    /// Gets the message of the day and the banner after it: `rsh` and its version,
+    /// the value of `banner` if it's set, nothing if it's empty.
+    /// Nothing is shown at all if `nomotd` is set or ~/.hushlogin exists.
+    fn greeting(&self, motd: &Path) -> String {
+        if self.variables.contains_key("nomotd") || self.home.join(HUSH_LOGIN).exists() {
+            return String::new();
+        }
+        // a missing or unreadable file is no message
+        let mut text = open_file(motd, O_RDONLY, None)
+            .and_then(|fd| {
+                let text = read_file(fd);
+                close_file(fd)?;
+                text
+            })
+            .unwrap_or_default();
+        let banner = match self.variables.get("banner") {
+            Some(banner) => banner.clone(),
+            None => format!("rsh {}", env!("CARGO_PKG_VERSION")),
+        };
+        if !banner.is_empty() {
+            text.push_str(&banner);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn greeting_is_configurable() {
+        let dir = TestDir::new("motd");
+        let motd = dir.join("motd");
+        std::fs::write(&motd, "Maintenance at 5pm\n").unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.home = dir.path().to_path_buf();
+        shell.variables.insert(String::from("banner"), String::from("Welcome"));
+        assert_eq!(shell.greeting(&motd), "Maintenance at 5pm\nWelcome\n");
+        shell.variables.insert(String::from("banner"), String::new());
+        assert_eq!(shell.greeting(&dir.join("missing")), "");
+        shell.variables.remove("banner");
+        let banner = format!("\nrsh {}\n", env!("CARGO_PKG_VERSION"));
+        assert!(shell.greeting(&motd).ends_with(&banner));
+        shell.variables.insert(String::from("nomotd"), String::new());
+        assert_eq!(shell.greeting(&motd), "");
+        shell.variables.remove("nomotd");
+        std::fs::write(dir.join(HUSH_LOGIN), "").unwrap();
+        assert_eq!(shell.greeting(&motd), "");
+    }
+}
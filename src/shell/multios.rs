@@ -0,0 +1,264 @@
+use std::os::unix::io::RawFd;
+use std::process::exit;
+use std::time::Instant;
+
+use libc::EPIPE;
+
+use native::error::*;
+use native::term::is_terminal;
+use native::{close_file, create_pipe, duplicate_fd, fork_current, read_chunk, replace_fdi,
+             wait_process, write_all, write_to_file};
+
+use super::guard::{ask_to_continue, Guard, Watch};
+
+/// Size of the buffer used to copy data between the command and its targets
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Redirections of one descriptor of the command. The first target replaces the descriptor
+/// as usual, the others are kept open until the command is started.
+pub struct Multio {
+    fd: RawFd,
+    input: bool,
+    others: Vec<RawFd>,
+    guard: Option<Guard>,
+}
+
+/// What happens to the output of a foreground command which goes to the terminal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TerminalOutput {
+    /// The file of `sessionlog` which gets a copy of stdout and stderr
+    pub log: Option<RawFd>,
+    /// Limits of stdout, the user is asked whether to go on when they are exceeded
+    pub guard: Option<Guard>,
+}
+
+/// Redirects the descriptor of the command to the opened target and closes the latter.
+/// With `multios` set, another target of an already redirected descriptor is added to it:
+/// outputs are copied to every target and inputs are read one after another.
+pub fn redirect(
+    multios: &mut Vec<Multio>,
+    enabled: bool,
+    fd: RawFd,
+    target: RawFd,
+    input: bool,
+) -> Result<()> {
+    if let Some(multio) = multios.iter_mut().find(|multio| multio.fd == fd) {
+        if enabled && multio.input == input {
+            multio.others.push(target);
+            return Ok(());
+        }
+        // the direction has changed, so the descriptor starts over
+        for other in multio.others.drain(..) {
+            close_file(other)?;
+        }
+        multio.input = input;
+    } else {
+        multios.push(Multio {
+            fd,
+            input,
+            others: Vec::new(),
+            guard: None,
+        });
+    }
+    // the target may have got the number of a closed descriptor, then it is already in place
+    if target == fd {
+        return Ok(());
+    }
+    replace_fdi(fd, target)?;
+    close_file(target)
+}
+
+/// Passes the output which the command writes to the terminal through a copier
+/// which writes it to the session log too and pauses stdout if it's too long
+pub fn watch_terminal(multios: &mut Vec<Multio>, terminal: TerminalOutput) -> Result<()> {
+    for fd in 1..=2 {
+        let guard = if fd == 1 { terminal.guard } else { None };
+        if !is_terminal(fd) || (terminal.log.is_none() && guard.is_none()) {
+            continue;
+        }
+        let index = match multios.iter().position(|multio| multio.fd == fd) {
+            Some(index) => index,
+            None => {
+                multios.push(Multio {
+                    fd,
+                    input: false,
+                    others: Vec::new(),
+                    guard: None,
+                });
+                multios.len() - 1
+            }
+        };
+        if let Some(log) = terminal.log {
+            multios[index].others.push(duplicate_fd(log)?);
+        }
+        multios[index].guard = guard;
+    }
+    Ok(())
+}
+
+/// A pipe between the command and the process copying the data of the descriptor
+struct Plumbing {
+    fd: RawFd,
+    command_end: RawFd,
+    copier_end: RawFd,
+    targets: Vec<RawFd>,
+    input: bool,
+    guard: Option<Guard>,
+}
+
+/// Connects descriptors with several targets to pipes and forks a copier for each of them.
+/// Returns in the process which is going to execute the command. The current process waits
+/// for the command and the copiers and exits with the status of the command, so all the output
+/// has been written when the shell sees the command finished.
+pub fn join(multios: Vec<Multio>) -> Result<()> {
+    let mut plumbing = Vec::new();
+    let copied = |multio: &Multio| !multio.others.is_empty() || multio.guard.is_some();
+    for multio in multios.into_iter().filter(copied) {
+        let (read_end, write_end) = create_pipe()?;
+        let (command_end, copier_end) = if multio.input {
+            (read_end, write_end)
+        } else {
+            (write_end, read_end)
+        };
+        let mut targets = vec![duplicate_fd(multio.fd)?];
+        targets.extend(multio.others);
+        plumbing.push(Plumbing {
+            fd: multio.fd,
+            command_end,
+            copier_end,
+            targets,
+            input: multio.input,
+            guard: multio.guard,
+        });
+    }
+    if plumbing.is_empty() {
+        return Ok(());
+    }
+    let mut copiers = Vec::with_capacity(plumbing.len());
+    for index in 0..plumbing.len() {
+        let pid = fork_current()?;
+        if pid == 0 {
+            for (other, pipe) in plumbing.iter().enumerate() {
+                close_file(pipe.command_end)?;
+                if other != index {
+                    close_file(pipe.copier_end)?;
+                }
+            }
+            let pipe = &plumbing[index];
+            let result = if pipe.input {
+                concatenate(&pipe.targets, pipe.copier_end)
+            } else {
+                spread(pipe.copier_end, pipe.targets.clone(), pipe.guard)
+            };
+            match result {
+                Ok(()) => exit(0),
+                Err(reason) => {
+                    write_to_file(2, &reason.diagnostic()).ok();
+                    exit(1);
+                }
+            }
+        }
+        copiers.push(pid);
+    }
+    let command = fork_current()?;
+    for pipe in &plumbing {
+        if command == 0 {
+            replace_fdi(pipe.fd, pipe.command_end)?;
+        }
+        close_file(pipe.command_end)?;
+        close_file(pipe.copier_end)?;
+        for target in &pipe.targets {
+            close_file(*target)?;
+        }
+    }
+    if command == 0 {
+        return Ok(());
+    }
+    let status = wait_process(command)?;
+    for copier in copiers {
+        wait_process(copier)?;
+    }
+    exit(status);
+}
+
+/// Copies the output of the command to every target like tee(1).
+/// A target which fails is dropped, the others still get the data.
+/// Failures are reported unless the reader of a pipe has just gone.
+/// With the guard, long output waits for the user who may kill the job instead.
+fn spread(source: RawFd, mut targets: Vec<RawFd>, guard: Option<Guard>) -> Result<()> {
+    let mut buf = vec![0; COPY_BUFFER_SIZE];
+    let mut watch = guard.map(Watch::new);
+    loop {
+        let count = read_chunk(source, &mut buf)?;
+        if count == 0 {
+            return Ok(());
+        }
+        if let Some(ref mut watch) = watch {
+            if watch.exceeded(count) {
+                if !ask_to_continue()? {
+                    return Ok(());
+                }
+                watch.restart(Instant::now());
+            }
+        }
+        targets.retain(|target| match write_all(*target, &buf[..count]) {
+            Ok(()) => true,
+            Err(ref reason) if reason.is_errno(EPIPE) => false,
+            Err(reason) => {
+                write_to_file(2, &reason.diagnostic()).ok();
+                false
+            }
+        });
+    }
+}
+
+/// Feeds the sources to the command one after another like cat(1).
+/// Stops quietly if the command doesn't read everything and exits.
+fn concatenate(sources: &[RawFd], target: RawFd) -> Result<()> {
+    let mut buf = vec![0; COPY_BUFFER_SIZE];
+    for source in sources {
+        loop {
+            let count = read_chunk(*source, &mut buf)?;
+            if count == 0 {
+                break;
+            }
+            match write_all(target, &buf[..count]) {
+                Err(ref reason) if reason.is_errno(EPIPE) => return Ok(()),
+                result => result?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native::read_file;
+
+    #[test]
+    fn copy_loops() {
+        let (input, command) = create_pipe().unwrap();
+        let (first, first_target) = create_pipe().unwrap();
+        let (second, second_target) = create_pipe().unwrap();
+        let sources: Vec<RawFd> = ["a", "b"]
+            .iter()
+            .map(|text| {
+                let (source, write_end) = create_pipe().unwrap();
+                write_all(write_end, text.as_bytes()).unwrap();
+                close_file(write_end).unwrap();
+                source
+            })
+            .collect();
+        concatenate(&sources, command).unwrap();
+        close_file(command).unwrap();
+        spread(input, vec![first_target, second_target], None).unwrap();
+        close_file(first_target).unwrap();
+        close_file(second_target).unwrap();
+        assert_eq!(read_file(first).unwrap(), "ab");
+        assert_eq!(read_file(second).unwrap(), "ab");
+        for fd in sources.iter().chain(&[input, first, second]) {
+            close_file(*fd).unwrap();
+        }
+    }
+}
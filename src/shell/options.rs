@@ -0,0 +1,147 @@
+use native::error::*;
+
+/// Command line flags which change the behaviour of the shell
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Options {
+    /// The command string given with `-c`
+    pub command: Option<String>,
+    /// The rc file given with `-r` which replaces ~/.cshrc
+    pub rc: Option<String>,
+    /// Whether `-l` or `--login` was given
+    pub login: bool,
+    /// Whether `-f` was given to skip rc files
+    pub fast: bool,
+    /// Whether `-x` was given to print commands before execution
+    pub echo: bool,
+    /// Whether `-v` was given to print input lines
+    pub verbose: bool,
+    /// Whether `-n` was given to parse commands without executing them
+    pub noexec: bool,
+    /// Whether `--deterministic` was given
+    pub deterministic: bool,
+    /// The script to interpret, `-` stands for the standard input
+    pub script: Option<String>,
+    /// Arguments after the script or the command of `-c`, they become `$argv`
+    pub arguments: Vec<String>,
+}
+
+impl Options {
+    /// Collects the flags from the arguments, the first one is the name of the shell.
+    /// Single letter flags can be combined like `-xv`. Flags end at the first other argument,
+    /// which is the script unless `-c` was given, the rest are arguments of the script.
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Options::default();
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--deterministic" {
+                options.deterministic = true;
+                continue;
+            }
+            if arg == "--login" {
+                options.login = true;
+                continue;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                if options.command.is_none() {
+                    options.script = Some(arg.to_owned());
+                } else {
+                    options.arguments.push(arg.to_owned());
+                }
+                options.arguments.extend(args.cloned());
+                break;
+            }
+            if arg.starts_with("--") {
+                continue;
+            }
+            for flag in arg.chars().skip(1) {
+                match flag {
+                    'c' => {
+                        let command = args.next().ok_or(Error::MissingArgument("-c"))?;
+                        options.command = Some(command.to_owned());
+                    }
+                    'r' => {
+                        let rc = args.next().ok_or(Error::MissingArgument("-r"))?;
+                        options.rc = Some(rc.to_owned());
+                    }
+                    'l' => options.login = true,
+                    'f' => options.fast = true,
+                    'x' => options.echo = true,
+                    'v' => options.verbose = true,
+                    'n' => options.noexec = true,
+                    _ => continue,
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parse_command() {
+        let options = parse(&["rsh", "-c", "ls -l", "-l"]).unwrap();
+        assert_eq!(options.command, Some(String::from("ls -l")));
+        assert!(options.login);
+        assert!(!options.deterministic);
+    }
+
+    #[test]
+    fn parse_command_missing() {
+        assert!(parse(&["rsh", "-c"]).is_err());
+    }
+
+    #[test]
+    fn parse_combined_flags() {
+        let options = parse(&["rsh", "-xvn", "script.rsh"]).unwrap();
+        assert!(options.echo);
+        assert!(options.verbose);
+        assert!(options.noexec);
+        let options = parse(&["rsh", "-xc", "pwd"]).unwrap();
+        assert!(options.echo);
+        assert_eq!(options.command, Some(String::from("pwd")));
+    }
+
+    #[test]
+    fn parse_rc() {
+        let options = parse(&["rsh", "-r", "test.cshrc", "-l", "script.rsh"]).unwrap();
+        assert_eq!(options.rc, Some(String::from("test.cshrc")));
+        assert_eq!(options.script, Some(String::from("script.rsh")));
+        assert!(options.login);
+        assert!(parse(&["rsh", "-r"]).is_err());
+    }
+
+    #[test]
+    fn parse_long_login() {
+        let options = parse(&["rsh", "--login", "script.rsh"]).unwrap();
+        assert!(options.login);
+    }
+
+    #[test]
+    fn parse_script_arguments() {
+        let options = parse(&["rsh", "-f", "script.rsh", "a", "-x", "--login"]).unwrap();
+        assert_eq!(options.script, Some(String::from("script.rsh")));
+        assert_eq!(options.arguments, vec!["a", "-x", "--login"]);
+        assert!(!options.echo);
+        assert!(!options.login);
+        let options = parse(&["rsh", "-c", "shift", "-l", "a", "b"]).unwrap();
+        assert_eq!(options.script, None);
+        assert_eq!(options.arguments, vec!["a", "b"]);
+        assert!(options.login);
+    }
+
+    #[test]
+    fn parse_script() {
+        let options = parse(&["rsh", "--deterministic", "-f", "script.rsh"]).unwrap();
+        assert_eq!(options.command, None);
+        assert!(options.deterministic);
+        assert!(options.fast);
+    }
+}
@@ -0,0 +1,214 @@
+use std::env::vars;
+use std::os::unix::io::RawFd;
+
+use native::error::*;
+use native::term::set_terminal_group;
+use native::{close_file, create_pipe, get_process_group, replace_fdi, set_process_group,
+             start_process};
+
+use super::ast::Group;
+use super::debug::Debug;
+use super::multios::TerminalOutput;
+use super::redirect::Redirection;
+use super::{Flow, Shell};
+
+/// An expanded command of the pipeline, see `Shell::expand_command`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stage {
+    /// `NAME=value` added to the environment of the command
+    pub assignments: Vec<String>,
+    /// The command and its arguments
+    pub words: Vec<String>,
+    /// `( list )` which runs in a subshell instead of a command
+    pub group: Option<Group>,
+    pub redirections: Vec<Redirection>,
+    /// The stderr goes to the pipe too (`|&`)
+    pub with_stderr: bool,
+}
+
+impl Stage {
+    /// The environment of the shell with the assignments of the command
+    pub fn environment(&self) -> Vec<String> {
+        vars()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .chain(self.assignments.iter().cloned())
+            .collect()
+    }
+}
+
+/// Writes the commands like they are run, `ls -l > out | wc` for example
+pub fn pipeline_text(stages: &[Stage]) -> String {
+    let mut words = Vec::new();
+    for (index, stage) in stages.iter().enumerate() {
+        if let Some(group) = &stage.group {
+            words.push(format!("({})", group.text));
+        }
+        words.extend(stage.words.iter().cloned());
+        words.extend(stage.redirections.iter().map(Redirection::to_string));
+        if index + 1 < stages.len() {
+            words.push(String::from(if stage.with_stderr { "|&" } else { "|" }));
+        }
+    }
+    words.join(" ")
+}
+
+/// Checks whether the word separates commands of a pipeline
+pub fn is_pipe(word: &str) -> bool {
+    word == "|" || word == "|&"
+}
+
+impl Shell {
+    /// Runs commands connected with `|` or `|&` in parallel and waits for all of them.
+    /// A builtin or a group runs in a forked subshell, others are external programs.
+    /// The status is the one of the last command. A single command is a pipeline too.
+    /// A background pipeline and, with job control, a foreground one get their own process group.
+    /// The foreground group is given the terminal, background ones are added to the jobs table.
+    /// The terminal output of a foreground pipeline is copied to `sessionlog` if it's set
+    /// and paused when it's longer than `outputlimit` or `outputtime` allow.
+    pub fn run_pipeline(&mut self, stages: &[Stage], background: bool) -> Result<Flow> {
+        let text = pipeline_text(stages);
+        // looked up and copied before forking, so the hash table of the shell is updated
+        let mut subshells = Vec::with_capacity(stages.len());
+        let mut paths = Vec::with_capacity(stages.len());
+        for stage in stages {
+            let builtin = stage.group.is_none() && self.builtins.contains_key(&*stage.words[0]);
+            if stage.group.is_some() || builtin {
+                subshells.push(Some(self.subshell()));
+                paths.push(None);
+            } else {
+                subshells.push(None);
+                paths.push(self.hash_lookup(&stage.words[0]));
+            }
+        }
+        // the shell has already reported a program it couldn't find
+        let explained = subshells.last().is_some_and(Option::is_some)
+            || paths.last().is_some_and(Option::is_some);
+        let mut children = Vec::with_capacity(stages.len());
+        let mut input: Option<RawFd> = None;
+        let own_group = background || self.jobs.control;
+        let terminal = if background {
+            TerminalOutput::default()
+        } else {
+            TerminalOutput {
+                guard: self.output_guard()?,
+                log: self.open_session_log()?,
+            }
+        };
+        for (index, stage) in stages.iter().enumerate() {
+            let output = if index + 1 < stages.len() {
+                Some(create_pipe()?)
+            } else {
+                None
+            };
+            // the first process makes the group, the others join it
+            let group = children.first().cloned().unwrap_or(0);
+            let subshell = subshells[index].take();
+            let started = start_process(|| {
+                if own_group {
+                    if let Err(reason) = set_process_group(0, group) {
+                        return reason;
+                    }
+                }
+                if own_group && !background {
+                    // the parent does it too, but the program may read the terminal earlier
+                    if let Err(reason) = set_terminal_group(0, get_process_group()) {
+                        return reason;
+                    }
+                }
+                if let Err(reason) = connect(input, output, stage.with_stderr) {
+                    return reason;
+                }
+                if let Some(subshell) = subshell {
+                    subshell.run_stage(stage, terminal);
+                }
+                let (name, arguments) = stage.words.split_first().expect("commands have names");
+                let path = paths[index].as_deref();
+                let environment = stage.environment();
+                let redirections = &stage.redirections;
+                self.execute_command(name, path, arguments, redirections, environment, terminal)
+            });
+            if let Some(fd) = input {
+                close_file(fd)?;
+            }
+            if let Some((read_end, write_end)) = output {
+                close_file(write_end)?;
+                input = Some(read_end);
+            }
+            let pid = match started {
+                Ok(pid) => pid,
+                Err(reason) => {
+                    if let Some(fd) = terminal.log {
+                        close_file(fd)?;
+                    }
+                    return Err(reason);
+                }
+            };
+            if own_group {
+                // the child may have already executed the program, then it has joined by itself
+                set_process_group(pid, if group == 0 { pid } else { group }).ok();
+            }
+            let name = stage.words.first().map_or("(", String::as_str);
+            self.debug(Debug::Jobs, || format!("child {} started for {}", pid, name));
+            children.push(pid);
+        }
+        if let Some(fd) = terminal.log {
+            close_file(fd)?;
+        }
+        if background {
+            self.start_job(children, text)?;
+            self.status = 0;
+        } else {
+            let group = if own_group { children[0] } else { get_process_group() };
+            let status = self.wait_foreground(group, children, text)?;
+            if explained {
+                self.explain_exit(status)?;
+            }
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+/// Replaces stdin and stdout of the child with the ends of pipes and closes the originals
+fn connect(input: Option<RawFd>, output: Option<(RawFd, RawFd)>, with_stderr: bool) -> Result<()> {
+    if let Some(fd) = input {
+        replace_fdi(0, fd)?;
+        close_file(fd)?;
+    }
+    if let Some((read_end, write_end)) = output {
+        replace_fdi(1, write_end)?;
+        if with_stderr {
+            replace_fdi(2, write_end)?;
+        }
+        close_file(read_end)?;
+        close_file(write_end)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn pipelines_are_written() {
+        let stages = vec![
+            Stage {
+                words: words("ls -l"),
+                redirections: vec![Redirection::parse("2>", Some("errors")).unwrap()],
+                with_stderr: true,
+                ..Stage::default()
+            },
+            Stage {
+                assignments: words("LANG=C"),
+                words: words("wc"),
+                ..Stage::default()
+            },
+        ];
+        assert_eq!(pipeline_text(&stages), "ls -l 2> errors |& wc");
+        assert!(stages[1].environment().contains(&String::from("LANG=C")));
+    }
+}
@@ -0,0 +1,82 @@
+use native::error::*;
+use native::write_to_file;
+
+use super::Shell;
+
+impl Shell {
+    /// Prints the prompt followed by `rprompt` aligned to the right edge of the terminal
+    pub fn write_prompt(&self) -> Result<()> {
+        write_to_file(1, &self.prompt_text())?;
+        Ok(())
+    }
+
+    /// Builds the text of the prompt including `rprompt` if it is set.
+    /// An incognito shell marks the prompt, so the user knows nothing is recorded.
+    pub fn prompt_text(&self) -> String {
+        let mut prompt = match self.theme {
+            Some(theme) => self.render_theme(theme),
+            None => self.prompt.clone(),
+        };
+        if self.incognito {
+            prompt.insert_str(0, &msg!("(incognito) "));
+        }
+        match (self.variables.get("rprompt"), self.window) {
+            (Some(right), Some(size)) => render_rprompt(&prompt, right, usize::from(size.columns)),
+            _ => prompt,
+        }
+    }
+}
+
+/// Builds the text which prints the prompt, draws the right prompt in the last columns
+/// and returns the cursor back after the prompt. The right prompt is omitted if it doesn't fit.
+fn render_rprompt(prompt: &str, right: &str, columns: usize) -> String {
+    let used = display_width(prompt);
+    let width = display_width(right);
+    if width == 0 || used + width >= columns {
+        return String::from(prompt);
+    }
+    // save the cursor, move to the right edge, draw and restore the cursor
+    format!("{}\x1b7\x1b[{}C{}\x1b8", prompt, columns - used - width, right)
+}
+
+/// Counts characters which take place on the screen skipping escape sequences like colors
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += 1;
+        } else if chars.next() == Some('[') {
+            // a control sequence ends with a character from @ to ~
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_rprompt_fits() {
+        let text = render_rprompt("host% ", "~/src", 20);
+        assert_eq!(text, "host% \x1b7\x1b[9C~/src\x1b8");
+    }
+
+    #[test]
+    fn width_without_escapes() {
+        assert_eq!(display_width("\x1b[34mhost\x1b[39m% "), 6);
+        assert_eq!(display_width("\x1b7ab\x1b8"), 2);
+    }
+
+    #[test]
+    fn render_rprompt_too_wide() {
+        assert_eq!(render_rprompt("host% ", "~/src/rsh", 15), "host% ");
+        assert_eq!(render_rprompt("host% ", "", 80), "host% ");
+    }
+}
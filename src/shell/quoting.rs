@@ -0,0 +1,102 @@
+/// Characters which have a special meaning for the shell and have to be quoted
+const SPECIAL: &str = "'\"\\$`!#&|;<>()[]{}*?~^=";
+
+/// Quotes the word so that the lexer turns it back into exactly the same single word.
+/// Words without special characters are returned as is.
+pub fn quote_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && !word.contains(|c: char| c.is_whitespace() || c.is_control() || SPECIAL.contains(c));
+    if plain {
+        return String::from(word);
+    }
+    let mut result = String::with_capacity(word.len() + 2);
+    result.push('\'');
+    for c in word.chars() {
+        if c == '\'' {
+            // close the quotes, add an escaped quote and open them again
+            result.push_str("'\\''");
+        } else {
+            result.push(c);
+        }
+    }
+    result.push('\'');
+    result
+}
+
+/// Removes quotes and backslashes from the word.
+/// An unmatched quote is treated as if it was closed at the end of the word.
+pub fn unquote_word(word: &str) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => result.extend(chars.by_ref().take_while(|inner| *inner != c)),
+            '`' => {
+                result.push(c);
+                for inner in chars.by_ref() {
+                    result.push(inner);
+                    if inner == '`' {
+                        break;
+                    }
+                }
+            }
+            '\\' => result.push(chars.next().unwrap_or('\\')),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell::lexer::split_words;
+    use shell::random::Random;
+
+    /// Characters used to build random words, biased towards the troublesome ones
+    const ALPHABET: &[char] = &[
+        'a', 'Z', '0', '_', '-', '/', '.', ' ', '\t', '\'', '"', '\\', '$', '`', '#', '>', '(',
+        ')', '*', '~', '=', 'é', '→', '\u{7f}',
+    ];
+
+    fn random_word(random: &mut Random) -> String {
+        let len = random.next_u64() % 12;
+        (0..len)
+            .map(|_| ALPHABET[random.next_u64() as usize % ALPHABET.len()])
+            .collect()
+    }
+
+    #[test]
+    fn quote_plain_word() {
+        assert_eq!(quote_word("ls"), "ls");
+        assert_eq!(quote_word("/usr/bin/env"), "/usr/bin/env");
+    }
+
+    #[test]
+    fn quote_special_words() {
+        assert_eq!(quote_word(""), "''");
+        assert_eq!(quote_word("a b"), "'a b'");
+        assert_eq!(quote_word("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        let mut random = Random::new(1);
+        for _ in 0..10_000 {
+            let word = random_word(&mut random);
+            let quoted = quote_word(&word);
+            assert_eq!(split_words(&quoted).unwrap(), vec![word.clone()], "{}", quoted);
+            assert_eq!(unquote_word(&quoted), word, "{}", quoted);
+        }
+    }
+
+    #[test]
+    fn quote_round_trip_in_line() {
+        let mut random = Random::new(2);
+        for _ in 0..1_000 {
+            let words: Vec<String> = (0..4).map(|_| random_word(&mut random)).collect();
+            let line: Vec<String> = words.iter().map(|word| quote_word(word)).collect();
+            assert_eq!(split_words(&line.join(" ")).unwrap(), words);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use native::error::*;
+use native::write_to_file;
+
+use super::Shell;
+
+/// Seed used when the shell is started with `--deterministic`
+const DETERMINISTIC_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Xorshift pseudo random number generator. It is fast but MUST NOT be used for cryptography.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates the generator with the provided seed, zero is replaced since it is a fixed point
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { DETERMINISTIC_SEED } else { seed };
+        Random { state }
+    }
+
+    /// Creates the generator seeded with the current time and pid or with a fixed seed
+    pub fn with_seed_from_time(deterministic: bool) -> Self {
+        if deterministic {
+            return Random::new(DETERMINISTIC_SEED);
+        }
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() ^ (u64::from(time.subsec_nanos()) << 32))
+            .unwrap_or(0);
+        Random::new(nanos ^ u64::from(process::id()))
+    }
+
+    /// Generates the next number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Generates a number below the bound which must not be zero.
+    /// Numbers from the incomplete last range are skipped, so all results are equally likely.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Puts the items in a random order using Fisher-Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for last in (1..items.len()).rev() {
+            let other = self.below(last as u64 + 1) as usize;
+            items.swap(last, other);
+        }
+    }
+}
+
+/// Gets the words of the list, it may be put in parentheses like `(a b c)`
+fn word_list(words: &[&str]) -> Vec<String> {
+    let text = words.join(" ");
+    let text = text.trim();
+    let text = match text.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+        Some(inner) => inner,
+        None => text,
+    };
+    text.split_whitespace().map(String::from).collect()
+}
+
+impl Shell {
+    /// Implements `shuffle words`: prints the words in a random order
+    pub fn shuffle_builtin(&mut self, words: &[&str]) -> Result<()> {
+        let mut list = word_list(words);
+        self.random.shuffle(&mut list);
+        write_to_file(1, &format!("{}\n", list.join(" ")))?;
+        Ok(())
+    }
+
+    /// Implements `pick words`: prints one of the words chosen at random
+    pub fn pick_builtin(&mut self, words: &[&str]) -> Result<()> {
+        let list = word_list(words);
+        if list.is_empty() {
+            return Err(Error::MissingArgument("pick"));
+        }
+        let index = self.random.below(list.len() as u64) as usize;
+        write_to_file(1, &format!("{}\n", list[index]))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_sequence() {
+        let mut first = Random::with_seed_from_time(true);
+        let mut second = Random::with_seed_from_time(true);
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut random = Random::new(1);
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            seen[random.below(3) as usize] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+    }
+
+    #[test]
+    fn shuffle_keeps_items() {
+        let mut items: Vec<u32> = (0..10).collect();
+        Random::new(7).shuffle(&mut items);
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<u32>>());
+        let mut again: Vec<u32> = (0..10).collect();
+        Random::new(7).shuffle(&mut again);
+        assert_eq!(items, again);
+    }
+
+    #[test]
+    fn word_list_in_parentheses() {
+        assert_eq!(word_list(&["(a", "b", "c)"]), vec!["a", "b", "c"]);
+        assert_eq!(word_list(&["a", "b"]), vec!["a", "b"]);
+        assert!(word_list(&["()"]).is_empty());
+    }
+
+    #[test]
+    fn zero_seed_is_replaced() {
+        assert_ne!(Random::new(0).next_u64(), 0);
+    }
+
+    #[test]
+    fn pick_needs_words() {
+        let mut shell = Shell::new().unwrap();
+        assert!(matches!(shell.pick_builtin(&[]), Err(Error::MissingArgument("pick"))));
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::{Display, Formatter};
+use std::os::unix::io::RawFd;
+
+use native::error::*;
+
+/// What a redirection does with its descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// `< file` reads the file
+    Input(String),
+    /// `> file` truncates the file and `>> file` appends to it, `>&` and `>>&` send
+    /// stderr there too. The target may also be a socket, see `Shell::apply_redirections`.
+    Output {
+        target: String,
+        append: bool,
+        both: bool,
+    },
+    /// `N>&M` makes the descriptor a copy of another one
+    Duplicate(RawFd),
+}
+
+/// A redirection which is only recorded by the parent and applied in the forked child
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirection {
+    pub fd: RawFd,
+    pub kind: Kind,
+}
+
+impl Redirection {
+    /// Parses the expanded words of a redirection: the operator with an optional
+    /// descriptor number and the target, which may be a part of the operator word.
+    /// Nothing is opened, a malformed redirection is an error before anything runs.
+    pub fn parse(operator: &str, target: Option<&str>) -> Result<Redirection> {
+        let index = operator.find(['>', '<']).ok_or_else(|| Error::SyntaxError.about(operator))?;
+        let input = operator[index..].starts_with('<');
+        let fd = match index {
+            0 if input => 0,
+            0 => 1,
+            _ => operator[..index].parse().map_err(|_| Error::SyntaxError.about(operator))?,
+        };
+        let mut rest = &operator[index + 1..];
+        let append = !input && rest.starts_with('>');
+        if append {
+            rest = &rest[1..];
+        }
+        let both = !input && rest.starts_with('&');
+        if both {
+            rest = &rest[1..];
+        }
+        let target = match (rest, target) {
+            ("", Some(target)) => target,
+            ("", None) => return Err(Error::MissingRedirectName),
+            (rest, _) => rest,
+        };
+        let kind = if input {
+            Kind::Input(String::from(target))
+        } else if let (true, false, Ok(other)) = (both, append, target.parse()) {
+            Kind::Duplicate(other)
+        } else {
+            Kind::Output {
+                target: String::from(target),
+                append,
+                both,
+            }
+        };
+        Ok(Redirection { fd, kind })
+    }
+
+    /// Writes the redirection as the operator and the target
+    pub fn words(&self) -> Vec<String> {
+        match &self.kind {
+            Kind::Input(target) if self.fd == 0 => vec![String::from("<"), target.clone()],
+            Kind::Input(target) => vec![format!("{}<", self.fd), target.clone()],
+            Kind::Duplicate(other) => vec![format!("{}>&{}", self.fd, other)],
+            Kind::Output {
+                target,
+                append,
+                both,
+            } => {
+                let fd = if self.fd == 1 { String::new() } else { self.fd.to_string() };
+                let append = if *append { ">" } else { "" };
+                let both = if *both { "&" } else { "" };
+                vec![format!("{}>{}{}", fd, append, both), target.clone()]
+            }
+        }
+    }
+}
+
+impl Display for Redirection {
+    fn fmt(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        write!(formatter, "{}", self.words().join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(operator: &str, target: Option<&str>) -> String {
+        Redirection::parse(operator, target).unwrap().to_string()
+    }
+
+    #[test]
+    fn redirections_are_recorded() {
+        assert_eq!(parse(">", Some("out")), "> out");
+        assert_eq!(parse("2>>&log", None), "2>>& log");
+        assert_eq!(parse("2>&1", None), "2>&1");
+        assert_eq!(parse(">&", Some("all")), ">& all");
+        assert_eq!(parse("<in", None), "< in");
+        assert_eq!(parse("3<", Some("in")), "3< in");
+        let redirection = Redirection::parse(">&", Some("2")).unwrap();
+        assert_eq!(redirection.kind, Kind::Duplicate(2));
+        assert!(Redirection::parse(">", None).is_err());
+        assert!(Redirection::parse("99999999999>", Some("out")).is_err());
+    }
+}
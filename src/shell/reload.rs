@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::mem;
+use std::path::PathBuf;
+
+use native::error::*;
+use native::term::is_terminal;
+use native::watch::FileWatch;
+
+use super::guard::ask_key;
+use super::theme::Theme;
+use super::variables::Value;
+use super::{Flow, Shell, SYSTEM_CSHRC, SYSTEM_LOGIN};
+
+/// What the rc files have changed, so `reload` can undo it before reading them again
+#[derive(Debug, Default)]
+pub struct RcChanges {
+    /// Values of the variables before and after the rc files, None for an unset one
+    variables: HashMap<String, (Option<Value>, Option<Value>)>,
+    /// The prompt theme before and after the rc files if they have selected one
+    theme: Option<(Option<&'static Theme>, &'static Theme)>,
+    /// Key bindings after the rc files in `bindkey` notation if they have changed them
+    bindings: Option<String>,
+}
+
+impl Shell {
+    /// Reads the rc files like `on_start` and remembers what they have changed
+    pub fn read_rc_files(&mut self) -> Result<Flow> {
+        let variables = self.variables.clone();
+        let theme = self.theme;
+        let bindings = self.editor.bindings();
+        let result = self.on_start();
+        let mut changes = RcChanges::default();
+        for name in variables.keys().chain(self.variables.keys()) {
+            let (before, after) = (variables.value(name), self.variables.value(name));
+            if before != after {
+                changes.variables.insert(name.clone(), (before.cloned(), after.cloned()));
+            }
+        }
+        changes.theme = match self.theme {
+            Some(current) if !theme.is_some_and(|theme| theme == current) => Some((theme, current)),
+            _ => None,
+        };
+        let current = self.editor.bindings();
+        if current != bindings {
+            changes.bindings = Some(current);
+        }
+        self.rc_changes = changes;
+        result
+    }
+
+    /// Implements `reload`: undoes the changes made by the rc files and reads them again,
+    /// so edits of ~/.cshrc take effect in the running shell. What was changed again
+    /// after the rc files, e.g. a variable set by hand, is kept.
+    pub fn reload_builtin(&mut self) -> Result<Flow> {
+        let changes = mem::take(&mut self.rc_changes);
+        for (name, (before, after)) in changes.variables {
+            if self.variables.value(&name) != after.as_ref() {
+                continue;
+            }
+            match before {
+                Some(value) => self.variables.insert_value(name, value),
+                None => self.variables.remove(&name),
+            };
+        }
+        if let Some((before, after)) = changes.theme {
+            if self.theme.is_some_and(|theme| theme == after) {
+                self.theme = before;
+            }
+        }
+        if changes.bindings.is_some_and(|bindings| bindings == self.editor.bindings()) {
+            self.editor.use_emacs_keys();
+        }
+        self.read_rc_files()
+    }
+
+    /// Called before the prompt: with `rcwatch` set, asks whether to `reload`
+    /// when some of the rc files has changed since they were read
+    pub fn check_rc_files(&mut self) -> Result<Flow> {
+        if !self.variables.contains_key("rcwatch") || !is_terminal(0) {
+            self.rc_watch = None;
+            return Ok(Flow::Continue);
+        }
+        let files = self.rc_files();
+        let watch = self.rc_watch.get_or_insert_with(|| FileWatch::new(files));
+        if !watch.changed() {
+            return Ok(Flow::Continue);
+        }
+        if ask_key(0, &msg!("The rc files have changed, reload? [y/n] "), b'y', b'n')? {
+            self.reload_builtin()
+        } else {
+            Ok(Flow::Continue)
+        }
+    }
+
+    /// The rc files read at start in this order, see `on_start`
+    fn rc_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![PathBuf::from(SYSTEM_CSHRC)];
+        if self.is_login {
+            files.push(PathBuf::from(SYSTEM_LOGIN));
+        }
+        files.push(self.user_rc());
+        if self.is_login {
+            files.push(self.home.join(".login"));
+        }
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use native::test_dir::TestDir;
+
+    use super::*;
+
+    #[test]
+    fn reload_keeps_changes_made_by_hand() {
+        let dir = TestDir::new("reload");
+        let path = dir.join("cshrc");
+        std::fs::write(&path, "set a = 1\nset b = 2\n").unwrap();
+        let mut shell = Shell::new().unwrap();
+        shell.options.rc = Some(path.display().to_string());
+        shell.read_rc_files().unwrap();
+        shell.variables.insert(String::from("b"), String::from("mine"));
+        std::fs::write(&path, "set c = 3\n").unwrap();
+        shell.reload_builtin().unwrap();
+        assert_eq!(shell.variables.get("a"), None);
+        assert_eq!(shell.variables["b"], "mine");
+        assert_eq!(shell.variables["c"], "3");
+    }
+}
@@ -0,0 +1,72 @@
+use native::ExitCode;
+use native::error::*;
+#[cfg(target_os = "linux")]
+use native::spawn_process;
+
+#[cfg(target_os = "linux")]
+use super::debug::Debug;
+use super::pipeline::Stage;
+use super::Shell;
+
+impl Shell {
+    /// Runs a simple command with posix_spawn(3) if `spawn` is set.
+    /// Returns None if the command has redirections or can't be found,
+    /// so it has to be forked as usual. With job control the command is always forked:
+    /// it needs its own process group and the terminal, and Ctrl-Z has to stop it.
+    #[cfg(target_os = "linux")]
+    pub fn try_spawn(&mut self, stage: &Stage) -> Option<Result<ExitCode>> {
+        let redirected = !stage.redirections.is_empty();
+        // the session log and the output guard need the output copied by a forked child
+        let logged = ["sessionlog", "outputlimit", "outputtime"]
+            .iter()
+            .any(|name| self.variables.contains_key(name));
+        if !self.variables.contains_key("spawn") || redirected || logged || self.jobs.control {
+            return None;
+        }
+        let (name, arguments) = stage.words.split_first()?;
+        let path = self.hash_lookup(name)?;
+        self.debug(Debug::Exec, || format!("{} resolved to {}", name, path.display()));
+        let argv = Some(name.to_owned()).into_iter().chain(arguments.to_vec()).collect();
+        let status = spawn_process(&path, argv, stage.environment());
+        if status.as_ref().is_err_and(Error::is_too_long) {
+            // the forked child tells about it or runs the command in batches
+            return None;
+        }
+        let status = status.map_err(|e| e.about(name));
+        self.debug(Debug::Jobs, || format!("{} spawned and exited with {:?}", name, status));
+        Some(status.and_then(|(pid, status)| {
+            self.core_dumped = false;
+            self.report_death(pid, Some(name), status)?;
+            self.explain_exit(status)?;
+            Ok(status.exit_code())
+        }))
+    }
+
+    /// Spawning is supported only on Linux, commands are always forked elsewhere
+    #[cfg(not(target_os = "linux"))]
+    pub fn try_spawn(&mut self, _: &Stage) -> Option<Result<ExitCode>> {
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_control_forks() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("spawn"), String::new());
+        shell.jobs.control = true;
+        let stage = Stage {
+            assignments: Vec::new(),
+            words: vec![String::from("true")],
+            group: None,
+            redirections: Vec::new(),
+            with_stderr: false,
+        };
+        assert!(shell.try_spawn(&stage).is_none());
+        shell.jobs.control = false;
+        assert_eq!(shell.try_spawn(&stage).unwrap().unwrap(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use native::error::*;
+use native::write_to_file;
+
+use super::complete::command_usage;
+use super::dirs::abbreviate_home;
+use super::Shell;
+
+/// Number of rows in every table of `stats`
+const TOP: usize = 10;
+/// Width of the longest bar
+const BAR_WIDTH: usize = 30;
+
+/// Runs of one command in this session
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CommandStats {
+    runs: u32,
+    failures: u32,
+    total: Duration,
+}
+
+/// What the commands typed in this session did, `stats` shows it with the history
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    commands: HashMap<String, CommandStats>,
+    dirs: HashMap<PathBuf, u32>,
+}
+
+impl SessionStats {
+    /// Counts the command line run in the directory
+    pub fn record(&mut self, line: &str, dir: &Path, duration: Duration, failed: bool) {
+        let command = match line.split_whitespace().next() {
+            Some(command) => command,
+            None => return,
+        };
+        let stats = self.commands.entry(String::from(command)).or_default();
+        stats.runs += 1;
+        stats.total += duration;
+        if failed {
+            stats.failures += 1;
+        }
+        *self.dirs.entry(dir.to_path_buf()).or_insert(0) += 1;
+    }
+}
+
+/// Draws a bar proportional to the value, a non-zero value gets at least one mark
+fn bar(value: f64, max: f64) -> String {
+    if max <= 0.0 || value <= 0.0 {
+        return String::new();
+    }
+    let width = (value / max * BAR_WIDTH as f64).round().max(1.0) as usize;
+    "#".repeat(width)
+}
+
+/// A row of a table: the name, the value of the bar and the label shown before the bar
+type Row = (String, f64, String);
+
+/// Sorts the rows by the value, the biggest first, and keeps the first `TOP` of them
+fn top(mut rows: Vec<Row>) -> Vec<Row> {
+    rows.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows.truncate(TOP);
+    rows
+}
+
+/// Renders the table with the title, the bars are scaled to the biggest value
+fn render(title: &str, rows: &[Row]) -> String {
+    let mut text = format!("{}\n", title);
+    let max = rows.iter().map(|row| row.1).fold(0.0, f64::max);
+    let name_width = rows.iter().map(|row| row.0.chars().count()).max().unwrap_or(0);
+    let label_width = rows.iter().map(|row| row.2.chars().count()).max().unwrap_or(0);
+    for (name, value, label) in rows {
+        let line = format!(
+            "  {:<name_width$}  {:>label_width$}  {}",
+            name,
+            label,
+            bar(*value, max),
+            name_width = name_width,
+            label_width = label_width
+        );
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+impl Shell {
+    /// Implements `stats`: the most used commands according to the history,
+    /// the average duration and the failures of commands and the busiest directories
+    /// of this session, drawn as bar charts
+    pub fn stats_builtin(&self) -> Result<()> {
+        let used = command_usage(&self.history.entries)
+            .into_iter()
+            .map(|(command, count)| (String::from(command), count as f64, count.to_string()))
+            .collect();
+        let durations = self
+            .session_stats
+            .commands
+            .iter()
+            .map(|(command, stats)| {
+                let average = stats.total.as_secs_f64() / f64::from(stats.runs);
+                let seconds = format!("{:.2}", average);
+                let label = msg!("{}s, {} of {} failed", seconds, stats.failures, stats.runs);
+                (command.clone(), average, label)
+            })
+            .collect();
+        let dirs = self
+            .session_stats
+            .dirs
+            .iter()
+            .map(|(dir, count)| {
+                (abbreviate_home(dir, &self.home), f64::from(*count), count.to_string())
+            })
+            .collect();
+        let text = [
+            render(&msg!("Most used commands"), &top(used)),
+            render(&msg!("Average duration in this session"), &top(durations)),
+            render(&msg!("Busiest directories in this session"), &top(dirs)),
+        ]
+        .join("\n");
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars_are_proportional() {
+        assert_eq!(bar(10.0, 10.0).len(), BAR_WIDTH);
+        assert_eq!(bar(5.0, 10.0).len(), BAR_WIDTH / 2);
+        assert_eq!(bar(0.01, 10.0), "#");
+        assert_eq!(bar(0.0, 10.0), "");
+    }
+
+    #[test]
+    fn record_runs() {
+        let mut stats = SessionStats::default();
+        stats.record("make -j4", Path::new("/src"), Duration::from_secs(3), false);
+        stats.record("make", Path::new("/src"), Duration::from_secs(1), true);
+        stats.record("  ", Path::new("/"), Duration::from_secs(1), false);
+        let make = stats.commands["make"];
+        assert_eq!((make.runs, make.failures), (2, 1));
+        assert_eq!(make.total, Duration::from_secs(4));
+        assert_eq!(stats.dirs.len(), 1);
+    }
+}
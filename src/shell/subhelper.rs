@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::env::{remove_var, set_var, vars};
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use libc::pid_t;
+
+use native::error::*;
+use native::signal::ignoring_broken_pipe;
+use native::{change_dir, close_file, create_pipe, read_byte, read_chunk, replace_fdi,
+             set_close_on_exec, start_process, wait_process, write_all, write_to_file};
+
+use super::ast::parse_line;
+use super::variables::{Value, Variables};
+use super::{Flow, Shell};
+
+/// A subshell which stays alive while `subhelper` is set and runs the commands of
+/// `` `...` `` sent to it over a pipe, so a burst of substitutions like in an rc file
+/// forks once instead of every time. Every request carries what the commands see:
+/// the directories, the variables, the environment and the status of the shell.
+pub struct SubstitutionHelper {
+    pid: pid_t,
+    /// The shell writes requests here
+    requests: RawFd,
+    /// The output of the commands followed by the end mark of the request
+    replies: RawFd,
+}
+
+/// Builds the mark which ends the output of the request with the nonce
+fn end_mark(nonce: &str) -> String {
+    format!("\0{}\n", nonce)
+}
+
+/// Takes the fields of a request one by one
+struct Fields<'a>(std::str::Split<'a, char>);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a str> {
+        self.0.next().ok_or(Error::SyntaxError)
+    }
+
+    fn count(&mut self) -> Result<usize> {
+        self.next()?.parse().map_err(|_| Error::SyntaxError)
+    }
+}
+
+impl Shell {
+    /// Gets the output of the commands from the helper, starting it first if needed.
+    /// Returns None if the helper can't take them, then a subshell is forked as usual.
+    pub fn helper_output(&mut self, line: &str) -> Option<Result<String>> {
+        if self.helper.is_none() {
+            self.helper = self.start_helper().ok();
+        }
+        let nonce = format!("{:016x}", self.random.next_u64());
+        let request = self.helper_request(&nonce, line)?;
+        let requests = self.helper.as_ref()?.requests;
+        let sent = ignoring_broken_pipe(|| write_all(requests, &request));
+        if !matches!(sent, Ok(Ok(()))) {
+            // the helper has died, the next substitution starts another one
+            self.stop_helper();
+            return None;
+        }
+        Some(self.helper_reply(&end_mark(&nonce)))
+    }
+
+    /// Closes the pipes of the helper and waits for it to exit
+    pub fn stop_helper(&mut self) {
+        if let Some(helper) = self.helper.take() {
+            close_file(helper.requests).ok();
+            close_file(helper.replies).ok();
+            // it may have been reaped with the jobs already
+            wait_process(helper.pid).ok();
+        }
+    }
+
+    fn start_helper(&mut self) -> Result<SubstitutionHelper> {
+        let subshell = self.subshell();
+        let (request_read, request_write) = create_pipe()?;
+        let (reply_read, reply_write) = create_pipe()?;
+        let started = start_process(|| {
+            subshell.run_subshell(|shell| {
+                close_file(request_write)?;
+                close_file(reply_read)?;
+                replace_fdi(1, reply_write)?;
+                close_file(reply_write)?;
+                shell.serve_requests(request_read)
+            })
+        });
+        close_file(request_read)?;
+        close_file(reply_write)?;
+        let helper = SubstitutionHelper {
+            pid: started?,
+            requests: request_write,
+            replies: reply_read,
+        };
+        // programs started later mustn't keep the helper alive after the shell exits
+        set_close_on_exec(helper.requests, true)?;
+        set_close_on_exec(helper.replies, true)?;
+        Ok(helper)
+    }
+
+    /// Writes the request as its length and the fields separated with zeros.
+    /// None if the current directory isn't unicode.
+    fn helper_request(&self, nonce: &str, line: &str) -> Option<Vec<u8>> {
+        let mut fields = vec![
+            String::from(nonce),
+            String::from(self.cwd.to_str()?),
+            self.status.to_string(),
+            String::from(line),
+        ];
+        fields.push(self.dir_stack.len().to_string());
+        for dir in &self.dir_stack {
+            fields.push(String::from(dir.to_str()?));
+        }
+        let environment: Vec<(String, String)> = vars().collect();
+        fields.push(environment.len().to_string());
+        for (key, value) in environment {
+            fields.push(key);
+            fields.push(value);
+        }
+        fields.push(self.variables.keys().count().to_string());
+        for name in self.variables.keys() {
+            let value = self.variables.value(name)?;
+            fields.push(name.clone());
+            if value.is_list() {
+                let words = value.words();
+                fields.push(format!("l{}", words.len()));
+                fields.extend(words);
+            } else {
+                fields.push(String::from("w"));
+                fields.push(String::from(value.text()));
+            }
+        }
+        let payload = fields.join("\0");
+        Some(format!("{}\n{}", payload.len(), payload).into_bytes())
+    }
+
+    /// Reads the output up to the end mark. If the helper dies, what it has written
+    /// is the output like the one of a killed subshell.
+    fn helper_reply(&mut self, mark: &str) -> Result<String> {
+        let replies = match &self.helper {
+            Some(helper) => helper.replies,
+            None => return Ok(String::new()),
+        };
+        let mut output = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let length = read_chunk(replies, &mut buf)?;
+            if length == 0 {
+                self.stop_helper();
+                break;
+            }
+            output.extend_from_slice(&buf[..length]);
+            if output.ends_with(mark.as_bytes()) {
+                output.truncate(output.len() - mark.len());
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// The loop of the helper: every request runs in a fresh subshell of it,
+    /// so nothing but the process itself is left from the previous ones
+    fn serve_requests(&mut self, requests: RawFd) -> Result<Flow> {
+        while let Some(request) = read_request(requests)? {
+            let mut fields = Fields(request.split('\0'));
+            let nonce = fields.next()?;
+            let mut shell = self.subshell();
+            if let Err(reason) = shell.run_request(&mut fields) {
+                write_to_file(2, &reason.diagnostic()).ok();
+            }
+            write_all(1, end_mark(nonce).as_bytes())?;
+        }
+        Ok(Flow::Exit)
+    }
+
+    /// Takes the state of the shell from the request and runs its commands
+    fn run_request(&mut self, fields: &mut Fields) -> Result<Flow> {
+        let cwd = PathBuf::from(fields.next()?);
+        self.status = fields.next()?.parse().map_err(|_| Error::SyntaxError)?;
+        let line = fields.next()?;
+        self.dir_stack = Vec::new();
+        for _ in 0..fields.count()? {
+            self.dir_stack.push(PathBuf::from(fields.next()?));
+        }
+        let mut environment = HashMap::new();
+        for _ in 0..fields.count()? {
+            environment.insert(fields.next()?, fields.next()?);
+        }
+        for (key, _) in vars() {
+            if !environment.contains_key(key.as_str()) {
+                remove_var(key);
+            }
+        }
+        for (key, value) in environment {
+            set_var(key, value);
+        }
+        self.variables = Variables::default();
+        for _ in 0..fields.count()? {
+            let name = String::from(fields.next()?);
+            let value = match fields.next()? {
+                "w" => Value::word(String::from(fields.next()?)),
+                kind => {
+                    let count = kind[1..].parse().map_err(|_| Error::SyntaxError)?;
+                    let words = (0..count).map(|_| fields.next().map(String::from));
+                    Value::list(words.collect::<Result<_>>()?)
+                }
+            };
+            self.variables.insert_value(name, value);
+        }
+        change_dir(&cwd)?;
+        self.cwd = cwd;
+        self.run_list(&parse_line(line)?)
+    }
+}
+
+/// Reads the length of the next request and the request itself, None once the shell
+/// has closed the pipe
+fn read_request(fd: RawFd) -> Result<Option<String>> {
+    let mut length = String::new();
+    loop {
+        match read_byte(fd)? {
+            None if length.is_empty() => return Ok(None),
+            None => return Err(Error::SyntaxError),
+            Some(b'\n') => break,
+            Some(byte) => length.push(byte as char),
+        }
+    }
+    let length: usize = length.parse().map_err(|_| Error::SyntaxError)?;
+    let mut request = vec![0; length];
+    let mut filled = 0;
+    while filled < length {
+        match read_chunk(fd, &mut request[filled..])? {
+            0 => return Err(Error::SyntaxError),
+            read => filled += read,
+        }
+    }
+    String::from_utf8(request).map(Some).map_err(|_| Error::InvalidUnicode)
+}
+
+#[cfg(test)]
+mod tests {
+    use native::signal::send_signal;
+
+    use super::*;
+
+    #[test]
+    fn helper_runs_substitutions() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("subhelper"), String::new());
+        shell.variables.insert(String::from("x"), String::from("1"));
+        assert_eq!(shell.command_output("echo $x ; cd / ; pwd").unwrap(), "1\n/\n");
+        let pid = shell.helper.as_ref().unwrap().pid;
+        shell.variables.insert_value(String::from("x"), Value::list(vec![String::from("a")]));
+        let cwd = format!("{}\n", shell.cwd.display());
+        assert_eq!(shell.command_output("echo $x ; pwd").unwrap(), format!("a\n{}", cwd));
+        assert_eq!(shell.helper.as_ref().unwrap().pid, pid);
+        assert!(shell.command_output("ls |").is_err());
+        send_signal(pid, libc::SIGKILL).unwrap();
+        wait_process(pid).unwrap();
+        assert_eq!(shell.command_output("echo b").unwrap(), "b\n");
+        assert!(shell.helper.is_none());
+        shell.command_output("true").unwrap();
+        assert_ne!(shell.helper.as_ref().unwrap().pid, pid);
+        shell.variables.remove("subhelper");
+        assert_eq!(shell.command_output("echo c").unwrap(), "c\n");
+        assert!(shell.helper.is_none());
+    }
+}
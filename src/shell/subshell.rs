@@ -0,0 +1,158 @@
+use std::process::exit;
+
+use native::error::*;
+use native::signal::default_signals;
+use native::{close_file, create_pipe, read_bytes, replace_fdi, start_process, wait_process,
+             write_to_file};
+
+use super::ast::parse_line;
+use super::builtins::Outcome;
+use super::editor::Editor;
+use super::hash::CommandHash;
+use super::history::History;
+use super::jobs::Jobs;
+use super::multios::TerminalOutput;
+use super::pipeline::Stage;
+use super::random::Random;
+use super::reload::RcChanges;
+use super::stats::SessionStats;
+use super::{Flow, Shell};
+
+impl Shell {
+    /// Makes the state of a subshell before forking. The variables are shared until one
+    /// of the shells changes them, so it's cheap. The subshell has no jobs, no history
+    /// and records nothing, whatever it does is lost when it exits.
+    pub fn subshell(&mut self) -> Shell {
+        Shell {
+            variables: self.variables.clone(),
+            is_login: false,
+            argv: self.argv.clone(),
+            user: self.user,
+            status: self.status,
+            core_dumped: self.core_dumped,
+            home: self.home.clone(),
+            path: self.path.clone(),
+            prompt: self.prompt.clone(),
+            cwd: self.cwd.clone(),
+            dir_stack: self.dir_stack.clone(),
+            history: History::default(),
+            jobs: Jobs::default(),
+            hash: CommandHash::default(),
+            session_stats: SessionStats::default(),
+            incognito: true,
+            editor: Editor::default(),
+            theme: self.theme,
+            rc_changes: RcChanges::default(),
+            rc_watch: None,
+            builtins: self.builtins.clone(),
+            window: self.window,
+            temp_dir: None,
+            helper: None,
+            options: self.options.clone(),
+            random: Random::new(self.random.next_u64()),
+        }
+    }
+
+    /// Runs the commands in the forked child and exits with the status of the subshell.
+    /// Ctrl-C and Ctrl-Z act on the subshell like on any program.
+    pub fn run_subshell<F>(mut self, run: F) -> !
+    where
+        F: FnOnce(&mut Shell) -> Result<Flow>,
+    {
+        if let Err(reason) = default_signals().and_then(|_| run(&mut self)) {
+            write_to_file(2, &reason.diagnostic()).ok();
+            self.status = 1;
+        }
+        self.remove_temp_dir();
+        exit(self.status);
+    }
+
+    /// Runs a builtin or a group of a pipeline in the subshell of the forked child
+    pub fn run_stage(self, stage: &Stage, terminal: TerminalOutput) -> ! {
+        self.run_subshell(|shell| {
+            shell.apply_redirections(&stage.redirections, terminal)?;
+            match &stage.group {
+                Some(group) => shell.run_list(&group.list),
+                None => {
+                    let arguments: Vec<&str> =
+                        stage.words[1..].iter().map(String::as_str).collect();
+                    shell.run_builtin(&stage.words[0], &arguments)
+                }
+            }
+        })
+    }
+
+    /// Runs the builtin in the shell itself and keeps its status
+    pub fn run_builtin(&mut self, name: &str, arguments: &[&str]) -> Result<Flow> {
+        let builtin = match self.builtins.get(name).cloned() {
+            Some(builtin) => builtin,
+            None => return Err(Error::CommandNotFound(String::from(name))),
+        };
+        Ok(match builtin.execute(self, arguments)? {
+            Outcome::Status(status) => {
+                self.status = status;
+                Flow::Continue
+            }
+            Outcome::Exit(status) => {
+                self.status = status;
+                Flow::Exit
+            }
+        })
+    }
+
+    /// Runs the commands of `` `...` `` in a subshell and gets what they write to stdout.
+    /// The line is parsed by the shell, so its errors are reported like the others.
+    /// While `subhelper` is set the helper runs them instead, see `SubstitutionHelper`.
+    pub fn command_output(&mut self, line: &str) -> Result<String> {
+        let list = parse_line(line)?;
+        if !self.variables.contains_key("subhelper") {
+            self.stop_helper();
+        } else if let Some(output) = self.helper_output(line) {
+            return output;
+        }
+        let subshell = self.subshell();
+        let (read_end, write_end) = create_pipe()?;
+        let started = start_process(|| {
+            subshell.run_subshell(|shell| {
+                replace_fdi(1, write_end)?;
+                close_file(read_end)?;
+                close_file(write_end)?;
+                shell.run_list(&list)
+            })
+        });
+        close_file(write_end)?;
+        let output = started.and_then(|pid| {
+            let output = read_bytes(read_end);
+            wait_process(pid)?;
+            output
+        });
+        close_file(read_end)?;
+        Ok(String::from_utf8_lossy(&output?).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subshell_shares_variables() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("x"), String::from("1"));
+        let mut subshell = shell.subshell();
+        assert_eq!(subshell.variables["x"], "1");
+        subshell.variables.insert(String::from("x"), String::from("2"));
+        assert_eq!(shell.variables["x"], "1");
+        assert!(subshell.incognito && !subshell.is_login);
+    }
+
+    #[test]
+    fn output_of_commands() {
+        let mut shell = Shell::new().unwrap();
+        shell.variables.insert(String::from("x"), String::from("a b"));
+        assert_eq!(shell.command_output("echo $x ; cd / ; pwd").unwrap(), "a b\n/\n");
+        assert_ne!(shell.cwd.to_str(), Some("/"));
+        assert_eq!(shell.command_output("exit 3").unwrap(), "");
+        assert!(shell.command_output("ls |").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::env::var;
+use std::fs::remove_dir_all;
+use std::path::PathBuf;
+
+use libc::pid_t;
+
+use native::error::*;
+use native::{close_file, get_process_id, make_temp_dir, make_temp_file};
+
+use super::debug::Debug;
+use super::Shell;
+
+/// Directory for temporary files of the shell, it is created on the first `mktemp`
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+    /// Forked children share the field, only the shell which created the directory removes it
+    owner: pid_t,
+}
+
+/// Where the directory of the shell is created, TMPDIR or /tmp
+fn temp_base() -> PathBuf {
+    var("TMPDIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+}
+
+impl Shell {
+    /// Implements `mktemp [-d] name`: creates a file or, with `-d`, a directory with a unique
+    /// name in the temporary directory of the shell and stores its path in the variable.
+    /// Everything created this way is removed when the shell exits.
+    pub fn mktemp_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        let (directory, name) = match arguments {
+            ["-d", name] => (true, *name),
+            [name] if !name.starts_with('-') => (false, *name),
+            [] | ["-d"] => return Err(Error::MissingArgument("mktemp")),
+            _ => return Err(Error::SyntaxError),
+        };
+        let template = self.temp_dir()?.join(format!("{}.XXXXXX", name));
+        let path = if directory {
+            make_temp_dir(&template)?
+        } else {
+            let (fd, path) = make_temp_file(&template)?;
+            close_file(fd)?;
+            path
+        };
+        self.variables.insert(String::from(name), path.display().to_string());
+        Ok(())
+    }
+
+    /// Gets the temporary directory of the shell, it is created if there is none yet
+    pub fn temp_dir(&mut self) -> Result<PathBuf> {
+        if let Some(ref dir) = self.temp_dir {
+            return Ok(dir.path.clone());
+        }
+        let path = make_temp_dir(&temp_base().join("rsh.XXXXXX"))?;
+        self.debug(Debug::Exec, || format!("temporary directory {}", path.display()));
+        self.temp_dir = Some(TempDir {
+            path: path.clone(),
+            owner: get_process_id(),
+        });
+        Ok(path)
+    }
+
+    /// Removes the temporary directory with everything in it, it's called on exit
+    pub fn remove_temp_dir(&mut self) {
+        if let Some(dir) = self.temp_dir.take() {
+            if dir.owner == get_process_id() {
+                remove_dir_all(&dir.path).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_name_is_required() {
+        let mut shell = Shell::new().unwrap();
+        let missing = shell.mktemp_builtin(&["-d"]);
+        assert!(matches!(missing, Err(Error::MissingArgument("mktemp"))));
+        assert!(matches!(shell.mktemp_builtin(&["one", "two"]), Err(Error::SyntaxError)));
+    }
+}
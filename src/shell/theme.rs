@@ -0,0 +1,219 @@
+use std::env::var;
+
+use native::error::*;
+use native::get_hostname;
+use native::users::get_user_name;
+use native::write_to_file;
+
+use super::dirs::abbreviate_home;
+use super::Shell;
+
+/// Terminal capability a prompt theme depends on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    /// Characters outside of ASCII are displayed, checked by the locale
+    Unicode,
+    /// ANSI color sequences are understood, checked by TERM
+    Colors,
+}
+
+impl Feature {
+    fn name(self) -> &'static str {
+        match self {
+            Feature::Unicode => "unicode",
+            Feature::Colors => "colors",
+        }
+    }
+
+    /// Guesses from the environment whether the terminal has the feature
+    fn is_supported(self) -> bool {
+        match self {
+            Feature::Unicode => ["LC_ALL", "LC_CTYPE", "LANG"]
+                .iter()
+                .filter_map(|name| var(name).ok())
+                .find(|value| !value.is_empty())
+                .is_some_and(|locale| {
+                    let locale = locale.to_lowercase();
+                    locale.contains("utf-8") || locale.contains("utf8")
+                }),
+            Feature::Colors => var("TERM").is_ok_and(|term| !term.is_empty() && term != "dumb"),
+        }
+    }
+}
+
+/// A predefined prompt, its template is expanded every time the prompt is printed
+#[derive(Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Text with `%` sequences, see `expand`
+    template: &'static str,
+    requires: &'static [Feature],
+}
+
+/// Themes selectable with `prompt theme`
+pub const THEMES: &[Theme] = &[
+    Theme {
+        name: "minimal",
+        template: "%# ",
+        requires: &[],
+    },
+    Theme {
+        name: "classic",
+        template: "%n@%m:%~%# ",
+        requires: &[],
+    },
+    Theme {
+        name: "powerline",
+        template: "%K{4}%F{7} %n@%m %K{2}%F{4}\u{e0b0}%F{0} %~ %k%F{2}\u{e0b0}%f ",
+        requires: &[Feature::Unicode, Feature::Colors],
+    },
+];
+
+/// Values substituted into templates
+struct Values {
+    user: String,
+    host: String,
+    cwd: String,
+    root: bool,
+}
+
+/// Expands the template: `%n` is the user, `%m` is the host name up to the first dot,
+/// `%~` is the current directory, `%#` is `#` for root and `%` otherwise, `%%` is `%`.
+/// `%F{n}` and `%K{n}` set the foreground and background colors, `%f` and `%k` reset them.
+/// Unknown sequences are kept as is.
+fn expand(template: &str, values: &Values) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push_str(&values.user),
+            Some('m') => result.push_str(values.host.split('.').next().unwrap_or("")),
+            Some('~') => result.push_str(&values.cwd),
+            Some('#') => result.push(if values.root { '#' } else { '%' }),
+            Some('%') => result.push('%'),
+            Some('f') => result.push_str("\x1b[39m"),
+            Some('k') => result.push_str("\x1b[49m"),
+            Some(kind @ 'F') | Some(kind @ 'K') => {
+                let rest = chars.as_str();
+                let color = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .filter(|(color, _)| color.len() == 1 && color.as_bytes()[0].is_ascii_digit());
+                match color {
+                    Some((color, after)) => {
+                        let base = if kind == 'F' { 3 } else { 4 };
+                        result.push_str(&format!("\x1b[{}{}m", base, color));
+                        chars = after.chars();
+                    }
+                    None => {
+                        result.push('%');
+                        result.push(kind);
+                    }
+                }
+            }
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+/// Finds the theme by name
+fn find_theme(name: &str) -> Result<&'static Theme> {
+    THEMES.iter().find(|theme| theme.name == name).ok_or(Error::NoSuchTheme)
+}
+
+/// Names of features required by the theme which the terminal lacks
+fn missing_features(theme: &Theme) -> Vec<&'static str> {
+    theme
+        .requires
+        .iter()
+        .filter(|feature| !feature.is_supported())
+        .map(|feature| feature.name())
+        .collect()
+}
+
+impl Shell {
+    /// Expands the template of the theme for the current state of the shell
+    pub fn render_theme(&self, theme: &Theme) -> String {
+        let values = Values {
+            user: get_user_name(self.user).unwrap_or_default(),
+            host: get_hostname().unwrap_or_default(),
+            cwd: abbreviate_home(&self.cwd, &self.home),
+            root: self.user == 0,
+        };
+        expand(theme.template, &values)
+    }
+
+    /// Implements `prompt theme [name]` which selects the theme or lists them and
+    /// `prompt preview` which prints a sample of every theme the terminal supports
+    pub fn prompt_builtin(&mut self, arguments: &[&str]) -> Result<()> {
+        match arguments {
+            ["theme"] => {
+                let names: Vec<&str> = THEMES.iter().map(|theme| theme.name).collect();
+                write_to_file(1, &format!("{}\n", names.join(" ")))?;
+            }
+            ["theme", name] => {
+                let theme = find_theme(name)?;
+                if let Some(feature) = missing_features(theme).first() {
+                    return Err(Error::MissingFeature(feature));
+                }
+                self.theme = Some(theme);
+            }
+            ["preview"] => {
+                let mut text = String::new();
+                for theme in THEMES {
+                    let missing = missing_features(theme);
+                    let sample = if missing.is_empty() {
+                        self.render_theme(theme)
+                    } else {
+                        msg!("(requires {})", missing.join(", "))
+                    };
+                    text.push_str(&format!("{:<10} {}\n", theme.name, sample));
+                }
+                write_to_file(1, &text)?;
+            }
+            _ => return Err(Error::SyntaxError),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        Values {
+            user: String::from("kirill"),
+            host: String::from("vm.local"),
+            cwd: String::from("~/src"),
+            root: false,
+        }
+    }
+
+    #[test]
+    fn expand_sequences() {
+        assert_eq!(expand("%n@%m:%~%# ", &values()), "kirill@vm:~/src% ");
+        assert_eq!(expand("100%% %x %", &values()), "100% %x %");
+    }
+
+    #[test]
+    fn expand_colors() {
+        assert_eq!(expand("%F{1}%K{7}x%f%k", &values()), "\x1b[31m\x1b[47mx\x1b[39m\x1b[49m");
+        assert_eq!(expand("%F{12}%K", &values()), "%F{12}%K");
+    }
+
+    #[test]
+    fn themes_are_found() {
+        assert_eq!(find_theme("classic").unwrap().name, "classic");
+        assert!(find_theme("fancy").is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use native::error::*;
+use native::write_to_file;
+
+use super::Shell;
+
+impl Shell {
+    /// Prints the input line to stderr if `verbose` is set
+    pub fn trace_input(&self, line: &str) -> Result<()> {
+        if self.variables.contains_key("verbose") {
+            write_to_file(2, &format!("{}\n", line))?;
+        }
+        Ok(())
+    }
+
+    /// Prints the expanded command to stderr if `echo` is set
+    pub fn trace_command(&self, text: &str) -> Result<()> {
+        if self.variables.contains_key("echo") {
+            write_to_file(2, &format!("{}\n", text))?;
+        }
+        Ok(())
+    }
+}
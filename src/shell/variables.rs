@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::sync::Arc;
+
+use native::write_to_file;
+use native::error::*;
+
+use super::Shell;
+use super::quoting::quote_word;
+
+/// Variables computed by the shell which can't be assigned
+const READ_ONLY: &[&str] = &["loginsh"];
+
+/// The value of a shell variable: a word or a list of words like `set path = (/bin /usr/bin)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    /// The word or the words of the list joined with spaces
+    text: String,
+    /// The words of a list, None for a word
+    list: Option<Vec<String>>,
+}
+
+impl Value {
+    pub fn word(text: String) -> Value {
+        Value { text, list: None }
+    }
+
+    pub fn list(words: Vec<String>) -> Value {
+        Value {
+            text: words.join(" "),
+            list: Some(words),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_list(&self) -> bool {
+        self.list.is_some()
+    }
+
+    /// Gets the words of the list, a word is split on whitespace
+    pub fn words(&self) -> Vec<String> {
+        match &self.list {
+            Some(words) => words.clone(),
+            None => self.text.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+/// Shell variables by their names. A list is seen as its words joined with spaces
+/// unless its words are asked for with `value`. A copy shares the variables till one
+/// of them is changed, so a subshell gets them without copying every value.
+#[derive(Debug, Clone, Default)]
+pub struct Variables(Arc<HashMap<String, Value>>);
+
+impl Variables {
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.0.get(name).map(|value| &value.text)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    /// Sets the variable to the word
+    pub fn insert(&mut self, name: String, text: String) -> Option<Value> {
+        self.insert_value(name, Value::word(text))
+    }
+
+    pub fn insert_value(&mut self, name: String, value: Value) -> Option<Value> {
+        Arc::make_mut(&mut self.0).insert(name, value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        if !self.0.contains_key(name) {
+            // the shared variables aren't copied for nothing
+            return None;
+        }
+        Arc::make_mut(&mut self.0).remove(name)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+}
+
+impl Index<&str> for Variables {
+    type Output = String;
+
+    fn index(&self, name: &str) -> &String {
+        &self.0[name].text
+    }
+}
+
+impl Shell {
+    /// Implements `set`: prints all variables if there are no words, assigns them otherwise.
+    /// Supported forms are `set name`, `set name = word` and `set name = (word ...)`.
+    pub fn set_variables(&mut self, words: &[&str]) -> Result<()> {
+        if words.is_empty() {
+            return self.print_variables();
+        }
+        let assignments = parse_assignments(words)?;
+        if let Some(name) = READ_ONLY
+            .iter()
+            .find(|name| assignments.iter().any(|(assigned, _)| assigned == *name))
+        {
+            return Err(Error::ReadOnlyVariable(name));
+        }
+        for (name, value) in assignments {
+            self.store_variable(name, value);
+        }
+        Ok(())
+    }
+
+    /// Implements a `NAME=value` line: sets the shell variable to the value as it is,
+    /// while the same word before a command only goes to the environment of the command
+    pub fn assign_variable(&mut self, assignment: &str) -> Result<()> {
+        let (name, value) = assignment.split_once('=').ok_or(Error::SyntaxError)?;
+        if !is_variable_name(name) {
+            return Err(Error::SyntaxError.about(name));
+        }
+        if let Some(name) = READ_ONLY.iter().find(|read_only| **read_only == name) {
+            return Err(Error::ReadOnlyVariable(name));
+        }
+        self.store_variable(String::from(name), Value::word(String::from(value)));
+        Ok(())
+    }
+
+    fn store_variable(&mut self, name: String, value: Value) {
+        // `set edit = vi` and `set edit = emacs` choose the keymap like `bindkey -v` and `-e`
+        match (name.as_str(), value.text()) {
+            ("edit", "vi") => self.editor.use_vi_keys(),
+            ("edit", "emacs") => self.editor.use_emacs_keys(),
+            _ => {}
+        }
+        self.variables.insert_value(name, value);
+    }
+
+    /// Implements `unset`: removes all the named variables
+    pub fn unset_variables(&mut self, words: &[&str]) -> Result<()> {
+        if words.is_empty() {
+            return Err(Error::SyntaxError);
+        }
+        for name in words {
+            self.variables.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Implements `shift [name]`: drops the first word of the list variable, `argv` by default
+    pub fn shift_variable(&mut self, name: Option<&str>) -> Result<()> {
+        let name = name.unwrap_or("argv");
+        let mut words = self.variables.value(name).map(Value::words).unwrap_or_default();
+        if words.is_empty() {
+            return Err(Error::NoMoreWords);
+        }
+        words.remove(0);
+        self.variables.insert_value(String::from(name), Value::list(words));
+        Ok(())
+    }
+
+    /// Prints variables sorted by name, one per line, lists in parentheses
+    fn print_variables(&self) -> Result<()> {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+        let mut text = String::new();
+        for name in names {
+            let value = match self.variables.value(name) {
+                Some(value) if value.is_list() => {
+                    let words: Vec<String> =
+                        value.words().iter().map(|word| quote_word(word)).collect();
+                    format!("({})", words.join(" "))
+                }
+                _ => quote_word(&self.variables[name]),
+            };
+            text.push_str(&format!("{}\t{}\n", name, value));
+        }
+        write_to_file(1, &text)?;
+        Ok(())
+    }
+}
+
+/// Checks that the name may be assigned: letters, digits and `_`, not starting with a digit
+pub fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Splits the words of `set` into (name, value) pairs, the `=` may be a part of a word
+/// or a separate one. Word lists in parentheses become list values.
+fn parse_assignments(words: &[&str]) -> Result<Vec<(String, Value)>> {
+    let mut result = Vec::new();
+    let mut words = words.iter().cloned().peekable();
+    while let Some(word) = words.next() {
+        let (name, value) = match word.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None if words.peek().is_some_and(|next| next.starts_with('=')) => {
+                (word, words.next().map(|next| &next[1..]))
+            }
+            None => (word, None),
+        };
+        if !is_variable_name(name) {
+            return Err(Error::SyntaxError);
+        }
+        let value = match value {
+            None => Value::word(String::new()),
+            // the value is the next word like in `set name = value`
+            Some("") => match words.next() {
+                Some(first) => parse_value(first, &mut words)?,
+                None => Value::word(String::new()),
+            },
+            Some(first) => parse_value(first, &mut words)?,
+        };
+        result.push((String::from(name), value));
+    }
+    Ok(result)
+}
+
+/// Reads the value beginning with the word, a list is read till the word ending with `)`
+fn parse_value<'a, I>(first: &'a str, words: &mut I) -> Result<Value>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut word = match first.strip_prefix('(') {
+        Some(word) => word,
+        None => return Ok(Value::word(String::from(first))),
+    };
+    let mut list = Vec::new();
+    loop {
+        if let Some(last) = word.strip_suffix(')') {
+            list.push(last);
+            break;
+        }
+        list.push(word);
+        word = words.next().ok_or(Error::SyntaxError)?;
+    }
+    list.retain(|word| !word.is_empty());
+    Ok(Value::list(list.into_iter().map(String::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(name: &str, value: &str) -> (String, Value) {
+        (String::from(name), Value::word(String::from(value)))
+    }
+
+    fn list(name: &str, words: &[&str]) -> (String, Value) {
+        (String::from(name), Value::list(words.iter().map(|word| String::from(*word)).collect()))
+    }
+
+    fn parse(text: &str) -> Result<Vec<(String, Value)>> {
+        parse_assignments(&text.split_whitespace().collect::<Vec<&str>>())
+    }
+
+    #[test]
+    fn parse_assignments_forms() {
+        let parsed = parse("a b=1 c = 2 d =3 e= 4").unwrap();
+        let expected = vec![
+            pair("a", ""),
+            pair("b", "1"),
+            pair("c", "2"),
+            pair("d", "3"),
+            pair("e", "4"),
+        ];
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn parse_assignments_keep_words() {
+        let parsed = parse_assignments(&["x", "=", "a b", "y=it's", "z", "=", ""]).unwrap();
+        assert_eq!(parsed, vec![pair("x", "a b"), pair("y", "it's"), pair("z", "")]);
+    }
+
+    #[test]
+    fn quoted_values() {
+        let mut shell = Shell::new().unwrap();
+        shell.parse("set x = 'a b' y=\"it's\" z = ( 1 2 )").unwrap();
+        assert_eq!(shell.variables["x"], "a b");
+        assert_eq!(shell.variables["y"], "it's");
+        assert_eq!(shell.variables["z"], "1 2");
+        assert_eq!(shell.variables.value("z").unwrap().words(), vec!["1", "2"]);
+        shell.parse("set l = ('a b' c)").unwrap();
+        assert_eq!(shell.variables.value("l").unwrap().words(), vec!["a b", "c"]);
+        shell.parse(&format!("set w = {}", quote_word("a 'b' = (c)"))).unwrap();
+        assert_eq!(shell.variables["w"], "a 'b' = (c)");
+    }
+
+    #[test]
+    fn parse_assignments_list() {
+        let parsed = parse("cdpath = ( /tmp  ~/src ) x").unwrap();
+        assert_eq!(parsed, vec![list("cdpath", &["/tmp", "~/src"]), pair("x", "")]);
+        assert_eq!(parse("l=(a b) e = ( )").unwrap(), vec![list("l", &["a", "b"]), list("e", &[])]);
+    }
+
+    #[test]
+    fn parse_assignments_invalid() {
+        assert!(parse("1abc = 2").is_err());
+        assert!(parse("list = (a b").is_err());
+        assert!(parse("= 2").is_err());
+    }
+
+    #[test]
+    fn copies_are_independent() {
+        let mut variables = Variables::default();
+        variables.insert(String::from("x"), String::from("1"));
+        let mut copy = variables.clone();
+        assert!(Arc::ptr_eq(&variables.0, &copy.0));
+        copy.insert(String::from("x"), String::from("2"));
+        copy.remove("missing");
+        assert_eq!((&variables["x"][..], &copy["x"][..]), ("1", "2"));
+        variables.remove("x");
+        assert!(copy.contains_key("x"));
+    }
+
+    #[test]
+    fn variable_names() {
+        assert!(is_variable_name("_PATH2"));
+        assert!(!is_variable_name("2x"));
+        assert!(!is_variable_name("a/b"));
+        assert!(!is_variable_name(""));
+    }
+
+    #[test]
+    fn shift_arguments() {
+        let mut shell = Shell::new().unwrap();
+        let arguments = vec![String::from("a b"), String::from("-v"), String::from("file")];
+        shell.variables.insert_value(String::from("argv"), Value::list(arguments));
+        shell.parse("shift").unwrap();
+        assert_eq!(shell.variables.value("argv").unwrap().words(), vec!["-v", "file"]);
+        shell.parse("shift argv").unwrap();
+        assert_eq!(shell.variables["argv"], "file");
+        shell.parse("shift").unwrap();
+        assert_eq!(shell.variables["argv"], "");
+        assert!(shell.parse("shift").is_err());
+        shell.variables.insert(String::from("x"), String::from("-v file"));
+        shell.parse("shift x").unwrap();
+        assert_eq!(shell.variables.value("x").unwrap().words(), vec!["file"]);
+    }
+}